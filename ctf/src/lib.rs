@@ -15,22 +15,22 @@ pub mod shuffle;
 mod systems;
 
 fn setup_flag_entities(game: &mut AirmashGame) {
-  use std::time::Instant;
-
   use airmash::component::*;
 
   use crate::component::*;
   use crate::config::{BLUE_TEAM, RED_TEAM};
 
+  let start_time = game.start_time();
+
   game.world.spawn((
     Position(config::flag_home_pos(RED_TEAM)),
     Team(RED_TEAM),
     FlagCarrier(None),
     LastDrop {
       player: None,
-      time: Instant::now(),
+      time: start_time,
     },
-    LastReturnTime(Instant::now()),
+    LastReturnTime(start_time),
     IsFlag,
   ));
 
@@ -40,9 +40,9 @@ fn setup_flag_entities(game: &mut AirmashGame) {
     FlagCarrier(None),
     LastDrop {
       player: None,
-      time: Instant::now(),
+      time: start_time,
     },
-    LastReturnTime(Instant::now()),
+    LastReturnTime(start_time),
     IsFlag,
   ));
 }
@@ -54,5 +54,7 @@ pub fn setup_ctf_server(game: &mut AirmashGame) {
 
   setup_flag_entities(game);
   crate::resource::register_all(game);
+  crate::systems::register_commands(game);
+  crate::systems::register_snapshot(game);
   airmash::system::ctf::register_all(game);
 }
@@ -1,7 +1,8 @@
 use std::time::Duration;
 
+use airmash::cli::ServerCli;
 use airmash::util::PeriodicPowerupSpawner;
-use airmash::{AirmashGame, Vector2};
+use airmash::Vector2;
 
 fn set_default_var(name: &str, value: &str) {
   use std::env;
@@ -12,53 +13,18 @@ fn set_default_var(name: &str, value: &str) {
 }
 
 fn main() {
-  use std::env;
-
-  use airmash::resource::RegionName;
-  use clap::arg;
-
-  let matches = clap::Command::new("airmash-server-ctf")
-    .version(env!("CARGO_PKG_VERSION"))
-    .author("STEAMROLLER")
-    .about("Airmash CTF server")
-    .arg(arg!(-c --config [FILE] "Provides an alternate config file"))
-    .arg(arg!(--port   [PORT]    "Port that the server will listen on"))
-    .arg(arg!(--region [REGION]  "The region that this server belongs to"))
-    .get_matches();
+  let mut cli = ServerCli::parse(
+    clap::Command::new("airmash-server-ctf")
+      .version(env!("CARGO_PKG_VERSION"))
+      .author("STEAMROLLER")
+      .about("Airmash CTF server"),
+  );
 
   set_default_var("RUST_BACKTRACE", "1");
   set_default_var("RUST_LOG", "info");
   env_logger::init();
 
-  let bind_addr = format!("0.0.0.0:{}", matches.value_of("port").unwrap_or("3501"));
-
-  let mut game = AirmashGame::with_network(
-    bind_addr
-      .parse()
-      .expect("Unable to parse provided network port address"),
-  );
-  game.resources.insert(RegionName(
-    matches.value_of("region").unwrap_or("default").to_string(),
-  ));
-
-  let mut config = airmash::config::GamePrototype::default();
-  if let Some(path) = matches.value_of("config") {
-    let script = match std::fs::read_to_string(path) {
-      Ok(script) => script,
-      Err(e) => {
-        eprintln!("Unable to open config file. Error was {}", e);
-        std::process::exit(1);
-      }
-    };
-
-    config
-      .patch(&script)
-      .expect("Error while running config file");
-  }
-
-  game
-    .resources
-    .insert(airmash::resource::Config::new(config).unwrap());
+  let mut game = cli.init_game();
 
   airmash_server_ctf::setup_ctf_server(&mut game);
 
@@ -87,5 +53,5 @@ fn main() {
     Duration::from_secs(90),
   ));
 
-  game.run_until_shutdown();
+  cli.run(game);
 }
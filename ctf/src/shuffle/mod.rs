@@ -1,6 +1,8 @@
 use airmash::component::*;
+use airmash::resource::GameRng;
 use airmash::{AirmashGame, Entity};
 use rand::prelude::SliceRandom;
+use rand::Rng;
 
 use crate::config::{BLUE_TEAM, RED_TEAM};
 
@@ -28,7 +30,7 @@ pub fn alternating_score_shuffle(game: &mut AirmashGame) -> Vec<TeamChangeEntry>
     .map(|(player, score)| (player, score.0))
     .collect::<Vec<_>>();
 
-  let start = if rand::random() { 0 } else { 1 };
+  let start = game.resources.write::<GameRng>().gen_range(0..2);
   let teams = [BLUE_TEAM, RED_TEAM];
   players.sort_unstable_by_key(|p| p.1);
 
@@ -60,7 +62,7 @@ pub fn alternating_earnings_shuffle(game: &mut AirmashGame) -> Vec<TeamChangeEnt
     .map(|(player, score)| (player, score.0))
     .collect::<Vec<_>>();
 
-  let start = if rand::random() { 0 } else { 1 };
+  let start = game.resources.write::<GameRng>().gen_range(0..2);
   let teams = [BLUE_TEAM, RED_TEAM];
   players.sort_unstable_by_key(|p| p.1);
 
@@ -92,14 +94,16 @@ pub fn even_random_shuffle(game: &mut AirmashGame) -> Vec<TeamChangeEntry> {
     .map(|(player, _)| player)
     .collect::<Vec<_>>();
 
-  let teams = if rand::random() {
+  let mut rng = game.resources.write::<GameRng>();
+  let teams = if rng.gen() {
     [BLUE_TEAM, RED_TEAM]
   } else {
     [RED_TEAM, BLUE_TEAM]
   };
   let half = players.len() / 2;
 
-  players.shuffle(&mut rand::thread_rng());
+  players.shuffle(&mut *rng);
+  drop(rng);
 
   players
     .into_iter()
@@ -1,42 +1,52 @@
 use std::borrow::Cow;
 use std::time::Duration;
 
+use airmash::command::*;
 use airmash::component::*;
-use airmash::event::{PacketEvent, PlayerRespawn};
-use airmash::protocol::client::Command;
+use airmash::event::{BeforeRespawn, PlayerRespawn};
+use airmash::protocol::ErrorType;
 use airmash::AirmashGame;
 
-#[handler]
-fn switch_teams(event: &PacketEvent<Command>, game: &mut AirmashGame) {
-  use airmash::protocol::server::{CommandReply, Error, PlayerReteam, PlayerReteamPlayer};
-  use airmash::protocol::ErrorType;
+pub(super) fn register_commands(game: &mut AirmashGame) {
+  game.register_command(CommandSpec::new("switch", switch_teams).help("Switch to the other team"));
+  game.register_command(CommandSpec::new("drop", drop_flag).help("Drop the flag you are carrying"));
+}
 
-  use crate::config::{BLUE_TEAM, RED_TEAM};
+fn switch_teams(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  use airmash::protocol::server::{CommandReply, PlayerReteam, PlayerReteamPlayer};
 
-  if event.packet.com != "switch" {
-    return;
-  }
+  use crate::config::{BLUE_TEAM, RED_TEAM};
 
   let this_frame = game.this_frame();
-  let (team, name, &alive, &last_action, _) =
-    match game
-      .world
-      .query_one_mut::<(&mut Team, &Name, &IsAlive, &LastActionTime, &IsPlayer)>(event.entity)
-    {
-      Ok(query) => query,
-      Err(_) => return,
-    };
+  let (&alive, &last_action, _) = match game
+    .world
+    .query_one_mut::<(&IsAlive, &LastActionTime, &IsPlayer)>(ctx.player)
+  {
+    Ok(query) => query,
+    Err(_) => return Ok(()),
+  };
 
   if this_frame - last_action.0 < Duration::from_secs(2) {
-    game.send_to(
-      event.entity,
-      Error {
-        error: ErrorType::IdleRequiredBeforeRespawn,
-      },
-    );
-    return;
+    return Err(ErrorType::IdleRequiredBeforeRespawn.into());
+  }
+
+  // Switching teams respawns the player so don't switch if the respawn would
+  // be cancelled.
+  let before = game.dispatch_pre(BeforeRespawn {
+    player: ctx.player,
+    alive: alive.0,
+    pos: None,
+    cancelled: false,
+  });
+  if before.cancelled {
+    return Ok(());
   }
 
+  let (team, name) = match game.world.query_one_mut::<(&mut Team, &Name)>(ctx.player) {
+    Ok(query) => query,
+    Err(_) => return Ok(()),
+  };
+
   team.0 = match team.0 {
     RED_TEAM => BLUE_TEAM,
     BLUE_TEAM => RED_TEAM,
@@ -45,7 +55,7 @@ fn switch_teams(event: &PacketEvent<Command>, game: &mut AirmashGame) {
 
   let reteam = PlayerReteam {
     players: vec![PlayerReteamPlayer {
-      id: event.entity.id() as _,
+      id: ctx.player.id() as _,
       team: team.0,
     }],
   };
@@ -68,7 +78,15 @@ fn switch_teams(event: &PacketEvent<Command>, game: &mut AirmashGame) {
   game.send_to_all(reply);
 
   game.dispatch(PlayerRespawn {
-    player: event.entity,
+    player: ctx.player,
     alive: alive.0,
+    pos: before.pos,
   });
+
+  Ok(())
+}
+
+fn drop_flag(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  super::drop_carried_flags(ctx.player, game);
+  Ok(())
 }
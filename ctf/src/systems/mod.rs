@@ -1,5 +1,4 @@
-use airmash::event::{EventStealth, PacketEvent, PlayerKilled, PlayerLeave, PlayerRespawn};
-use airmash::protocol::client::Command;
+use airmash::event::{EventStealth, PlayerKilled, PlayerLeave, PlayerRespawn};
 use airmash::{AirmashGame, Entity};
 
 mod command;
@@ -10,6 +9,15 @@ mod on_game_start;
 mod on_player_join;
 mod on_player_leave;
 mod on_player_respawn;
+mod snapshot;
+
+pub(crate) fn register_commands(game: &mut AirmashGame) {
+  self::command::register_commands(game);
+}
+
+pub(crate) fn register_snapshot(game: &mut AirmashGame) {
+  self::snapshot::register_snapshot(game);
+}
 
 pub fn drop_carried_flags(player: Entity, game: &mut AirmashGame) {
   use airmash::component::IsPlayer;
@@ -59,10 +67,3 @@ fn drop_on_stealth(event: &EventStealth, game: &mut AirmashGame) {
 fn drop_on_respawn(event: &PlayerRespawn, game: &mut AirmashGame) {
   drop_carried_flags(event.player, game);
 }
-
-#[handler]
-fn drop_on_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
-  if event.packet.com == "drop" {
-    drop_carried_flags(event.entity, game);
-  }
-}
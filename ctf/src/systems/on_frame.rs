@@ -161,9 +161,24 @@ fn return_and_pickup_flags(game: &mut AirmashGame) {
   game.dispatch_many(events);
 }
 
+/// Keep the scores reported on the server status endpoint up to date.
+fn publish_scores(game: &mut AirmashGame) {
+  use airmash::resource::TeamScores;
+
+  let scores = *game.resources.read::<GameScores>();
+  let mut published = game.resources.write::<TeamScores>();
+
+  for (team, score) in [("red", scores.redteam), ("blue", scores.blueteam)] {
+    if published.get(team) != Some(&score.into()) {
+      published.insert(team.to_owned(), score.into());
+    }
+  }
+}
+
 #[handler]
 pub fn tick_updates(_: &Frame, game: &mut AirmashGame) {
   update_flag_positions(game);
   capture_flags(game);
   return_and_pickup_flags(game);
+  publish_scores(game);
 }
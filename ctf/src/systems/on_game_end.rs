@@ -45,35 +45,41 @@ fn shuffle_players(game: &mut AirmashGame) {
 fn schedule_tasks(_: &GameEndEvent, game: &mut AirmashGame) {
   let scheduler = game.resources.read::<TaskScheduler>().clone();
 
-  unsafe {
-    scheduler.spawn(move |mut game| async move {
-      display_message(&mut *game, "New game starting in 1 minute", 12);
+  scheduler
+    .task()
+    .name("ctf::game-start-countdown")
+    .spawn(move |mut game| async move {
+      game.with(|game| display_message(game, "New game starting in 1 minute", 12));
       game.sleep_for(Duration::from_secs(30)).await;
 
-      // Shuffle all players
-      shuffle_players(&mut game);
+      game.with(|game| {
+        // Shuffle all players
+        shuffle_players(game);
 
-      // Display countdown
-      display_message(&mut game, "Game starting in 30 seconds", 7);
+        // Display countdown
+        display_message(game, "Game starting in 30 seconds", 7);
+      });
       game.sleep_for(Duration::from_secs(20)).await;
-      display_message(&mut game, "Game starting in 10 seconds", 7);
+      game.with(|game| display_message(game, "Game starting in 10 seconds", 7));
       game.sleep_for(Duration::from_secs(5)).await;
-      display_message(&mut game, "Game starting in 5 seconds", 2);
+      game.with(|game| display_message(game, "Game starting in 5 seconds", 2));
       game.sleep_for(Duration::from_secs(1)).await;
-      display_message(&mut game, "Game starting in 4 seconds", 2);
+      game.with(|game| display_message(game, "Game starting in 4 seconds", 2));
       game.sleep_for(Duration::from_secs(1)).await;
-      display_message(&mut game, "Game starting in 3 seconds", 2);
+      game.with(|game| display_message(game, "Game starting in 3 seconds", 2));
       game.sleep_for(Duration::from_secs(1)).await;
-      display_message(&mut game, "Game starting in 2 seconds", 2);
+      game.with(|game| display_message(game, "Game starting in 2 seconds", 2));
       game.sleep_for(Duration::from_secs(1)).await;
-      display_message(&mut game, "Game starting in 1 second", 2);
+      game.with(|game| display_message(game, "Game starting in 1 second", 2));
       game.sleep_for(Duration::from_secs(1)).await;
-      display_message(&mut game, "Game starting!", 3);
 
-      // Emit game start event
-      game.dispatch(GameStartEvent);
+      game.with(|game| {
+        display_message(game, "Game starting!", 3);
+
+        // Emit game start event
+        game.dispatch(GameStartEvent);
+      });
     });
-  }
 }
 
 #[handler]
@@ -1,5 +1,4 @@
 use airmash::component::*;
-use airmash::event::*;
 use airmash::resource::GameConfig;
 use airmash::AirmashGame;
 use smallvec::SmallVec;
@@ -10,23 +9,18 @@ use crate::resource::*;
 
 #[handler]
 fn respawn_all_players(_: &GameStartEvent, game: &mut AirmashGame) {
-  let mut events = Vec::new();
-  let query = game
+  let players: Vec<_> = game
     .world
-    .query_mut::<(&IsSpectating, &IsAlive)>()
-    .with::<IsPlayer>();
-  for (player, (spec, alive)) in query {
-    if spec.0 {
-      continue;
-    }
-
-    events.push(PlayerRespawn {
-      player,
-      alive: alive.0,
-    });
+    .query_mut::<&IsSpectating>()
+    .with::<IsPlayer>()
+    .into_iter()
+    .filter(|(_, spec)| !spec.0)
+    .map(|(player, _)| player)
+    .collect();
+
+  for player in players {
+    game.respawn_player(player);
   }
-
-  game.dispatch_many(events);
 }
 
 #[handler(priority = airmash::priority::MEDIUM)]
@@ -1,8 +1,10 @@
 use std::cmp::Ordering;
 
 use airmash::component::*;
-use airmash::event::PlayerJoin;
-use airmash::{AirmashGame, Vector2};
+use airmash::event::{PlayerJoin, PlayerResume};
+use airmash::resource::GameRng;
+use airmash::{AirmashGame, Entity, Vector2};
+use rand::Rng;
 
 use crate::component::*;
 use crate::config;
@@ -12,6 +14,7 @@ use crate::resource::{CTFGameStats, GameScores};
 fn setup_team_and_pos(event: &PlayerJoin, game: &mut AirmashGame) {
   let stats = game.resources.read::<CTFGameStats>();
   let score = game.resources.read::<GameScores>();
+  let mut rng = game.resources.write::<GameRng>();
 
   let team = match stats.red_players.cmp(&stats.blue_players) {
     Ordering::Less => config::RED_TEAM,
@@ -19,14 +22,14 @@ fn setup_team_and_pos(event: &PlayerJoin, game: &mut AirmashGame) {
     Ordering::Equal => match score.redteam.cmp(&score.blueteam) {
       Ordering::Less => config::RED_TEAM,
       Ordering::Greater => config::BLUE_TEAM,
-      Ordering::Equal => match rand::random() {
+      Ordering::Equal => match rng.gen() {
         true => config::RED_TEAM,
         false => config::BLUE_TEAM,
       },
     },
   };
 
-  let offset = Vector2::new(rand::random::<f32>() - 0.5, rand::random::<f32>() - 0.5);
+  let offset = Vector2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5);
   let respawn = config::team_respawn_pos(team) + 400.0 * offset;
 
   let _ = game
@@ -36,6 +39,15 @@ fn setup_team_and_pos(event: &PlayerJoin, game: &mut AirmashGame) {
 
 #[handler]
 fn send_flag_position_on_join(event: &PlayerJoin, game: &mut AirmashGame) {
+  send_flag_positions(game, event.player);
+}
+
+#[handler]
+fn send_flag_position_on_resume(event: &PlayerResume, game: &mut AirmashGame) {
+  send_flag_positions(game, event.player);
+}
+
+fn send_flag_positions(game: &mut AirmashGame, player: Entity) {
   use airmash::protocol::server::GameFlag;
   use airmash::protocol::FlagUpdateType;
 
@@ -59,7 +71,7 @@ fn send_flag_position_on_join(event: &PlayerJoin, game: &mut AirmashGame) {
       blueteam: scores.blueteam,
       redteam: scores.redteam,
     };
-    game.send_to(event.player, packet);
+    game.send_to(player, packet);
   }
 }
 
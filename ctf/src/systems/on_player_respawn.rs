@@ -1,6 +1,8 @@
 use airmash::component::*;
 use airmash::event::PlayerRespawn;
+use airmash::resource::GameRng;
 use airmash::{AirmashGame, Vector2};
+use rand::Rng;
 
 use crate::config;
 
@@ -11,7 +13,8 @@ fn setup_team_and_pos(event: &PlayerRespawn, game: &mut AirmashGame) {
     Err(_) => return,
   };
 
-  let offset = Vector2::new(rand::random::<f32>() - 0.5, rand::random::<f32>() - 0.5);
+  let mut rng = game.resources.write::<GameRng>();
+  let offset = Vector2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5);
   let respawn = config::team_respawn_pos(team) + 400.0 * offset;
 
   let _ = game.world.insert_one(event.player, Position(respawn));
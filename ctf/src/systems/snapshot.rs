@@ -0,0 +1,75 @@
+use airmash::component::*;
+use airmash::{AirmashGame, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::component::{FlagCarrier, IsFlag};
+use crate::event::GameStartEvent;
+use crate::resource::{GameActive, GameScores};
+
+#[derive(Serialize, Deserialize)]
+struct FlagSnapshot {
+  team: u16,
+  pos: Vector2,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CtfSnapshot {
+  redteam: u8,
+  blueteam: u8,
+  active: bool,
+  flags: Vec<FlagSnapshot>,
+}
+
+pub(super) fn register_snapshot(game: &mut AirmashGame) {
+  game.register_snapshot_extension("ctf", save, restore);
+}
+
+fn save(game: &AirmashGame) -> serde_json::Value {
+  let scores = *game.resources.read::<GameScores>();
+  let mut query = game.world.query::<(&Team, &Position)>().with::<IsFlag>();
+
+  let snapshot = CtfSnapshot {
+    redteam: scores.redteam,
+    blueteam: scores.blueteam,
+    active: game.resources.read::<GameActive>().0,
+    flags: query
+      .iter()
+      .map(|(_, (team, pos))| FlagSnapshot {
+        team: team.0,
+        pos: pos.0,
+      })
+      .collect(),
+  };
+
+  serde_json::to_value(snapshot).unwrap_or_default()
+}
+
+fn restore(game: &mut AirmashGame, data: serde_json::Value) -> Result<(), String> {
+  let snapshot: CtfSnapshot = serde_json::from_value(data).map_err(|e| e.to_string())?;
+
+  *game.resources.write::<GameScores>() = GameScores {
+    redteam: snapshot.redteam,
+    blueteam: snapshot.blueteam,
+  };
+
+  // Players are not in the game until they reconnect so any flag that was
+  // being carried is dropped where its carrier was.
+  let query = game
+    .world
+    .query_mut::<(&Team, &mut Position, &mut FlagCarrier)>()
+    .with::<IsFlag>();
+  for (_, (team, pos, carrier)) in query {
+    if let Some(flag) = snapshot.flags.iter().find(|flag| flag.team == team.0) {
+      pos.0 = flag.pos;
+      carrier.0 = None;
+    }
+  }
+
+  // The countdown between games isn't saved so start the next game right
+  // away instead.
+  if !snapshot.active {
+    game.dispatch(GameStartEvent);
+  }
+
+  Ok(())
+}
@@ -1,8 +1,7 @@
 use std::time::Duration;
 
-use airmash::component::Team;
+use airmash::component::{AdminRole, Team};
 use airmash::protocol::{FlagUpdateType, ServerPacket};
-use airmash::resource::GameConfig;
 use airmash::test::TestGame;
 use airmash_server_ctf::config::{FLAG_NO_REGRAB_TIME, RED_TEAM};
 
@@ -14,8 +13,10 @@ fn player_with_flag_has_flagspeed_set() {
   let mut client = mock.open();
   let entity = client.login("test", &mut game);
 
-  game.resources.write::<GameConfig>().admin_enabled = true;
-  game.world.insert_one(entity, Team(RED_TEAM)).unwrap();
+  game
+    .world
+    .insert(entity, (Team(RED_TEAM), AdminRole::Owner))
+    .unwrap();
   game.run_for(FLAG_NO_REGRAB_TIME + Duration::from_secs(1));
 
   client.send_command("teleport", "0 blue-flag");
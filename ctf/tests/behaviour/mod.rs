@@ -1 +1,2 @@
 mod flags;
+mod snapshot;
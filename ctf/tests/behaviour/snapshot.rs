@@ -0,0 +1,55 @@
+use airmash::component::{IsPlayer, Position, Team};
+use airmash::test::TestGame;
+use airmash::Vector2;
+use airmash_server_ctf::component::IsFlag;
+use airmash_server_ctf::config::BLUE_TEAM;
+use airmash_server_ctf::resource::GameScores;
+
+#[test]
+fn scores_and_flags_are_restored() {
+  let (mut game, mut mock) = TestGame::new();
+  airmash_server_ctf::setup_ctf_server(&mut game);
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  game.run_once();
+
+  *game.resources.write::<GameScores>() = GameScores {
+    redteam: 2,
+    blueteam: 1,
+  };
+  let query = game
+    .world
+    .query_mut::<(&Team, &mut Position)>()
+    .with::<IsFlag>();
+  for (_, (team, pos)) in query {
+    if team.0 == BLUE_TEAM {
+      pos.0 = Vector2::new(100.0, 200.0);
+    }
+  }
+
+  let snapshot = game.snapshot();
+
+  let (mut game, _mock) = TestGame::new();
+  airmash_server_ctf::setup_ctf_server(&mut game);
+  game.run_once();
+  game.restore_snapshot(&snapshot).unwrap();
+  game.run_once();
+
+  let scores = *game.resources.read::<GameScores>();
+  assert_eq!(scores.redteam, 2);
+  assert_eq!(scores.blueteam, 1);
+
+  let mut query = game.world.query::<(&Team, &Position)>().with::<IsFlag>();
+  let (_, (_, pos)) = query
+    .iter()
+    .find(|(_, (team, _))| team.0 == BLUE_TEAM)
+    .unwrap();
+  assert_eq!(pos.0, Vector2::new(100.0, 200.0));
+  drop(query);
+
+  assert_eq!(
+    game.world.query::<()>().with::<IsPlayer>().iter().count(),
+    0
+  );
+}
@@ -0,0 +1,123 @@
+//! Serves a recorded demo over a websocket so that it can be watched with the
+//! regular airmash client.
+//!
+//! Each client that connects gets its own playback of the demo, starting once
+//! the client has sent its login packet. Anything else the client sends is
+//! ignored.
+
+#[macro_use]
+extern crate log;
+
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use airmash::network::Demo;
+use clap::arg;
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::{self, Message};
+
+fn set_default_var(name: &str, value: &str) {
+  if env::var_os(name).is_none() {
+    env::set_var(name, value);
+  }
+}
+
+#[tokio::main]
+async fn main() {
+  let matches = clap::Command::new("airmash-demo-server")
+    .version(env!("CARGO_PKG_VERSION"))
+    .author("STEAMROLLER")
+    .about("Airmash Demo Server")
+    .arg(arg!(<DEMO> "The demo file to play back"))
+    .arg(arg!(--port [PORT] "Port that the server will listen on"))
+    .get_matches();
+
+  set_default_var("RUST_LOG", "info");
+  env_logger::init();
+
+  let path = matches.value_of("DEMO").unwrap();
+  let demo = match Demo::load(path) {
+    Ok(demo) => Arc::new(demo),
+    Err(e) => {
+      eprintln!("Unable to load demo. Error was {}", e);
+      std::process::exit(1);
+    }
+  };
+
+  let bind_addr: SocketAddr = format!("0.0.0.0:{}", matches.value_of("port").unwrap_or("3501"))
+    .parse()
+    .expect("Unable to parse provided network port address");
+
+  let listener = match TcpListener::bind(bind_addr).await {
+    Ok(listener) => listener,
+    Err(e) => {
+      eprintln!("Unable to bind to {}. Error was {}", bind_addr, e);
+      std::process::exit(1);
+    }
+  };
+
+  info!(
+    "Serving {} ({} packets, {}s) on {}",
+    path,
+    demo.packets().len(),
+    demo.duration().as_secs(),
+    bind_addr
+  );
+
+  loop {
+    let (stream, addr) = match listener.accept().await {
+      Ok(conn) => conn,
+      Err(e) => {
+        warn!("Unable to accept connection: {}", e);
+        continue;
+      }
+    };
+
+    let demo = Arc::clone(&demo);
+    tokio::spawn(async move {
+      info!("Playing demo to {}", addr);
+
+      match play(stream, &demo).await {
+        Ok(()) => info!("Finished playing demo to {}", addr),
+        Err(e) => info!("Stopped playing demo to {}: {}", addr, e),
+      }
+    });
+  }
+}
+
+async fn play(stream: TcpStream, demo: &Demo) -> Result<(), tungstenite::Error> {
+  let ws = tokio_tungstenite::accept_async(stream).await?;
+  let (mut send, mut recv) = ws.split();
+
+  // Wait for the client to send its login packet before starting playback.
+  loop {
+    match recv.next().await {
+      Some(Ok(Message::Binary(_))) => break,
+      Some(Ok(_)) => continue,
+      Some(Err(e)) => return Err(e),
+      None => return Ok(()),
+    }
+  }
+
+  // Keep reading so that control frames are handled and a closed connection
+  // is noticed, but otherwise ignore whatever the client sends.
+  tokio::spawn(async move { while let Some(Ok(_)) = recv.next().await {} });
+
+  // Sleep until each packet's time relative to the start of playback instead
+  // of for each delay so that timing errors don't accumulate.
+  let mut time = Instant::now();
+  for packet in demo.packets() {
+    if !packet.delay.is_zero() {
+      time += packet.delay;
+      tokio::time::sleep_until(time).await;
+    }
+
+    send.send(Message::Binary(packet.data.clone())).await?;
+  }
+
+  send.close().await
+}
@@ -1,7 +1,9 @@
 use airmash::component::*;
 use airmash::event::{PlayerJoin, PlayerRespawn};
 use airmash::resource::collision::{LayerSpec, Terrain};
+use airmash::resource::GameRng;
 use airmash::{AirmashGame, Vector2};
+use rand::Rng;
 
 const SPAWN_TOP_RIGHT: Vector2 = Vector2::new(-1325.0, -4330.0);
 const SPAWN_SIZE: Vector2 = Vector2::new(3500.0, 2500.0);
@@ -9,12 +11,13 @@ const SPAWN_RADIUS: f32 = 100.0;
 
 pub fn select_spawn_position(game: &AirmashGame) -> Vector2 {
   let terrain = game.resources.read::<Terrain>();
+  let mut rng = game.resources.write::<GameRng>();
 
   loop {
     let pos = SPAWN_TOP_RIGHT
       + Vector2::new(
-        rand::random::<f32>() * SPAWN_SIZE.x,
-        rand::random::<f32>() * SPAWN_SIZE.y,
+        rng.gen::<f32>() * SPAWN_SIZE.x,
+        rng.gen::<f32>() * SPAWN_SIZE.y,
       );
 
     if !terrain.contains(pos, SPAWN_RADIUS, LayerSpec::None) {
@@ -1,8 +1,8 @@
 use proc_macro2::{Span, TokenStream};
 use proc_macro_crate::FoundCrate;
-use quote::quote;
+use quote::{quote, quote_spanned};
 use syn::parse::Parse;
-use syn::{parse_macro_input, parse_quote, Expr, Ident, ItemFn, Result};
+use syn::{parse_macro_input, parse_quote, Expr, FnArg, Ident, ItemFn, Result, Type};
 
 use crate::args::AttrArg;
 
@@ -65,18 +65,42 @@ fn impl_handler(item: ItemFn, args: MacroArgs) -> Result<TokenStream> {
     .map(|x| x.value)
     .unwrap_or_else(|| parse_quote! { 0 });
 
+  // Handlers that take the event by mutable reference are for pre-events.
+  let register = match item.sig.inputs.first() {
+    Some(FnArg::Typed(arg)) if is_mut_ref(&arg.ty) => quote! { register_pre_handler },
+    _ => quote! { register_handler },
+  };
+
+  // Use the span of the function name so that the recorded line is that of the
+  // function and not of the attribute.
+  let line = quote_spanned! { name.span() => line!() };
+
   Ok(quote! {
     #item
 
     const _: () = {
       const PRIORITY: i32 = #priority;
+      const NAME: &str = concat!(module_path!(), "::", stringify!(#name));
+      const LOCATION: #krate::_exports::SourceLocation = #krate::_exports::SourceLocation {
+        file: file!(),
+        line: #line,
+      };
 
       #[allow(non_upper_case_globals)]
       #[#krate::_exports::linkme::distributed_slice(#krate::_exports::AIRMASH_EVENT_HANDLERS)]
       #[linkme(crate = #krate::_exports::linkme)]
       static __: fn(&#krate::_exports::EventDispatcher) = |dispatch| {
-        dispatch.register_with_priority(PRIORITY, #name);
+        dispatch.#register(PRIORITY, NAME, LOCATION, #name);
       };
     };
   })
 }
+
+fn is_mut_ref(ty: &Type) -> bool {
+  match ty {
+    Type::Reference(reference) => reference.mutability.is_some(),
+    Type::Paren(paren) => is_mut_ref(&paren.elem),
+    Type::Group(group) => is_mut_ref(&group.elem),
+    _ => false,
+  }
+}
@@ -10,6 +10,12 @@ mod handler;
 /// event that the function is supposed to handle. Any struct should
 /// work here as long as it meets the requirements for the `Event` trait.
 ///
+/// The handler is registered under the full path of the function (e.g.
+/// `my_crate::handlers::my_first_handler`). This name can be used to disable or
+/// replace the handler later on. The file and line of the function are
+/// recorded along with it so that they show up when listing the handlers
+/// registered with the event dispatcher.
+///
 /// # Caveats
 /// Internally this macro uses the [`linkme`] crate. `linkme` has an
 /// [issue](https://github.com/dtolnay/linkme/issues/31) where if a module
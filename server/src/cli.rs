@@ -0,0 +1,234 @@
+//! Command-line options shared by all the server binaries.
+//!
+//! Each game mode binary builds its own [`clap::Command`] and hands it to
+//! [`ServerCli::parse`] which adds all the common options. The binary then uses
+//! [`ServerCli::init_game`] to create the game, sets up its game mode, and
+//! finally calls [`ServerCli::run`].
+//!
+//! ```ignore
+//! let mut cli = ServerCli::parse(clap::Command::new("my-server"));
+//! let mut game = cli.init_game();
+//! // ... register game mode handlers here
+//! cli.run(game);
+//! ```
+//!
+//! Any errors in the provided options are printed to stderr and cause the
+//! process to exit.
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use clap::{arg, ArgMatches, Command};
+
+use crate::network::{AllowedOrigins, IpCidr, ProxyConfig, ProxySettings, Replay, TlsConfig};
+use crate::resource::{
+  AdminTokens, BanList, Config, ConnectionLimits, Limits, OutputDirs, RegionName, WordFilter,
+};
+use crate::snapshot::Snapshot;
+use crate::AirmashGame;
+
+const DEFAULT_PORT: &str = "3501";
+
+/// Parsed command-line options for a server binary.
+pub struct ServerCli {
+  matches: ArgMatches,
+  replay: Option<Replay>,
+}
+
+impl ServerCli {
+  /// Add all the common options to `command` and parse the command line.
+  pub fn parse(command: Command<'static>) -> Self {
+    Self {
+      matches: Self::args(command).get_matches(),
+      replay: None,
+    }
+  }
+
+  /// Add all the common options to `command`.
+  pub fn args(command: Command<'static>) -> Command<'static> {
+    command
+      .arg(arg!(-c --config [FILE] "Provides an alternate config file"))
+      .arg(arg!(--port   [PORT]    "Port that the server will listen on"))
+      .arg(arg!(--region [REGION]  "The region that this server belongs to"))
+      .arg(arg!(--"admin-tokens" [FILE] "File containing tokens for admin authentication"))
+      .arg(arg!(--"ban-file" [FILE] "File in which the list of bans is stored"))
+      .arg(arg!(--"word-filter" [FILE] "File containing rules for filtering chat and names"))
+      .arg(arg!(--"tls-cert" [FILE] "PEM file containing the TLS certificate chain"))
+      .arg(arg!(--"tls-key" [FILE] "PEM file containing the TLS private key"))
+      .arg(arg!(--"trusted-proxies" [CIDRS] "Comma-separated address ranges of reverse proxies to trust"))
+      .arg(arg!(--"proxy-protocol" "Read client addresses from PROXY protocol headers sent by trusted proxies"))
+      .arg(arg!(--"forwarded-headers" "Read client addresses from X-Forwarded-For headers sent by trusted proxies"))
+      .arg(arg!(--"allowed-origins" [ORIGINS] "Comma-separated origins allowed to connect (e.g. https://*.airmash.online)"))
+      .arg(arg!(--"max-connections-per-ip" [COUNT] "Maximum number of open connections from a single IP"))
+      .arg(arg!(--"max-logins-per-ip" [COUNT] "Maximum number of logins from a single IP per minute"))
+      .arg(arg!(--"max-players" [COUNT] "Maximum number of players in the game at once"))
+      .arg(arg!(--"profile-dir" [DIR] "Directory that admins can write profiling reports to"))
+      .arg(arg!(--"demo-dir" [DIR] "Directory that admins can record demos to"))
+      .arg(arg!(--"snapshot-dir" [DIR] "Directory that the owner can save snapshots to"))
+      .arg(arg!(--record [FILE] "Record all inbound connection events to a file"))
+      .arg(arg!(--replay [FILE] "Play back a recorded session instead of listening on the network"))
+      .arg(arg!(--snapshot [FILE] "Restore the game from a snapshot at startup and save it there on shutdown"))
+  }
+
+  /// The parsed matches. Use this to read any options specific to the binary.
+  pub fn matches(&self) -> &ArgMatches {
+    &self.matches
+  }
+
+  /// Create the game and set up all the resources controlled by the common
+  /// options.
+  pub fn init_game(&mut self) -> AirmashGame {
+    let matches = &self.matches;
+
+    let bind_addr = format!(
+      "0.0.0.0:{}",
+      matches.value_of("port").unwrap_or(DEFAULT_PORT)
+    );
+    let bind_addr = bind_addr
+      .parse()
+      .expect("Unable to parse provided network port address");
+
+    self.replay = matches
+      .value_of("replay")
+      .map(|path| Replay::load(path).unwrap_or_else(|e| fail("load recorded session", e)));
+
+    let mut game = match (matches.value_of("tls-cert"), matches.value_of("tls-key")) {
+      _ if self.replay.is_some() => AirmashGame::with_test_defaults(),
+      (Some(cert), Some(key)) => match TlsConfig::load(cert, key) {
+        Ok(tls) => AirmashGame::with_tls_network(bind_addr, tls),
+        Err(e) => fail("load TLS certificate", e),
+      },
+      (None, None) => AirmashGame::with_network(bind_addr),
+      _ => {
+        eprintln!("Both --tls-cert and --tls-key must be provided to enable TLS");
+        std::process::exit(1);
+      }
+    };
+    game.resources.insert(RegionName(
+      matches.value_of("region").unwrap_or("default").to_string(),
+    ));
+
+    match matches.value_of("config") {
+      Some(path) => {
+        if let Err(e) = game.load_config(path) {
+          fail("load config file", e);
+        }
+
+        game.reload_config_on_signal();
+      }
+      None => {
+        game.resources.insert(Config::default());
+      }
+    }
+
+    if let Some(path) = matches.value_of("admin-tokens") {
+      let tokens = AdminTokens::load(path).unwrap_or_else(|e| fail("load admin tokens", e));
+      game.resources.insert(tokens);
+    }
+
+    if let Some(path) = matches.value_of("ban-file") {
+      if let Err(e) = game.resources.read::<BanList>().load(path) {
+        fail("load ban list", e);
+      }
+    }
+
+    let limits = Limits {
+      connections_per_ip: parse_limit(matches, "max-connections-per-ip"),
+      logins_per_ip: parse_limit(matches, "max-logins-per-ip"),
+      max_players: parse_limit(matches, "max-players"),
+    };
+    game.resources.read::<ConnectionLimits>().set(limits);
+
+    let proxy_protocol = matches.is_present("proxy-protocol");
+    let forwarded_headers = matches.is_present("forwarded-headers");
+    let trusted = match matches.value_of("trusted-proxies") {
+      Some(list) => IpCidr::parse_list(list).unwrap_or_else(|e| fail("parse trusted proxies", e)),
+      None => Vec::new(),
+    };
+    if (proxy_protocol || forwarded_headers) && trusted.is_empty() {
+      eprintln!(
+        "--trusted-proxies must be provided to use --proxy-protocol or --forwarded-headers"
+      );
+      std::process::exit(1);
+    }
+    game.resources.read::<ProxyConfig>().set(ProxySettings {
+      proxy_protocol,
+      forwarded_headers,
+      trusted,
+    });
+
+    if let Some(origins) = matches.value_of("allowed-origins") {
+      game
+        .resources
+        .read::<AllowedOrigins>()
+        .set(origins.split(','));
+    }
+
+    game.resources.insert(OutputDirs {
+      profiles: matches.value_of("profile-dir").map(PathBuf::from),
+      demos: matches.value_of("demo-dir").map(PathBuf::from),
+      snapshots: matches.value_of("snapshot-dir").map(PathBuf::from),
+    });
+
+    if let Some(path) = matches.value_of("word-filter") {
+      let filter = WordFilter::load(path).unwrap_or_else(|e| fail("load word filter", e));
+      game.resources.insert(filter);
+    }
+
+    game
+  }
+
+  /// Start recording and restore the snapshot (if requested), run the game
+  /// until it shuts down, and then save the snapshot.
+  ///
+  /// This should be called after the game mode has been set up.
+  pub fn run(self, mut game: AirmashGame) {
+    let matches = &self.matches;
+
+    if let Some(path) = matches.value_of("record") {
+      if let Err(e) = game.record_session(path) {
+        fail("start recording", e);
+      }
+    }
+
+    let snapshot = matches.value_of("snapshot");
+    if let Some(path) = snapshot {
+      if Path::new(path).exists() {
+        let restored = Snapshot::load(path)
+          .map_err(|e| e.to_string())
+          .and_then(|snapshot| game.restore_snapshot(&snapshot));
+        if let Err(e) = restored {
+          fail("restore snapshot", e);
+        }
+      }
+
+      game.shutdown_on_signal();
+    }
+
+    match self.replay {
+      Some(replay) => replay.run(&mut game),
+      None => game.run_until_shutdown(),
+    }
+
+    if let Some(path) = snapshot {
+      if let Err(e) = game.save_snapshot(path) {
+        fail("save snapshot", e);
+      }
+    }
+  }
+}
+
+fn fail(action: &str, error: impl Display) -> ! {
+  eprintln!("Unable to {}. Error was {}", action, error);
+  std::process::exit(1);
+}
+
+fn parse_limit(matches: &ArgMatches, name: &str) -> Option<u32> {
+  matches.value_of(name).map(|value| match value.parse() {
+    Ok(value) => value,
+    Err(e) => {
+      eprintln!("Invalid value for --{}. Error was {}", name, e);
+      std::process::exit(1);
+    }
+  })
+}
@@ -0,0 +1,483 @@
+//! Registry for commands sent by players.
+//!
+//! Players send commands via the [`Command`] packet. Instead of having every
+//! command handler listen to every [`Command`] packet and parse its arguments
+//! by hand, commands are registered with the [`CommandRegistry`] resource along
+//! with their arguments, the [`AdminRole`] required to use them, and some help
+//! text. The registry then takes care of finding the right command, checking
+//! permissions, parsing arguments, and reporting errors back to the player.
+//!
+//! A `help` command which lists all commands that a player is allowed to use
+//! is registered by default.
+//!
+//! # Example
+//! ```
+//! # use airmash::AirmashGame;
+//! use airmash::command::{ArgType, CommandSpec};
+//!
+//! let mut game = AirmashGame::with_test_defaults();
+//! game.register_command(
+//!   CommandSpec::new("echo", |ctx, game| {
+//!     let text = ctx.args.string("text").unwrap_or("").to_owned();
+//!     ctx.reply(game, text);
+//!     Ok(())
+//!   })
+//!   .rest("text")
+//!   .help("Print some text back to the console"),
+//! );
+//! ```
+//!
+//! [`Command`]: crate::protocol::client::Command
+//! [`AdminRole`]: crate::component::AdminRole
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::Instant;
+
+use bstr::{BString, ByteSlice};
+use hecs::Entity;
+
+use crate::component::{AdminRole, IsPlayer};
+use crate::event::PacketEvent;
+use crate::network::ConnectionId;
+use crate::protocol::client::Command;
+use crate::protocol::server::{CommandReply, Error};
+use crate::protocol::{CommandReplyType, ErrorType};
+use crate::AirmashGame;
+
+/// The type of a command argument.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArgType {
+  /// A signed integer.
+  Integer,
+  /// A floating-point number.
+  Number,
+  /// A single word.
+  String,
+  /// The id of a player that is currently in the game.
+  Player,
+}
+
+/// A parsed command argument.
+#[derive(Clone, Debug)]
+pub enum ArgValue {
+  Integer(i64),
+  Number(f32),
+  String(String),
+  Player(Entity),
+}
+
+#[derive(Clone, Debug)]
+struct ArgSpec {
+  name: &'static str,
+  ty: ArgType,
+  optional: bool,
+}
+
+/// Parsed arguments for a command.
+#[derive(Clone, Debug, Default)]
+pub struct CommandArgs {
+  values: Vec<(&'static str, ArgValue)>,
+}
+
+impl CommandArgs {
+  /// Get the value of an argument. Returns `None` if an optional argument was
+  /// not provided.
+  pub fn get(&self, name: &str) -> Option<&ArgValue> {
+    self
+      .values
+      .iter()
+      .find(|(arg, _)| *arg == name)
+      .map(|(_, value)| value)
+  }
+
+  pub fn integer(&self, name: &str) -> Option<i64> {
+    match self.get(name)? {
+      ArgValue::Integer(value) => Some(*value),
+      _ => None,
+    }
+  }
+
+  pub fn number(&self, name: &str) -> Option<f32> {
+    match self.get(name)? {
+      ArgValue::Number(value) => Some(*value),
+      ArgValue::Integer(value) => Some(*value as f32),
+      _ => None,
+    }
+  }
+
+  pub fn string(&self, name: &str) -> Option<&str> {
+    match self.get(name)? {
+      ArgValue::String(value) => Some(value),
+      _ => None,
+    }
+  }
+
+  pub fn player(&self, name: &str) -> Option<Entity> {
+    match self.get(name)? {
+      ArgValue::Player(value) => Some(*value),
+      _ => None,
+    }
+  }
+}
+
+/// Context passed to a command handler.
+#[derive(Clone, Debug)]
+pub struct CommandContext {
+  /// The player that sent the command.
+  pub player: Entity,
+  /// The connection that the command was received on.
+  pub conn: ConnectionId,
+  /// The time at which the command was received.
+  pub time: Instant,
+  /// The parsed arguments of the command.
+  pub args: CommandArgs,
+}
+
+impl CommandContext {
+  /// Reply to the player that sent the command with a message in their
+  /// console.
+  pub fn reply(&self, game: &AirmashGame, text: impl Into<BString>) {
+    game.send_to(
+      self.player,
+      CommandReply {
+        ty: CommandReplyType::ShowInConsole,
+        text: text.into(),
+      },
+    );
+  }
+}
+
+/// Error returned by a command handler.
+#[derive(Clone, Debug)]
+pub enum CommandError {
+  /// Show a message in the player's console.
+  Message(Cow<'static, str>),
+  /// Send an error packet to the player.
+  Error(ErrorType),
+  /// Silently ignore the command.
+  Ignore,
+}
+
+impl From<&'static str> for CommandError {
+  fn from(msg: &'static str) -> Self {
+    Self::Message(msg.into())
+  }
+}
+
+impl From<String> for CommandError {
+  fn from(msg: String) -> Self {
+    Self::Message(msg.into())
+  }
+}
+
+impl From<ErrorType> for CommandError {
+  fn from(error: ErrorType) -> Self {
+    Self::Error(error)
+  }
+}
+
+pub type CommandResult = Result<(), CommandError>;
+
+type CommandHandler = dyn Fn(&CommandContext, &mut AirmashGame) -> CommandResult;
+
+/// Description of a command to be registered with the [`CommandRegistry`].
+pub struct CommandSpec {
+  name: Cow<'static, str>,
+  args: Vec<ArgSpec>,
+  rest: Option<&'static str>,
+  permission: Option<AdminRole>,
+  help: Cow<'static, str>,
+  handler: Box<CommandHandler>,
+}
+
+impl CommandSpec {
+  /// Create a new command with no arguments which can be used by everyone.
+  pub fn new<F>(name: impl Into<Cow<'static, str>>, handler: F) -> Self
+  where
+    F: Fn(&CommandContext, &mut AirmashGame) -> CommandResult + 'static,
+  {
+    Self {
+      name: name.into(),
+      args: Vec::new(),
+      rest: None,
+      permission: None,
+      help: Cow::Borrowed(""),
+      handler: Box::new(handler),
+    }
+  }
+
+  /// Add a required argument.
+  ///
+  /// # Panics
+  /// Panics if an optional argument has already been added.
+  pub fn arg(mut self, name: &'static str, ty: ArgType) -> Self {
+    assert!(
+      self.args.iter().all(|arg| !arg.optional),
+      "required argument `{}` of command `{}` follows an optional argument",
+      name,
+      self.name
+    );
+
+    self.args.push(ArgSpec {
+      name,
+      ty,
+      optional: false,
+    });
+    self
+  }
+
+  /// Add an optional argument. Optional arguments must come after all the
+  /// required ones.
+  pub fn optional(mut self, name: &'static str, ty: ArgType) -> Self {
+    self.args.push(ArgSpec {
+      name,
+      ty,
+      optional: true,
+    });
+    self
+  }
+
+  /// Collect all remaining text after the other arguments into a single string
+  /// argument. If there is no remaining text then the argument is not set.
+  pub fn rest(mut self, name: &'static str) -> Self {
+    self.rest = Some(name);
+    self
+  }
+
+  /// Require that players have at least `role` in order to use this command.
+  ///
+  /// Players without the required role will not be able to see the command in
+  /// `help` and the server will ignore it if they attempt to use it.
+  pub fn permission(mut self, role: AdminRole) -> Self {
+    self.permission = Some(role);
+    self
+  }
+
+  /// Set the help text that is shown for this command.
+  pub fn help(mut self, help: impl Into<Cow<'static, str>>) -> Self {
+    self.help = help.into();
+    self
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// The usage string of this command (e.g. `/kick <player>`).
+  pub fn usage(&self) -> String {
+    let mut usage = format!("/{}", self.name);
+    for arg in &self.args {
+      if arg.optional {
+        write!(usage, " [{}]", arg.name).unwrap();
+      } else {
+        write!(usage, " <{}>", arg.name).unwrap();
+      }
+    }
+    if let Some(rest) = self.rest {
+      write!(usage, " [{}...]", rest).unwrap();
+    }
+
+    usage
+  }
+
+  /// Whether `player` is allowed to use this command.
+  pub fn permitted(&self, game: &AirmashGame, player: Entity) -> bool {
+    let required = match self.permission {
+      Some(required) => required,
+      None => return true,
+    };
+
+    match game.world.get::<AdminRole>(player) {
+      Ok(role) => *role >= required,
+      Err(_) => false,
+    }
+  }
+
+  fn parse_args(&self, game: &AirmashGame, data: &[u8]) -> Result<CommandArgs, String> {
+    let data = data.to_str_lossy();
+    let mut remaining = data.trim();
+    let mut args = CommandArgs::default();
+
+    for spec in &self.args {
+      let (word, rest) = match remaining.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim_start()),
+        None => (remaining, ""),
+      };
+
+      if word.is_empty() {
+        if spec.optional {
+          break;
+        }
+        return Err(format!("Missing argument <{}>", spec.name));
+      }
+
+      let value = match spec.ty {
+        ArgType::Integer => word
+          .parse()
+          .map(ArgValue::Integer)
+          .map_err(|_| format!("<{}> must be an integer", spec.name))?,
+        ArgType::Number => word
+          .parse()
+          .ok()
+          .filter(|x: &f32| x.is_finite())
+          .map(ArgValue::Number)
+          .ok_or_else(|| format!("<{}> must be a number", spec.name))?,
+        ArgType::String => ArgValue::String(word.to_owned()),
+        ArgType::Player => {
+          let id: u16 = word
+            .parse()
+            .map_err(|_| format!("<{}> must be a player ID", spec.name))?;
+
+          game
+            .find_entity_by_id(id)
+            .filter(|&ent| game.world.get::<IsPlayer>(ent).is_ok())
+            .map(ArgValue::Player)
+            .ok_or_else(|| format!("Unknown player {}", id))?
+        }
+      };
+
+      args.values.push((spec.name, value));
+      remaining = rest;
+    }
+
+    match self.rest {
+      Some(name) if !remaining.is_empty() => {
+        args
+          .values
+          .push((name, ArgValue::String(remaining.to_owned())));
+      }
+      None if !remaining.is_empty() => return Err("Too many arguments".to_owned()),
+      _ => (),
+    }
+
+    Ok(args)
+  }
+}
+
+/// Registry of all commands that players can use.
+///
+/// See the [module docs](crate::command) for details.
+#[derive(Default)]
+pub struct CommandRegistry {
+  commands: BTreeMap<String, Rc<CommandSpec>>,
+}
+
+impl CommandRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Register a command. If there was already a command with the same name
+  /// then it will be replaced.
+  pub fn register(&mut self, command: CommandSpec) {
+    self
+      .commands
+      .insert(command.name.to_string(), Rc::new(command));
+  }
+
+  /// Remove the command with the provided name. Returns whether there was such
+  /// a command.
+  pub fn unregister(&mut self, name: &str) -> bool {
+    self.commands.remove(name).is_some()
+  }
+
+  /// Get the command with the provided name.
+  pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+    self.commands.get(name).map(|command| &**command)
+  }
+
+  /// Iterate over all registered commands in alphabetical order.
+  pub fn iter(&self) -> impl Iterator<Item = &CommandSpec> {
+    self.commands.values().map(|command| &**command)
+  }
+}
+
+impl AirmashGame {
+  /// Register a command with the [`CommandRegistry`].
+  ///
+  /// See the [`command`](crate::command) module docs for details.
+  pub fn register_command(&mut self, command: CommandSpec) {
+    self.resources.write::<CommandRegistry>().register(command);
+  }
+}
+
+pub(crate) fn register_builtin_commands(registry: &mut CommandRegistry) {
+  registry.register(
+    CommandSpec::new("help", help)
+      .optional("command", ArgType::String)
+      .help("List available commands or show help for a single command"),
+  );
+}
+
+fn help(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  let registry = game.resources.read::<CommandRegistry>();
+
+  let text = match ctx.args.string("command") {
+    Some(name) => {
+      let name = name.trim_start_matches('/');
+      let command = registry
+        .get(name)
+        .filter(|command| command.permitted(game, ctx.player))
+        .ok_or_else(|| format!("Unknown command /{}", name))?;
+
+      format!("{}\n  {}", command.usage(), command.help)
+    }
+    None => {
+      let mut text = "Available commands:".to_owned();
+      for command in registry.iter() {
+        if command.permitted(game, ctx.player) {
+          write!(text, "\n  {} - {}", command.usage(), command.help).unwrap();
+        }
+      }
+      text
+    }
+  };
+
+  drop(registry);
+  ctx.reply(game, text);
+
+  Ok(())
+}
+
+#[handler]
+fn dispatch_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
+  let command = {
+    let registry = game.resources.read::<CommandRegistry>();
+    let name = event.packet.com.to_str_lossy();
+
+    match registry.commands.get(&*name) {
+      Some(command) => Rc::clone(command),
+      None => return,
+    }
+  };
+
+  if !command.permitted(game, event.entity) {
+    return;
+  }
+
+  let ctx = CommandContext {
+    player: event.entity,
+    conn: event.conn,
+    time: event.time,
+    args: CommandArgs::default(),
+  };
+
+  let args = match command.parse_args(game, &event.packet.data) {
+    Ok(args) => args,
+    Err(e) => {
+      ctx.reply(game, format!("{}\nUsage: {}", e, command.usage()));
+      return;
+    }
+  };
+  let ctx = CommandContext { args, ..ctx };
+
+  match (command.handler)(&ctx, game) {
+    Ok(()) => (),
+    Err(CommandError::Message(msg)) => ctx.reply(game, msg.into_owned()),
+    Err(CommandError::Error(error)) => game.send_to(ctx.player, Error { error }),
+    Err(CommandError::Ignore) => (),
+  }
+}
@@ -0,0 +1,115 @@
+use std::time::{Duration, Instant};
+
+use bstr::{BStr, BString};
+
+use crate::resource::ChatLimits;
+
+/// The result of checking whether a player is allowed to send a message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChatCheck {
+  /// The message is allowed.
+  Allowed,
+  /// The message was dropped because the player is sending messages too
+  /// quickly or is repeating themselves.
+  Throttled,
+  /// The message was dropped and the player has now been muted for the given
+  /// duration.
+  Muted(Duration),
+  /// The message was dropped because the player is currently muted.
+  StillMuted,
+}
+
+/// Per-player chat rate limiting state.
+///
+/// See [`ChatLimits`] for a description of how the rate limiting works.
+#[derive(Clone, Debug)]
+pub struct ChatThrottle {
+  tokens: f32,
+  last_refill: Instant,
+
+  last_message: BString,
+  last_message_time: Instant,
+  repeats: u32,
+
+  violations: u32,
+  last_violation: Instant,
+
+  mutes: u32,
+  muted_until: Option<Instant>,
+}
+
+impl ChatThrottle {
+  pub fn new(now: Instant) -> Self {
+    Self {
+      // This gets clamped to the burst limit when the first message is sent.
+      tokens: f32::INFINITY,
+      last_refill: now,
+      last_message: BString::default(),
+      last_message_time: now,
+      repeats: 0,
+      violations: 0,
+      last_violation: now,
+      mutes: 0,
+      muted_until: None,
+    }
+  }
+
+  /// The time at which the player's current auto-mute expires, if they are
+  /// currently muted.
+  pub fn muted_until(&self, now: Instant) -> Option<Instant> {
+    self.muted_until.filter(|&until| until > now)
+  }
+
+  /// Record an attempt to send `text` at time `now` and check whether the
+  /// message should be allowed through.
+  pub fn check(&mut self, limits: &ChatLimits, now: Instant, text: &BStr) -> ChatCheck {
+    if self.muted_until(now).is_some() {
+      return ChatCheck::StillMuted;
+    }
+
+    let elapsed = now.saturating_duration_since(self.last_refill);
+    self.tokens = (self.tokens + elapsed.as_secs_f32() * limits.rate).min(limits.burst as f32);
+    self.last_refill = now;
+
+    let recent = now.saturating_duration_since(self.last_message_time) < limits.repeat_window;
+    if recent && self.last_message == text {
+      self.repeats += 1;
+    } else {
+      self.repeats = 0;
+      self.last_message = text.to_owned();
+    }
+    self.last_message_time = now;
+
+    if self.tokens < 1.0 || self.repeats >= limits.max_repeats {
+      return self.violation(limits, now);
+    }
+
+    self.tokens -= 1.0;
+    ChatCheck::Allowed
+  }
+
+  fn violation(&mut self, limits: &ChatLimits, now: Instant) -> ChatCheck {
+    if now.saturating_duration_since(self.last_violation) > limits.violation_timeout {
+      self.violations = 0;
+    }
+
+    self.violations += 1;
+    self.last_violation = now;
+
+    if self.violations < limits.violations_before_mute {
+      return ChatCheck::Throttled;
+    }
+
+    let index = (self.mutes as usize).min(limits.mute_durations.len().saturating_sub(1));
+    let duration = match limits.mute_durations.get(index) {
+      Some(&duration) => duration,
+      None => return ChatCheck::Throttled,
+    };
+
+    self.violations = 0;
+    self.mutes += 1;
+    self.muted_until = Some(now + duration);
+
+    ChatCheck::Muted(duration)
+  }
+}
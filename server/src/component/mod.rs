@@ -1,5 +1,7 @@
 //! Components used within airmash.
 
+use std::fmt;
+use std::str::FromStr;
 use std::time::{Duration, Instant};
 
 use bstr::BString;
@@ -8,9 +10,11 @@ use uuid::Uuid;
 
 use crate::Vector2;
 
+mod chat;
 mod effect;
 mod keystate;
 
+pub use self::chat::{ChatCheck, ChatThrottle};
 pub use self::effect::Effects;
 pub use self::keystate::KeyState;
 pub use crate::protocol::{FlagCode, MobType, PlaneType, PowerupType};
@@ -104,6 +108,12 @@ def_wrappers! {
   /// The time at which a zombie entity will be deleted.
   pub type Expiry = Instant;
 
+  /// The time at which a player whose connection has dropped will leave the
+  /// game unless they reconnect before then.
+  ///
+  /// This is only present on players that are currently disconnected.
+  pub type DisconnectDeadline = Instant;
+
   /// The time at which a missile spawned.
   pub type SpawnTime = Instant;
   /// The time at which a player joined.
@@ -124,9 +134,19 @@ def_wrappers! {
   /// Whether a player is currently allowed to respawn.
   pub type RespawnAllowed = bool;
 
+  /// Whether a player has been muted and is no longer allowed to chat.
+  pub type IsMuted = bool;
+
   /// A unique ID corresponding to the current player connection ID.
   pub type Session = Uuid;
 
+  /// The session token that the player supplied when they logged in.
+  ///
+  /// Unlike [`Session`] this is chosen by the client so it stays the same
+  /// when a player reconnects. Players without an account send `"none"`.
+  ##[nocopy]
+  pub type LoginSession = BString;
+
   /// The player that currently owns a missile.
   ///
   /// Normally this corresponds to the player that fired the missile but if
@@ -224,6 +244,46 @@ impl MissileFiringSide {
   }
 }
 
+/// The administrative role that a player has authenticated as.
+///
+/// Players without this component have no admin permissions. Roles are ordered
+/// so that each role has all the permissions of the roles below it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AdminRole {
+  Moderator,
+  Admin,
+  Owner,
+}
+
+impl AdminRole {
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Moderator => "moderator",
+      Self::Admin => "admin",
+      Self::Owner => "owner",
+    }
+  }
+}
+
+impl FromStr for AdminRole {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Ok(match s {
+      "moderator" => Self::Moderator,
+      "admin" => Self::Admin,
+      "owner" => Self::Owner,
+      _ => return Err(()),
+    })
+  }
+}
+
+impl fmt::Display for AdminRole {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.name())
+  }
+}
+
 macro_rules! mint_wrapper {
   ($ty:ty => $mint:ty) => {
     impl<'a> From<&'a $ty> for $mint {
@@ -46,12 +46,15 @@ pub(crate) fn build_default_player(
     .add(IsAlive(true))
     .add(IsSpectating(false))
     .add(Session(Uuid::new_v4()))
+    .add(LoginSession(login.session.clone()))
     .add(KeyState::default())
     .add(LastFireTime(start_time))
     .add(LastSpecialTime(start_time))
     .add(LastActionTime(start_time))
     .add(SpecialActive(false))
     .add(RespawnAllowed(true))
+    .add(IsMuted(false))
+    .add(ChatThrottle::new(this_frame))
     .add(JoinTime(this_frame))
     .add(Spectating::default())
     .add(PlayerPing(Duration::ZERO))
@@ -1,10 +1,14 @@
-use std::cell::RefCell;
+use std::any::TypeId;
+use std::cell::{Cell, RefCell, RefMut};
 use std::collections::VecDeque;
+use std::fmt;
 use std::rc::Rc;
+use std::time::Instant;
 
 use anymap::AnyMap;
 use linkme::distributed_slice;
 
+use crate::resource::Profiler;
 use crate::AirmashGame;
 
 #[distributed_slice]
@@ -34,18 +38,128 @@ where
   }
 }
 
-struct HandlerWithPriority<E>(i32, Box<dyn EventHandler<E>>);
-type HandlerList<E> = Vec<HandlerWithPriority<E>>;
+/// Trait for a handler of a pre-event.
+///
+/// Pre-events are dispatched before the server performs an action and their
+/// handlers get mutable access to the event so that they can change how the
+/// action is performed or cancel it outright. See the [`event`] module docs for
+/// details.
+///
+/// [`event`]: crate::event
+pub trait PreEventHandler<E: Event>: 'static {
+  fn on_event(&mut self, event: &mut E, game: &mut AirmashGame);
+}
+
+impl<F, E> PreEventHandler<E> for F
+where
+  F: FnMut(&mut E, &mut AirmashGame) + 'static,
+  E: Event,
+{
+  fn on_event(&mut self, event: &mut E, world: &mut AirmashGame) {
+    self(event, world);
+  }
+}
+
+/// The place in the source code where an event handler was registered.
+///
+/// For handlers registered via `#[handler]` this is the location of the handler
+/// function itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+  pub file: &'static str,
+  pub line: u32,
+}
+
+impl SourceLocation {
+  /// The location of the caller of the current function.
+  #[track_caller]
+  pub fn caller() -> Self {
+    let location = std::panic::Location::caller();
+
+    Self {
+      file: location.file(),
+      line: location.line(),
+    }
+  }
+}
+
+impl fmt::Display for SourceLocation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}:{}", self.file, self.line)
+  }
+}
+
+/// Information about a registered event handler.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HandlerInfo {
+  /// The name of the handler.
+  ///
+  /// For handlers registered via `#[handler]` this is the full path of the
+  /// function (e.g. `airmash::system::handler::on_player_killed::launch_respawn_task`).
+  pub name: &'static str,
+  /// The type name of the event that the handler is registered for.
+  pub event: &'static str,
+  pub priority: i32,
+  /// Whether this is a handler for a pre-event.
+  pub pre: bool,
+  /// Where the handler was registered.
+  pub location: SourceLocation,
+}
+
+/// An entry in the index of all registered handlers.
+struct IndexEntry {
+  info: HandlerInfo,
+  event: TypeId,
+  /// Removes all handlers with the provided name from the list for this
+  /// entry's event type.
+  remove: fn(&BaseEventDispatcher, &str),
+}
+
+/// An event handler along with its priority and its name.
+struct HandlerWithPriority<H: ?Sized>(i32, &'static str, Box<H>);
+type HandlerList<E> = Vec<HandlerWithPriority<dyn EventHandler<E>>>;
+type PreHandlerList<E> = Rc<RefCell<Vec<HandlerWithPriority<dyn PreEventHandler<E>>>>>;
+type WaiterList<E> = Vec<Box<dyn FnMut(&E) -> bool>>;
+
+/// How an event came to be dispatched. This is only used for tracing.
+#[derive(Copy, Clone, Debug)]
+enum Origin {
+  Direct,
+  Queued,
+  Pre,
+}
+
+impl fmt::Display for Origin {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match self {
+      Self::Direct => "event",
+      Self::Queued => "queued event",
+      Self::Pre => "pre-event",
+    })
+  }
+}
 
 trait DelayedEvent {
-  fn dispatch(&mut self, world: &mut AirmashGame, map: &mut AnyMap);
+  fn dispatch(
+    &mut self,
+    dispatcher: &BaseEventDispatcher,
+    world: &mut AirmashGame,
+    map: &mut AnyMap,
+    trace: Option<Origin>,
+  );
 }
 
 struct ConcreteDelayedEvent<E>(Option<E>);
 
 impl<E: Event> DelayedEvent for ConcreteDelayedEvent<E> {
-  fn dispatch(&mut self, world: &mut AirmashGame, map: &mut AnyMap) {
-    BaseEventDispatcher::dispatch_raw(self.0.take().unwrap(), world, map);
+  fn dispatch(
+    &mut self,
+    dispatcher: &BaseEventDispatcher,
+    world: &mut AirmashGame,
+    map: &mut AnyMap,
+    trace: Option<Origin>,
+  ) {
+    dispatcher.dispatch_raw(self.0.take().unwrap(), world, map, trace);
   }
 }
 
@@ -56,42 +170,299 @@ impl<E: Event> DelayedEvent for ConcreteDelayedEvent<E> {
 #[allow(clippy::type_complexity)]
 struct BaseEventDispatcher {
   lists: RefCell<AnyMap>,
+  /// Handlers for pre-events.
+  ///
+  /// These are kept separately from the handlers for regular events since
+  /// pre-events are dispatched immediately even when another event is
+  /// currently being dispatched. Each list is borrowed independently so that
+  /// handlers can dispatch other pre-events.
+  pre_lists: RefCell<AnyMap>,
+  /// Index of every registered handler so that handlers can be found by name
+  /// without knowing which event they handle.
+  index: RefCell<Vec<IndexEntry>>,
+  /// Callbacks for tasks that are waiting on an event. These are called after
+  /// all the handlers for the event have run and are removed once they return
+  /// true.
+  waiters: RefCell<AnyMap>,
   queue: RefCell<VecDeque<Box<dyn DelayedEvent>>>,
   /// Cleanup tasks that need to be done after all the derivative events have
   /// been executed.
   ///
   /// This is not exposed outside of this crate.
   cleanup: RefCell<VecDeque<Box<dyn FnMut(&mut AirmashGame)>>>,
+  /// Whether every dispatched event should be logged along with the time taken
+  /// by each of its handlers.
+  tracing: Cell<bool>,
 }
 
 impl BaseEventDispatcher {
   pub fn new() -> Self {
     Self {
       lists: RefCell::new(AnyMap::new()),
+      pre_lists: RefCell::new(AnyMap::new()),
+      index: RefCell::new(Vec::new()),
+      waiters: RefCell::new(AnyMap::new()),
       queue: RefCell::new(VecDeque::new()),
       cleanup: RefCell::new(VecDeque::new()),
+      tracing: Cell::new(false),
     }
   }
 
-  fn register_with_priority<E, H>(&self, priority: i32, handler: H)
-  where
+  fn lists_mut(&self) -> RefMut<'_, AnyMap> {
+    self
+      .lists
+      .try_borrow_mut()
+      .expect("event handlers cannot be modified while an event is being dispatched")
+  }
+
+  fn pre_list<E: Event>(&self) -> PreHandlerList<E> {
+    let mut lists = self.pre_lists.borrow_mut();
+    Rc::clone(lists.entry::<PreHandlerList<E>>().or_default())
+  }
+
+  /// Make `name` unique among the registered handlers by adding a `#N` suffix
+  /// if it is already taken. This is needed for handlers that are named after
+  /// their type since, for example, all closures within a function have the
+  /// same type name.
+  fn unique_name(&self, name: &'static str) -> &'static str {
+    let index = self.index.borrow();
+    let taken = |name: &str| index.iter().any(|entry| entry.info.name == name);
+    if !taken(name) {
+      return name;
+    }
+
+    let unique = (2..)
+      .map(|n| format!("{}#{}", name, n))
+      .find(|name| !taken(name))
+      .unwrap();
+
+    // Handlers are registered once when the server starts up so leaking the
+    // name doesn't amount to much.
+    Box::leak(unique.into_boxed_str())
+  }
+
+  fn add_to_index<E: Event>(
+    &self,
+    name: &'static str,
+    priority: i32,
+    pre: bool,
+    location: SourceLocation,
+    remove: fn(&BaseEventDispatcher, &str),
+  ) {
+    self.index.borrow_mut().push(IndexEntry {
+      info: HandlerInfo {
+        name,
+        event: std::any::type_name::<E>(),
+        priority,
+        pre,
+        location,
+      },
+      event: TypeId::of::<E>(),
+      remove,
+    });
+  }
+
+  fn register_with_priority<E, H>(
+    &self,
+    priority: i32,
+    name: &'static str,
+    location: SourceLocation,
+    handler: H,
+  ) where
     H: EventHandler<E>,
     E: Event,
   {
-    let mut lists = self.lists.borrow_mut();
+    let mut lists = self.lists_mut();
     let list = lists.entry::<HandlerList<E>>().or_insert_with(Vec::new);
 
-    list.push(HandlerWithPriority(priority, Box::new(handler)));
+    list.push(HandlerWithPriority(priority, name, Box::new(handler)));
     list.sort();
+
+    self.add_to_index::<E>(name, priority, false, location, |this, name| {
+      if let Some(list) = this.lists_mut().get_mut::<HandlerList<E>>() {
+        list.retain(|handler| handler.1 != name);
+      }
+    });
   }
 
-  fn dispatch_raw<E>(event: E, world: &mut AirmashGame, lists: &mut AnyMap)
+  fn register_pre_with_priority<E, H>(
+    &self,
+    priority: i32,
+    name: &'static str,
+    location: SourceLocation,
+    handler: H,
+  ) where
+    H: PreEventHandler<E>,
+    E: Event,
+  {
+    let list = self.pre_list::<E>();
+    let mut list = list.borrow_mut();
+
+    list.push(HandlerWithPriority(priority, name, Box::new(handler)));
+    list.sort();
+
+    self.add_to_index::<E>(name, priority, true, location, |this, name| {
+      this
+        .pre_list::<E>()
+        .borrow_mut()
+        .retain(|handler| handler.1 != name);
+    });
+  }
+
+  fn handlers(&self, event: Option<TypeId>) -> Vec<HandlerInfo> {
+    let mut handlers: Vec<_> = self
+      .index
+      .borrow()
+      .iter()
+      .filter(|entry| event.map(|event| entry.event == event).unwrap_or(true))
+      .map(|entry| entry.info.clone())
+      .collect();
+    // The sort is stable so handlers with the same priority stay in the order
+    // they were registered, which is the order they are executed in.
+    handlers.sort_by(|a, b| (a.event, a.pre, b.priority).cmp(&(b.event, b.pre, a.priority)));
+    handlers
+  }
+
+  fn disable(&self, name: &str) -> bool {
+    let mut removed = Vec::new();
+    self.index.borrow_mut().retain(|entry| {
+      if entry.info.name != name {
+        return true;
+      }
+
+      removed.push(entry.remove);
+      false
+    });
+
+    for remove in &removed {
+      remove(self, name);
+    }
+
+    !removed.is_empty()
+  }
+
+  fn replace<E, H>(&self, name: &str, handler: H) -> bool
+  where
+    H: EventHandler<E>,
+    E: Event,
+  {
+    let mut lists = self.lists_mut();
+    let entry = lists
+      .get_mut::<HandlerList<E>>()
+      .and_then(|list| list.iter_mut().find(|entry| entry.1 == name));
+
+    match entry {
+      Some(entry) => {
+        entry.2 = Box::new(handler);
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn replace_pre<E, H>(&self, name: &str, handler: H) -> bool
+  where
+    H: PreEventHandler<E>,
+    E: Event,
+  {
+    let list = self.pre_list::<E>();
+    let mut list = list.borrow_mut();
+
+    match list.iter_mut().find(|entry| entry.1 == name) {
+      Some(entry) => {
+        entry.2 = Box::new(handler);
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn profiling(world: &AirmashGame) -> bool {
+    world
+      .resources
+      .get::<Profiler>()
+      .map(|profiler| profiler.enabled())
+      .unwrap_or(false)
+  }
+
+  fn trace(&self, origin: Origin) -> Option<Origin> {
+    match self.tracing.get() {
+      true => Some(origin),
+      false => None,
+    }
+  }
+
+  fn trace_event<E: Event>(origin: Option<Origin>, handlers: usize) {
+    if let Some(origin) = origin {
+      info!(
+        "Dispatching {} {} to {} handlers",
+        origin,
+        std::any::type_name::<E>(),
+        handlers
+      );
+    }
+  }
+
+  fn add_waiter<E: Event>(&self, waiter: Box<dyn FnMut(&E) -> bool>) {
+    let mut waiters = self.waiters.borrow_mut();
+    let list = waiters.entry::<WaiterList<E>>().or_default();
+    list.push(waiter);
+  }
+
+  fn notify_waiters<E: Event>(&self, event: &E) {
+    let mut waiters = self.waiters.borrow_mut();
+    if let Some(list) = waiters.get_mut::<WaiterList<E>>() {
+      list.retain_mut(|waiter| !waiter(event));
+    }
+  }
+
+  fn dispatch_raw<E>(
+    &self,
+    event: E,
+    world: &mut AirmashGame,
+    lists: &mut AnyMap,
+    trace: Option<Origin>,
+  ) where
+    E: Event,
+  {
+    Self::run_handlers(&event, world, lists, trace);
+    self.notify_waiters(&event);
+  }
+
+  fn run_handlers<E>(event: &E, world: &mut AirmashGame, lists: &mut AnyMap, trace: Option<Origin>)
   where
     E: Event,
   {
-    if let Some(list) = lists.get_mut::<HandlerList<E>>() {
+    let list = match lists.get_mut::<HandlerList<E>>() {
+      Some(list) => list,
+      None => {
+        Self::trace_event::<E>(trace, 0);
+        return;
+      }
+    };
+
+    let profiling = Self::profiling(world);
+    if !profiling && trace.is_none() {
       for handler in list.iter_mut() {
-        handler.1.on_event(&event, world);
+        handler.2.on_event(event, world);
+      }
+      return;
+    }
+
+    Self::trace_event::<E>(trace, list.len());
+    for handler in list.iter_mut() {
+      let start = Instant::now();
+      handler.2.on_event(event, world);
+      let elapsed = start.elapsed();
+
+      if trace.is_some() {
+        info!("  {} [{}] took {:?}", handler.1, handler.0, elapsed);
+      }
+
+      if profiling {
+        if let Some(mut profiler) = world.resources.get_mut::<Profiler>() {
+          profiler.record_handler(handler.1, elapsed);
+        }
       }
     }
   }
@@ -109,17 +480,72 @@ impl BaseEventDispatcher {
       }
     };
 
-    Self::dispatch_raw(event, world, &mut lists);
+    self.dispatch_raw(event, world, &mut lists, self.trace(Origin::Direct));
 
     while let Some(mut event) = self.next_queued() {
-      event.dispatch(world, &mut lists);
+      event.dispatch(self, world, &mut lists, self.trace(Origin::Queued));
     }
 
     drop(lists);
     let mut cleanup = self.cleanup.borrow_mut();
     for mut func in cleanup.drain(..) {
+      self.run_cleanup(world, &mut *func);
+    }
+  }
+
+  fn run_cleanup(&self, world: &mut AirmashGame, func: &mut dyn FnMut(&mut AirmashGame)) {
+    if !self.tracing.get() {
       func(world);
+      return;
+    }
+
+    let start = Instant::now();
+    func(world);
+    info!("Cleanup task took {:?}", start.elapsed());
+  }
+
+  fn dispatch_pre<E>(&self, mut event: E, world: &mut AirmashGame) -> E
+  where
+    E: Event,
+  {
+    let trace = self.trace(Origin::Pre);
+    let list = match self.pre_lists.borrow().get::<PreHandlerList<E>>() {
+      Some(list) => Rc::clone(list),
+      None => {
+        Self::trace_event::<E>(trace, 0);
+        return event;
+      }
+    };
+    let mut list = match list.try_borrow_mut() {
+      Ok(list) => list,
+      Err(_) => {
+        warn!(
+          "Pre-event {} was dispatched by one of its own handlers. Ignoring it",
+          std::any::type_name::<E>()
+        );
+        return event;
+      }
+    };
+
+    let profiling = Self::profiling(world);
+    Self::trace_event::<E>(trace, list.len());
+    for handler in list.iter_mut() {
+      let start = Instant::now();
+      handler.2.on_event(&mut event, world);
+      let elapsed = start.elapsed();
+
+      if trace.is_some() {
+        info!("  {} [{}] took {:?}", handler.1, handler.0, elapsed);
+      }
+
+      if profiling {
+        if let Some(mut profiler) = world.resources.get_mut::<Profiler>() {
+          profiler.record_handler(handler.1, elapsed);
+        }
+      }
     }
+
+    event
   }
 
   /// Add a function to the cleanup queue. If no event is currently executing
@@ -128,16 +554,16 @@ impl BaseEventDispatcher {
   where
     F: FnOnce(&mut AirmashGame) + 'static,
   {
+    let mut func = Some(func);
+    let mut func = move |game: &mut AirmashGame| (func.take().unwrap())(game);
+
     if self.lists.try_borrow_mut().is_ok() {
-      func(world);
+      self.run_cleanup(world, &mut func);
       return;
     }
 
     let mut cleanup = self.cleanup.borrow_mut();
-    let mut func = Some(func);
-    cleanup.push_back(Box::new(move |game| {
-      (func.take().unwrap())(game);
-    }));
+    cleanup.push_back(Box::new(func));
   }
 
   fn next_queued(&self) -> Option<Box<dyn DelayedEvent>> {
@@ -165,12 +591,184 @@ impl EventDispatcher {
   }
 
   /// Register a new event handler with the provided priority.
+  ///
+  /// The handler is named after its type, with a `#N` suffix if another
+  /// handler already has that name. Use [`register_named_with_priority`] to
+  /// give it a name that can be used to disable or replace it later.
+  ///
+  /// [`register_named_with_priority`]: crate::EventDispatcher::register_named_with_priority
+  #[track_caller]
   pub fn register_with_priority<E, H>(&self, priority: i32, handler: H)
   where
     H: EventHandler<E>,
     E: Event,
   {
-    self.dispatcher.register_with_priority(priority, handler)
+    let name = self.dispatcher.unique_name(std::any::type_name::<H>());
+    self.register_handler(priority, name, SourceLocation::caller(), handler)
+  }
+
+  /// Register a new event handler with the provided priority and name.
+  #[track_caller]
+  pub fn register_named_with_priority<E, H>(&self, priority: i32, name: &'static str, handler: H)
+  where
+    H: EventHandler<E>,
+    E: Event,
+  {
+    self.register_handler(priority, name, SourceLocation::caller(), handler)
+  }
+
+  /// Register a new event handler with all of its details given explicitly.
+  ///
+  /// This is what `#[handler]` uses so that the location recorded for the
+  /// handler is the handler function itself.
+  pub fn register_handler<E, H>(
+    &self,
+    priority: i32,
+    name: &'static str,
+    location: SourceLocation,
+    handler: H,
+  ) where
+    H: EventHandler<E>,
+    E: Event,
+  {
+    self
+      .dispatcher
+      .register_with_priority(priority, name, location, handler)
+  }
+
+  /// Register a new pre-event handler with the provided priority. It is named
+  /// the same way as in [`register_with_priority`].
+  ///
+  /// [`register_with_priority`]: crate::EventDispatcher::register_with_priority
+  #[track_caller]
+  pub fn register_pre_with_priority<E, H>(&self, priority: i32, handler: H)
+  where
+    H: PreEventHandler<E>,
+    E: Event,
+  {
+    let name = self.dispatcher.unique_name(std::any::type_name::<H>());
+    self.register_pre_handler(priority, name, SourceLocation::caller(), handler)
+  }
+
+  /// Register a new pre-event handler with the provided priority and name.
+  #[track_caller]
+  pub fn register_pre_named_with_priority<E, H>(
+    &self,
+    priority: i32,
+    name: &'static str,
+    handler: H,
+  ) where
+    H: PreEventHandler<E>,
+    E: Event,
+  {
+    self.register_pre_handler(priority, name, SourceLocation::caller(), handler)
+  }
+
+  /// Register a new pre-event handler with all of its details given
+  /// explicitly. See [`register_handler`].
+  ///
+  /// [`register_handler`]: crate::EventDispatcher::register_handler
+  pub fn register_pre_handler<E, H>(
+    &self,
+    priority: i32,
+    name: &'static str,
+    location: SourceLocation,
+    handler: H,
+  ) where
+    H: PreEventHandler<E>,
+    E: Event,
+  {
+    self
+      .dispatcher
+      .register_pre_with_priority(priority, name, location, handler)
+  }
+
+  /// List all registered handlers, grouped by event type and in the order that
+  /// they will be executed.
+  pub fn handlers(&self) -> Vec<HandlerInfo> {
+    self.dispatcher.handlers(None)
+  }
+
+  /// List the handlers registered for `E` in the order that they will be
+  /// executed. Handlers for `E` as a pre-event come after the regular ones.
+  pub fn handlers_for<E: Event>(&self) -> Vec<HandlerInfo> {
+    self.dispatcher.handlers(Some(TypeId::of::<E>()))
+  }
+
+  /// Register a callback that is called with every `E` that is dispatched,
+  /// after all of its handlers have run, until it returns true.
+  ///
+  /// This is what backs [`GameRef::wait_for`](crate::GameRef::wait_for).
+  pub(crate) fn add_waiter<E, F>(&self, waiter: F)
+  where
+    E: Event,
+    F: FnMut(&E) -> bool + 'static,
+  {
+    self.dispatcher.add_waiter(Box::new(waiter));
+  }
+
+  /// Enable or disable tracing.
+  ///
+  /// While tracing is enabled every event that is dispatched, whether directly,
+  /// after being queued, or as a pre-event, is logged along with the time
+  /// taken by each of its handlers. Cleanup tasks are logged as well. This
+  /// produces a lot of output so it is disabled by default.
+  pub fn set_tracing(&self, enabled: bool) {
+    self.dispatcher.tracing.set(enabled);
+  }
+
+  /// Whether tracing is currently enabled.
+  pub fn tracing(&self) -> bool {
+    self.dispatcher.tracing.get()
+  }
+
+  /// Remove all handlers with the provided name. Returns whether there were
+  /// any such handlers.
+  ///
+  /// # Panics
+  /// Panics if called while an event is being dispatched.
+  pub fn disable(&self, name: &str) -> bool {
+    self.dispatcher.disable(name)
+  }
+
+  /// Replace the handler for `E` with the provided name. The new handler keeps
+  /// the name and priority of the one it replaces. Returns whether there was a
+  /// handler with that name for `E`.
+  ///
+  /// # Panics
+  /// Panics if called while an event is being dispatched.
+  pub fn replace<E, H>(&self, name: &str, handler: H) -> bool
+  where
+    H: EventHandler<E>,
+    E: Event,
+  {
+    self.dispatcher.replace(name, handler)
+  }
+
+  /// Replace the pre-event handler for `E` with the provided name. This works
+  /// the same as [`replace`] but for pre-events.
+  ///
+  /// [`replace`]: crate::EventDispatcher::replace
+  pub fn replace_pre<E, H>(&self, name: &str, handler: H) -> bool
+  where
+    H: PreEventHandler<E>,
+    E: Event,
+  {
+    self.dispatcher.replace_pre(name, handler)
+  }
+
+  /// Dispatch a pre-event, execute all of its handlers in decreasing order of
+  /// priority, and return the event as modified by the handlers.
+  ///
+  /// Unlike [`dispatch`], this executes the handlers immediately even if
+  /// another event is currently being dispatched.
+  ///
+  /// [`dispatch`]: crate::EventDispatcher::dispatch
+  pub fn dispatch_pre<E>(&self, event: E, world: &mut AirmashGame) -> E
+  where
+    E: Event,
+  {
+    self.dispatcher.dispatch_pre(event, world)
   }
 
   /// Dispatch the provided event and execute all the resulting event handlers
@@ -205,22 +803,22 @@ impl Default for EventDispatcher {
   }
 }
 
-impl<E> PartialEq for HandlerWithPriority<E> {
+impl<H: ?Sized> PartialEq for HandlerWithPriority<H> {
   fn eq(&self, other: &Self) -> bool {
     self.0 == other.0
   }
 }
 
-impl<E> PartialOrd for HandlerWithPriority<E> {
+impl<H: ?Sized> PartialOrd for HandlerWithPriority<H> {
   fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
     Some(other.0.cmp(&self.0))
   }
 }
 
-impl<E> Ord for HandlerWithPriority<E> {
+impl<H: ?Sized> Ord for HandlerWithPriority<H> {
   fn cmp(&self, other: &Self) -> std::cmp::Ordering {
     other.0.cmp(&self.0)
   }
 }
 
-impl<E> Eq for HandlerWithPriority<E> {}
+impl<H: ?Sized> Eq for HandlerWithPriority<H> {}
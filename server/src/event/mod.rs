@@ -96,12 +96,47 @@
 //! that is dispatched it will be queued up and executed as soon as the current
 //! event finishes executing.
 //!
+//! # Pre-Events
+//! Regular events are notifications that something has already happened. Some
+//! actions also have a pre-event (e.g. [`BeforePlayerHit`]) that is dispatched
+//! before the server performs the action. Handlers for pre-events take the
+//! event by mutable reference and can change it (e.g. to modify the damage
+//! done) or set its `cancelled` field to stop the action from happening. The
+//! server then acts on the event as it was left by the last handler.
+//!
+//! Pre-event handlers are declared with the same [`handler`] attribute, the
+//! only difference being that the event is taken by `&mut`:
+//! ```
+//! # use airmash::{handler, AirmashGame};
+//! # use airmash::event::BeforePlayerHit;
+//! #[handler]
+//! fn halve_damage(event: &mut BeforePlayerHit, game: &mut AirmashGame) {
+//!   event.damage *= 0.5;
+//! }
+//! ```
+//!
+//! Since the caller needs the result, pre-events are dispatched via
+//! [`AirmashGame::dispatch_pre`] which executes the handlers immediately, even
+//! if another event is currently being dispatched.
+//!
+//! # Replacing Built-in Handlers
+//! Every handler registered via [`handler`] is named after the full path of its
+//! function (e.g. `airmash::system::handler::on_player_killed::launch_respawn_task`).
+//! A game mode that wants different behaviour can use
+//! [`AirmashGame::disable_handler`] or [`AirmashGame::replace_handler`] with
+//! that name before starting the server. [`AirmashGame::handlers`] lists all
+//! the registered handlers.
+//!
 //! [`EventHandler`]: crate::EventHandler
 //! [`handler`]: crate::handler
 //! [`priority`]: crate::priority
 //! [`AirmashGame`]: crate::AirmashGame
 //! [`AirmashGame::register`]: crate::AirmashGame::register
 //! [`AirmashGame::dispatch`]: crate::AirmashGame::dispatch
+//! [`AirmashGame::dispatch_pre`]: crate::AirmashGame::dispatch_pre
+//! [`AirmashGame::disable_handler`]: crate::AirmashGame::disable_handler
+//! [`AirmashGame::replace_handler`]: crate::AirmashGame::replace_handler
+//! [`AirmashGame::handlers`]: crate::AirmashGame::handlers
 //! [`DEFAULT`]: crate::priority::DEFAULT
 //! [`MEDIUM`]: crate::priority::MEDIUM
 //! [`HIGH`]: crate::priority::HIGH
@@ -120,12 +155,14 @@ mod missile;
 mod mob;
 mod packet;
 mod player;
+mod pre;
 
 pub use self::collision::*;
 pub use self::missile::*;
 pub use self::mob::*;
 pub use self::packet::*;
 pub use self::player::*;
+pub use self::pre::*;
 
 /// Emitted during server startup.
 ///
@@ -133,6 +170,16 @@ pub use self::player::*;
 #[derive(Copy, Clone, Debug, Default)]
 pub struct ServerStartup;
 
+/// Emitted after the game [`Config`] has been replaced while the server is
+/// running.
+///
+/// By the time this is emitted all entities have already been migrated to the
+/// new prototypes.
+///
+/// [`Config`]: crate::resource::Config
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ConfigReload;
+
 /// Emitted at the very start of each frame.
 #[derive(Clone, Copy, Debug, Default)]
 pub struct FrameStart;
@@ -2,6 +2,7 @@ use hecs::Entity;
 use smallvec::SmallVec;
 
 use crate::config::{PlanePrototypeRef, PowerupPrototypeRef};
+use crate::Vector2;
 
 /// A new player has joined the game.
 #[derive(Clone, Copy, Debug)]
@@ -9,6 +10,17 @@ pub struct PlayerJoin {
   pub player: Entity,
 }
 
+/// A player whose connection dropped has reconnected and taken over their
+/// entity again.
+///
+/// The player's new connection has already been made primary by the time this
+/// is emitted. Handlers should resend any state that the client needs that it
+/// would have otherwise received when the player joined.
+#[derive(Clone, Copy, Debug)]
+pub struct PlayerResume {
+  pub player: Entity,
+}
+
 /// A player has left the game.
 ///
 /// Note that this event is emitted before the player's entity is despawned.
@@ -41,6 +53,11 @@ pub struct PlayerRespawn {
   pub player: Entity,
   /// Whether the player was alive when they respawned
   pub alive: bool,
+  /// The position requested by a [`BeforeRespawn`] handler. This takes
+  /// precedence over the position picked by the game mode.
+  ///
+  /// [`BeforeRespawn`]: crate::event::BeforeRespawn
+  pub pos: Option<Vector2>,
 }
 
 /// A player has spawned.
@@ -0,0 +1,65 @@
+//! Pre-events which are dispatched before the server performs an action.
+//!
+//! Handlers for these take the event by mutable reference so they can modify
+//! it or set `cancelled` to stop the action from happening at all. Later
+//! handlers see the changes made by earlier ones and can undo them.
+
+use hecs::Entity;
+use smallvec::SmallVec;
+
+use crate::config::PowerupPrototypeRef;
+use crate::{FireMissileInfo, Vector2};
+
+/// A player is about to be damaged by a missile.
+///
+/// This is dispatched once for each player hit by the missile. If it is
+/// cancelled then the player takes no damage but a [`PlayerHit`] packet is
+/// still sent since the missile still collided with them.
+///
+/// [`PlayerHit`]: crate::protocol::server::PlayerHit
+#[derive(Copy, Clone, Debug)]
+pub struct BeforePlayerHit {
+  pub player: Entity,
+  pub missile: Entity,
+  pub attacker: Option<Entity>,
+  /// The damage that will be applied to the player's health.
+  pub damage: f32,
+  pub cancelled: bool,
+}
+
+/// A player is about to fire missiles.
+///
+/// Handlers can change which missiles get fired. If the event is cancelled, or
+/// ends up with no missiles, then nothing is fired and the player is not
+/// charged any energy for the shot.
+#[derive(Clone, Debug)]
+pub struct BeforePlayerFire {
+  pub player: Entity,
+  pub missiles: SmallVec<[FireMissileInfo; 3]>,
+  pub cancelled: bool,
+}
+
+/// A player is about to respawn.
+///
+/// If the event is cancelled then the player is left as they are.
+#[derive(Copy, Clone, Debug)]
+pub struct BeforeRespawn {
+  pub player: Entity,
+  /// Whether the player is currently alive.
+  pub alive: bool,
+  /// Where to respawn the player. If this is `None` then the position picked by
+  /// the game mode is used.
+  pub pos: Option<Vector2>,
+  pub cancelled: bool,
+}
+
+/// A player is about to pick up a powerup.
+///
+/// If the event is cancelled then the player doesn't get the powerup and the
+/// powerup stays where it is.
+#[derive(Copy, Clone, Debug)]
+pub struct BeforePowerup {
+  pub player: Entity,
+  pub powerup: PowerupPrototypeRef,
+  pub cancelled: bool,
+}
@@ -32,14 +32,19 @@ mod consts;
 mod defaults;
 mod dispatch;
 mod mock;
+mod reload;
 mod task;
 mod world;
 mod worldext;
 
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod command;
 pub mod component;
 pub mod event;
 pub mod network;
 pub mod resource;
+pub mod snapshot;
 pub mod system;
 pub mod util;
 
@@ -47,15 +52,17 @@ pub use hecs::Entity;
 pub use server_macros::handler;
 
 pub use self::config::Vector2;
-pub use self::dispatch::{Event, EventDispatcher, EventHandler};
-pub use self::task::{GameRef, TaskScheduler};
+pub use self::dispatch::{
+  Event, EventDispatcher, EventHandler, HandlerInfo, PreEventHandler, SourceLocation,
+};
+pub use self::task::{GameRef, TaskBuilder, TaskHandle, TaskScheduler, WaitFor};
 pub use self::world::{AirmashGame, Resources};
 pub use self::worldext::{EntitySetBuilder, FireMissileInfo};
 
 /// Exports needed by the handler macro.
 #[doc(hidden)]
 pub mod _exports {
-  pub use crate::dispatch::{EventDispatcher, AIRMASH_EVENT_HANDLERS};
+  pub use crate::dispatch::{EventDispatcher, SourceLocation, AIRMASH_EVENT_HANDLERS};
   pub extern crate linkme;
 }
 
@@ -91,6 +98,13 @@ pub mod priority {
   /// this.
   pub const LOGIN: i32 = 1000;
   pub const PRE_LOGIN: i32 = 1500;
+
+  /// Priority of the handler that restores the saved state of a player who
+  /// reconnects after a snapshot was restored.
+  ///
+  /// This runs after [`PRE_LOGIN`] so that the saved team and position take
+  /// precedence over those assigned by the game mode.
+  pub const RESTORE: i32 = 1250;
 }
 
 /// Utilities to help with writing tests for server functionality.
@@ -5,12 +5,14 @@ use std::time::{Duration, Instant};
 use airmash_protocol::KeyCode;
 use crossbeam_channel::Sender;
 use hecs::Entity;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::sync::mpsc::{channel, Receiver};
 
 use crate::network::*;
 use crate::protocol::{client as c, ClientPacket, ServerPacket};
 use crate::AirmashGame;
 
+const MOCK_QUEUE_CAPACITY: usize = 1 << 20;
+
 /// Mock connection for testing purposes.
 ///
 /// This allows for sending packets to the server without having to go through a
@@ -18,7 +20,7 @@ use crate::AirmashGame;
 /// interfere with each other.
 pub struct MockConnection {
   tx: Sender<(ConnectionId, InternalEvent)>,
-  rx: UnboundedReceiver<Arc<Vec<u8>>>,
+  rx: Receiver<Arc<Vec<u8>>>,
   conn: ConnectionId,
   closed: bool,
 
@@ -28,7 +30,7 @@ pub struct MockConnection {
 impl MockConnection {
   fn new(
     tx: Sender<(ConnectionId, InternalEvent)>,
-    rx: UnboundedReceiver<Arc<Vec<u8>>>,
+    rx: Receiver<Arc<Vec<u8>>>,
     conn: ConnectionId,
   ) -> Self {
     Self {
@@ -99,7 +101,7 @@ impl MockConnection {
   }
 
   pub fn close(&mut self) {
-    if std::mem::replace(&mut self.closed, false) {
+    if !std::mem::replace(&mut self.closed, true) {
       let _ = self.tx.send((self.conn, InternalEvent::Closed));
     }
   }
@@ -180,20 +182,21 @@ impl MockConnectionEndpoint {
   }
 
   pub fn open(&mut self) -> MockConnection {
+    self.open_with_addr(SocketAddr::new(IpAddr::from([0; 4]), 0))
+  }
+
+  /// Open a new connection that appears to come from `addr`.
+  pub fn open_with_addr(&mut self, addr: SocketAddr) -> MockConnection {
     let conn = ConnectionId(self.nextid);
     self.nextid += 1;
 
-    let (tx, rx) = unbounded_channel();
+    // Tests only drain the queue when they read from the connection so it
+    // needs to be large enough to hold everything sent over a whole test.
+    let (tx, rx) = channel(MOCK_QUEUE_CAPACITY);
 
     self
       .sender
-      .send((
-        conn,
-        InternalEvent::Opened(ConnectionData {
-          send: tx,
-          addr: SocketAddr::new(IpAddr::from([0; 4]), 0),
-        }),
-      ))
+      .send((conn, InternalEvent::Opened(ConnectionData::new(tx, addr))))
       .expect("Network event channel is closed");
 
     MockConnection::new(self.sender.clone(), rx, conn)
@@ -0,0 +1,284 @@
+//! Recording of the outbound side of a server session as a demo.
+//!
+//! A [`DemoRecorder`] writes every packet that the server sends to a single
+//! player to a file along with the time it was sent. Alternatively, it can
+//! record from the point of view of a spectator that receives every packet
+//! that is broadcast to players. The resulting [`Demo`] can then be sent to a
+//! regular client over a websocket (see the `airmash-demo-server` binary) to
+//! watch the match again.
+//!
+//! Since the demo only contains what the client would have received it does
+//! not depend on the server configuration or game mode used to record it. The
+//! one exception is the session token in login packets, which is left out
+//! since anyone with the demo could otherwise take over the player's session.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use bstr::BString;
+use hecs::Entity;
+
+use super::ConnectionMgr;
+use crate::component::{IsPlayer, Team};
+use crate::protocol::server::{GameSpectate, Login, LoginPlayer};
+use crate::protocol::{v5, FlagCode, PlaneType, PlayerStatus, ServerPacket};
+use crate::{AirmashGame, Vector2};
+
+const MAGIC: &[u8; 8] = b"AMDEMO\0\0";
+const VERSION: u8 = 1;
+
+/// The player ID used for the synthetic spectator in spectator demos.
+pub const SPECTATOR_ID: u16 = u16::MAX;
+
+/// Whose point of view a demo is recorded from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DemoTarget {
+  /// Record every packet sent to a single player.
+  Player(Entity),
+  /// Record every packet that is broadcast to players, excluding packets
+  /// that are only meant for a single client (pings, command replies,
+  /// whispers, and so on).
+  Spectator,
+}
+
+/// Writes the packets sent to a [`DemoTarget`] to a file.
+///
+/// Use [`AirmashGame::record_demo`] to start recording.
+pub struct DemoRecorder {
+  out: BufWriter<File>,
+  target: DemoTarget,
+  start: Instant,
+  last: u64,
+}
+
+impl DemoRecorder {
+  fn create(path: &Path, target: DemoTarget, start: Instant) -> io::Result<Self> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(MAGIC)?;
+    out.write_all(&[VERSION])?;
+
+    Ok(Self {
+      out,
+      target,
+      start,
+      last: 0,
+    })
+  }
+
+  pub fn target(&self) -> DemoTarget {
+    self.target
+  }
+
+  /// Write a serialized packet that was sent at `time`.
+  pub(crate) fn packet(&mut self, time: Instant, data: &[u8]) -> io::Result<()> {
+    let offset = time.saturating_duration_since(self.start).as_millis() as u64;
+    let delay = offset.saturating_sub(self.last);
+    self.last = self.last.max(offset);
+
+    self.out.write_all(&(delay as u32).to_le_bytes())?;
+    self.out.write_all(&(data.len() as u32).to_le_bytes())?;
+    self.out.write_all(data)
+  }
+
+  pub(crate) fn flush(&mut self) -> io::Result<()> {
+    self.out.flush()
+  }
+}
+
+/// A copy of `packet` with the session token removed, if it contains one.
+pub(crate) fn without_token(packet: &ServerPacket) -> Option<ServerPacket> {
+  match packet {
+    ServerPacket::Login(login) => Some(
+      Login {
+        token: BString::default(),
+        ..login.clone()
+      }
+      .into(),
+    ),
+    _ => None,
+  }
+}
+
+/// Whether a packet sent to players should be included in a spectator demo.
+pub(crate) fn is_spectator_visible(packet: &ServerPacket) -> bool {
+  !matches!(
+    packet,
+    ServerPacket::Login(_)
+      | ServerPacket::Login2(_)
+      | ServerPacket::Backup
+      | ServerPacket::Ping(_)
+      | ServerPacket::PingResult(_)
+      | ServerPacket::Ack
+      | ServerPacket::Error(_)
+      | ServerPacket::CommandReply(_)
+      | ServerPacket::GameSpectate(_)
+      | ServerPacket::ScoreDetailedFFA(_)
+      | ServerPacket::ScoreDetailedCTF(_)
+      | ServerPacket::ScoreDetailedBTR(_)
+      | ServerPacket::ChatWhisper(_)
+  )
+}
+
+impl AirmashGame {
+  /// Start recording the packets sent to `target` into a demo file.
+  ///
+  /// The demo starts with a login packet describing all players currently in
+  /// the game. Login packets never include the player's session token. For spectator demos this logs in a synthetic dead player with
+  /// ID [`SPECTATOR_ID`] that then spectates the first player in the game.
+  ///
+  /// Any demo that is already being recorded is stopped.
+  pub fn record_demo(&mut self, path: impl AsRef<Path>, target: DemoTarget) -> io::Result<()> {
+    let mut packets: Vec<ServerPacket> = Vec::new();
+
+    match target {
+      DemoTarget::Player(player) => {
+        let mut query = self
+          .world
+          .query_one::<&Team>(player)
+          .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "player is not in the game"))?
+          .with::<IsPlayer>();
+        let team = query
+          .get()
+          .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "player is not in the game"))?;
+
+        packets.push(
+          crate::util::get_login_packet(self, player.id() as u16, team.0, BString::from("")).into(),
+        );
+      }
+      DemoTarget::Spectator => {
+        let mut login =
+          crate::util::get_login_packet(self, SPECTATOR_ID, SPECTATOR_ID, BString::from(""));
+        let first = login.players.iter().map(|p| p.id).min();
+
+        login.players.push(LoginPlayer {
+          id: SPECTATOR_ID,
+          status: PlayerStatus::Dead,
+          level: 0,
+          name: "spectator".into(),
+          ty: PlaneType::Predator,
+          team: SPECTATOR_ID,
+          pos: Vector2::zero().into(),
+          rot: 0.0,
+          flag: FlagCode::UnitedNations,
+          upgrades: Default::default(),
+        });
+        packets.push(login.into());
+
+        if let Some(id) = first {
+          packets.push(GameSpectate { id }.into());
+        }
+      }
+    }
+
+    let now = self.this_frame();
+    let mut recorder = DemoRecorder::create(path.as_ref(), target, now)?;
+    for packet in &packets {
+      let data = v5::serialize(packet)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, format!("{:?}", e)))?;
+      recorder.packet(now, &data)?;
+    }
+    recorder.flush()?;
+
+    self
+      .resources
+      .write::<ConnectionMgr>()
+      .set_demo_recorder(Some(recorder));
+    Ok(())
+  }
+
+  /// Stop recording the current demo, if there is one.
+  pub fn stop_demo(&mut self) {
+    self
+      .resources
+      .write::<ConnectionMgr>()
+      .set_demo_recorder(None);
+  }
+}
+
+/// A single packet within a [`Demo`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DemoPacket {
+  /// Time since the previous packet in the demo was sent.
+  pub delay: Duration,
+  /// The packet, serialized in the format sent to clients.
+  pub data: Vec<u8>,
+}
+
+/// A recorded demo that can be played back to a client.
+#[derive(Clone, Debug, Default)]
+pub struct Demo {
+  packets: Vec<DemoPacket>,
+}
+
+impl Demo {
+  /// Load a demo written by a [`DemoRecorder`].
+  ///
+  /// A demo that was cut short (e.g. because the server crashed while it was
+  /// being recorded) is loaded up to the last complete packet.
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    Self::read(BufReader::new(File::open(path)?))
+  }
+
+  fn read(mut input: impl Read) -> io::Result<Self> {
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(invalid_data("not an airmash demo"));
+    }
+
+    let mut version = [0u8; 1];
+    input.read_exact(&mut version)?;
+    if version[0] != VERSION {
+      return Err(invalid_data(&format!(
+        "unsupported demo version {}",
+        version[0]
+      )));
+    }
+
+    let mut packets = Vec::new();
+    loop {
+      match Self::read_packet(&mut input) {
+        Ok(packet) => packets.push(packet),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+        Err(e) => return Err(e),
+      }
+    }
+
+    Ok(Self { packets })
+  }
+
+  fn read_packet(input: &mut impl Read) -> io::Result<DemoPacket> {
+    let mut header = [0u8; 8];
+    input.read_exact(&mut header)?;
+
+    let delay = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+
+    let mut data = Vec::new();
+    input.take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len {
+      return Err(ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(DemoPacket {
+      delay: Duration::from_millis(delay as u64),
+      data,
+    })
+  }
+
+  /// The recorded packets, in the order they were sent.
+  pub fn packets(&self) -> &[DemoPacket] {
+    &self.packets
+  }
+
+  /// The total length of the demo.
+  pub fn duration(&self) -> Duration {
+    self.packets.iter().map(|p| p.delay).sum()
+  }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+  io::Error::new(ErrorKind::InvalidData, msg)
+}
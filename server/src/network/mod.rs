@@ -0,0 +1,1173 @@
+//! Raw networking interfaces.
+//!
+//! This module has the raw types for interacting with the network endpoint of
+//! the server. Usually you'll want to use the helper methods on [`AirmashGame`]
+//! instead of interacting directly with these types.
+//!
+//! [`AirmashGame`]: crate::AirmashGame
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+use hecs::Entity;
+use httparse::{Status, EMPTY_HEADER};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Sender as AsyncSender};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::WebSocketStream;
+
+use crate::mock::MockConnectionEndpoint;
+use crate::resource::{BanList, BanTarget, ConnectionLimits, OpenConnection};
+
+mod demo;
+mod origin;
+mod proxy;
+mod replay;
+mod status;
+mod tls;
+
+pub(crate) use self::demo::{is_spectator_visible, without_token};
+pub use self::demo::{Demo, DemoPacket, DemoRecorder, DemoTarget, SPECTATOR_ID};
+pub use self::origin::AllowedOrigins;
+pub use self::proxy::{IpCidr, ProxyConfig, ProxySettings};
+pub use self::replay::{RecordedEvent, RecordedFrame, Recorder, Replay};
+pub(crate) use self::status::SharedStatus;
+pub use self::status::{PlayerStatus, ServerStatus};
+pub use self::tls::TlsConfig;
+
+/// Unique ID for a remote connection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConnectionId(pub(crate) usize);
+
+impl ConnectionId {
+  pub fn id(&self) -> usize {
+    self.0
+  }
+}
+
+/// State shared between the game and the networking thread.
+#[derive(Clone, Default)]
+pub(crate) struct NetworkOptions {
+  pub(crate) bans: BanList,
+  pub(crate) limits: ConnectionLimits,
+  pub(crate) origins: AllowedOrigins,
+  pub(crate) tls: Option<TlsConfig>,
+  pub(crate) proxy: ProxyConfig,
+  pub(crate) status: SharedStatus,
+}
+
+pub(crate) struct ConnectionData {
+  /// The outgoing message queue. This is bounded so a client that stops
+  /// reading can't make the server buffer messages for it indefinitely.
+  pub(crate) send: AsyncSender<Arc<Vec<u8>>>,
+  pub(crate) addr: SocketAddr,
+  /// The time at which the send queue first went over the high-water mark.
+  pub(crate) backlogged_since: Option<Instant>,
+  pub(crate) inbound: InboundRate,
+  /// The total number of bytes queued to be sent on this connection.
+  pub(crate) bytes_sent: u64,
+}
+
+impl ConnectionData {
+  pub(crate) fn new(send: AsyncSender<Arc<Vec<u8>>>, addr: SocketAddr) -> Self {
+    Self {
+      send,
+      addr,
+      backlogged_since: None,
+      inbound: Default::default(),
+      bytes_sent: 0,
+    }
+  }
+
+  /// The number of messages that have been queued but not yet picked up by
+  /// the connection task.
+  pub(crate) fn queued(&self) -> usize {
+    self.send.max_capacity() - self.send.capacity()
+  }
+}
+
+/// Limits on the outgoing message queue of each connection.
+///
+/// Once a connection has more than `high_water_mark` messages queued then
+/// non-critical packets (e.g. [`PlayerUpdate`]) will be dropped instead of
+/// being queued. Connections that stay above the high-water mark for longer
+/// than `backlog_timeout`, or that have more than `max_queued` messages queued,
+/// will be disconnected.
+///
+/// The send queue of each network connection is a bounded channel whose
+/// capacity is the default `max_queued`, so lowering `max_queued` takes effect
+/// immediately while raising it above the default has no effect on network
+/// connections.
+///
+/// [`PlayerUpdate`]: crate::protocol::server::PlayerUpdate
+#[derive(Copy, Clone, Debug)]
+pub struct QueueLimits {
+  pub high_water_mark: usize,
+  pub max_queued: usize,
+  pub backlog_timeout: Duration,
+}
+
+impl QueueLimits {
+  /// Queue limits that will never drop messages or disconnect clients.
+  pub fn unlimited() -> Self {
+    Self {
+      high_water_mark: usize::MAX,
+      max_queued: usize::MAX,
+      backlog_timeout: Duration::MAX,
+    }
+  }
+}
+
+impl Default for QueueLimits {
+  fn default() -> Self {
+    Self {
+      high_water_mark: 256,
+      max_queued: 2048,
+      backlog_timeout: Duration::from_secs(10),
+    }
+  }
+}
+
+/// Statistics about the outgoing message queues of all connections.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct QueueStats {
+  /// The total number of messages queued across all connections.
+  pub queued: usize,
+  /// The number of messages queued on the most backlogged connection.
+  pub max_depth: usize,
+  /// The number of non-critical messages that have been dropped.
+  pub dropped: u64,
+}
+
+/// Categories of inbound packets. Each category is rate limited separately.
+///
+/// The category is determined from the opcode of the packet before it is
+/// deserialized, so malformed packets count against the category of their
+/// opcode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PacketCategory {
+  /// `Login` and `Backup` packets.
+  Login,
+  /// `Key` packets.
+  Key,
+  /// `Command` and `ScoreDetailed` packets.
+  Command,
+  /// `Chat`, `TeamChat`, `Whisper`, `Say`, and `VoteMute` packets.
+  Chat,
+  /// Everything else, including packets with an unknown opcode.
+  Other,
+}
+
+impl PacketCategory {
+  const COUNT: usize = 5;
+}
+
+/// A token bucket rate limit. Up to `burst` packets can be sent at once and the
+/// bucket refills at `rate` packets per second.
+#[derive(Copy, Clone, Debug)]
+pub struct RateLimit {
+  pub burst: u32,
+  pub rate: f32,
+}
+
+impl RateLimit {
+  pub fn new(burst: u32, rate: f32) -> Self {
+    Self { burst, rate }
+  }
+}
+
+/// Limits on the rate at which each connection may send packets.
+///
+/// Packets that go over the limit for their category are dropped without being
+/// processed. If a connection has more than `flood_threshold` packets dropped
+/// within `flood_window` then it is disconnected. A limit of `None` means that
+/// packets in that category are not rate limited.
+#[derive(Copy, Clone, Debug)]
+pub struct PacketLimits {
+  pub login: Option<RateLimit>,
+  pub key: Option<RateLimit>,
+  pub command: Option<RateLimit>,
+  pub chat: Option<RateLimit>,
+  pub other: Option<RateLimit>,
+
+  pub flood_threshold: u32,
+  pub flood_window: Duration,
+}
+
+impl PacketLimits {
+  /// Packet limits that will never drop packets or disconnect clients.
+  pub fn unlimited() -> Self {
+    Self {
+      login: None,
+      key: None,
+      command: None,
+      chat: None,
+      other: None,
+      flood_threshold: u32::MAX,
+      flood_window: Duration::ZERO,
+    }
+  }
+
+  /// Get the limit for a category of packets.
+  pub fn get(&self, category: PacketCategory) -> Option<RateLimit> {
+    match category {
+      PacketCategory::Login => self.login,
+      PacketCategory::Key => self.key,
+      PacketCategory::Command => self.command,
+      PacketCategory::Chat => self.chat,
+      PacketCategory::Other => self.other,
+    }
+  }
+}
+
+impl Default for PacketLimits {
+  fn default() -> Self {
+    Self {
+      login: Some(RateLimit::new(2, 0.2)),
+      key: Some(RateLimit::new(60, 30.0)),
+      command: Some(RateLimit::new(10, 2.0)),
+      chat: Some(RateLimit::new(10, 2.0)),
+      other: Some(RateLimit::new(60, 30.0)),
+      flood_threshold: 100,
+      flood_window: Duration::from_secs(10),
+    }
+  }
+}
+
+/// The result of checking an inbound packet against the [`PacketLimits`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RateCheck {
+  /// The packet should be processed.
+  Allowed,
+  /// The packet is over the rate limit and should be dropped.
+  Dropped,
+  /// The packet should be dropped and the connection has been sending so many
+  /// packets that it should be disconnected.
+  Flooding,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct TokenBucket {
+  tokens: f32,
+  last: Instant,
+}
+
+/// Inbound rate limiting state for a single connection.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct InboundRate {
+  buckets: [Option<TokenBucket>; PacketCategory::COUNT],
+  dropped: u32,
+  window_start: Option<Instant>,
+}
+
+impl InboundRate {
+  fn check(&mut self, limits: &PacketLimits, category: PacketCategory, time: Instant) -> RateCheck {
+    let limit = match limits.get(category) {
+      Some(limit) => limit,
+      None => return RateCheck::Allowed,
+    };
+
+    let bucket = self.buckets[category as usize].get_or_insert(TokenBucket {
+      tokens: limit.burst as f32,
+      last: time,
+    });
+
+    let elapsed = time.saturating_duration_since(bucket.last);
+    bucket.tokens = (bucket.tokens + elapsed.as_secs_f32() * limit.rate).min(limit.burst as f32);
+    bucket.last = bucket.last.max(time);
+
+    if bucket.tokens >= 1.0 {
+      bucket.tokens -= 1.0;
+      return RateCheck::Allowed;
+    }
+
+    let window_start = *self.window_start.get_or_insert(time);
+    if time.saturating_duration_since(window_start) > limits.flood_window {
+      self.window_start = Some(time);
+      self.dropped = 0;
+    }
+
+    self.dropped += 1;
+    if self.dropped > limits.flood_threshold {
+      RateCheck::Flooding
+    } else {
+      RateCheck::Dropped
+    }
+  }
+}
+
+pub(crate) enum InternalEvent {
+  Opened(ConnectionData),
+  Data {
+    data: Vec<u8>,
+    time: Instant,
+  },
+  Closed,
+  /// The server closed the connection in the session being replayed.
+  ServerClosed,
+}
+
+pub(crate) enum ConnectionEvent {
+  Opened,
+  Data { data: Vec<u8>, time: Instant },
+  Closed(Option<Entity>),
+}
+
+/// Interface for communicating with the networking side of the server.
+///
+/// The only way to initialize the server here is by calling
+/// [`AirmashGame::with_network`] so you usually won't need to interact with
+/// this struct directly. The one exception is for test cases in which case you
+/// want to call [`disconnected`] to get a mock connection endpoint that can be
+/// used to send messages without having to open up an actual server port.
+///
+/// [`AirmashGame::with_network`]: crate::AirmashGame::with_network
+/// [`disconnected`]: crate::network::ConnectionMgr::disconnected
+pub struct ConnectionMgr {
+  conns: HashMap<ConnectionId, ConnectionData>,
+  primary: HashMap<Entity, ConnectionId>,
+  known: HashMap<ConnectionId, Entity>,
+  closing: VecDeque<ConnectionId>,
+  /// Entities whose primary connection was closed by the server. These don't
+  /// get the chance to resume their session from a new connection.
+  evicted: HashSet<Entity>,
+  limits: QueueLimits,
+  packet_limits: PacketLimits,
+  dropped: u64,
+  recorder: Option<Recorder>,
+  demo: Option<DemoRecorder>,
+  /// Whether events are being fed in from a [`Replay`]. The replay already
+  /// contains the events for connections closed by the server so they are
+  /// not generated again.
+  replaying: bool,
+  /// Connections from a [`Replay`] that are to be closed once the send queues
+  /// are next checked.
+  replay_closes: Vec<ConnectionId>,
+
+  recv: Receiver<(ConnectionId, InternalEvent)>,
+  handle: Option<JoinHandle<()>>,
+  shutdown: Arc<AtomicBool>,
+}
+
+impl ConnectionMgr {
+  pub(crate) fn with_server(
+    addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    options: NetworkOptions,
+  ) -> Self {
+    let (evttx, evtrx) = unbounded();
+
+    let handle = std::thread::spawn({
+      let shutdown = Arc::clone(&shutdown);
+      move || server_thread(addr, evttx, shutdown, options)
+    });
+
+    Self {
+      conns: Default::default(),
+      primary: Default::default(),
+      known: Default::default(),
+      closing: Default::default(),
+      evicted: Default::default(),
+      limits: Default::default(),
+      packet_limits: Default::default(),
+      dropped: 0,
+      recorder: None,
+      demo: None,
+      replaying: false,
+      replay_closes: Vec::new(),
+      recv: evtrx,
+      handle: Some(handle),
+      shutdown,
+    }
+  }
+
+  /// Create a connection manager with no associated server. This is meant to
+  /// allow for testing and generally shouldn't be used otherwise.
+  pub fn disconnected() -> (Self, MockConnectionEndpoint) {
+    let (tx, rx) = unbounded();
+
+    let me = Self {
+      conns: Default::default(),
+      primary: Default::default(),
+      known: Default::default(),
+      closing: Default::default(),
+      evicted: Default::default(),
+      // Mock connections only drain their queue when a test reads from them so
+      // limits are disabled unless a test explicitly sets them.
+      limits: QueueLimits::unlimited(),
+      packet_limits: PacketLimits::unlimited(),
+      dropped: 0,
+      recorder: None,
+      demo: None,
+      replaying: false,
+      replay_closes: Vec::new(),
+      recv: rx,
+      handle: None,
+      shutdown: Arc::new(AtomicBool::new(false)),
+    };
+    let mock = MockConnectionEndpoint::new(tx);
+
+    (me, mock)
+  }
+
+  /// Create a connection manager for playing back a [`Replay`]. Nothing reads
+  /// from the outgoing queues so connections are never closed for falling
+  /// behind, instead the replay closes them wherever the recorded server did.
+  fn replaying() -> (Self, Sender<(ConnectionId, InternalEvent)>) {
+    let (tx, rx) = unbounded();
+
+    let me = Self {
+      conns: Default::default(),
+      primary: Default::default(),
+      known: Default::default(),
+      closing: Default::default(),
+      evicted: Default::default(),
+      limits: QueueLimits::unlimited(),
+      packet_limits: Default::default(),
+      dropped: 0,
+      recorder: None,
+      demo: None,
+      replaying: true,
+      replay_closes: Vec::new(),
+      recv: rx,
+      handle: None,
+      shutdown: Arc::new(AtomicBool::new(false)),
+    };
+
+    (me, tx)
+  }
+
+  /// Set the recorder that inbound events will be written to, or stop
+  /// recording if `None`.
+  pub fn set_recorder(&mut self, recorder: Option<Recorder>) {
+    if let Some(mut old) = std::mem::replace(&mut self.recorder, recorder) {
+      if let Err(e) = old.flush() {
+        warn!("Unable to write session recording: {}", e);
+      }
+    }
+  }
+
+  /// Set the recorder that outbound packets will be written to as a demo, or
+  /// stop recording if `None`.
+  pub fn set_demo_recorder(&mut self, demo: Option<DemoRecorder>) {
+    if let Some(mut old) = std::mem::replace(&mut self.demo, demo) {
+      if let Err(e) = old.flush() {
+        warn!("Unable to write demo: {}", e);
+      }
+    }
+  }
+
+  /// The target of the demo currently being recorded, if there is one.
+  pub fn demo_target(&self) -> Option<DemoTarget> {
+    self.demo.as_ref().map(|demo| demo.target())
+  }
+
+  pub(crate) fn record_demo_packet(&mut self, time: Instant, data: &[u8]) {
+    if let Some(demo) = &mut self.demo {
+      if let Err(e) = demo.packet(time, data) {
+        warn!("Unable to write demo, stopping: {}", e);
+        self.demo = None;
+      }
+    }
+  }
+
+  /// Mark the start of a new frame in the session recording, if there is one.
+  /// This also flushes the packets sent during the previous frame to the demo
+  /// being recorded.
+  pub(crate) fn begin_frame(&mut self, now: Instant) {
+    if let Some(recorder) = &mut self.recorder {
+      if let Err(e) = recorder.frame(now) {
+        warn!("Unable to write session recording, stopping: {}", e);
+        self.recorder = None;
+      }
+    }
+
+    if let Some(demo) = &mut self.demo {
+      if let Err(e) = demo.flush() {
+        warn!("Unable to write demo, stopping: {}", e);
+        self.demo = None;
+      }
+    }
+  }
+
+  /// Get the limits on the outgoing message queue of each connection.
+  pub fn queue_limits(&self) -> QueueLimits {
+    self.limits
+  }
+
+  pub fn set_queue_limits(&mut self, limits: QueueLimits) {
+    self.limits = limits;
+  }
+
+  /// Get the limits on the rate of inbound packets for each connection.
+  pub fn packet_limits(&self) -> PacketLimits {
+    self.packet_limits
+  }
+
+  pub fn set_packet_limits(&mut self, limits: PacketLimits) {
+    self.packet_limits = limits;
+  }
+
+  /// Check whether a packet received from `conn` at `time` is within the
+  /// connection's rate limit for `category`.
+  pub fn check_rate(
+    &mut self,
+    conn: ConnectionId,
+    category: PacketCategory,
+    time: Instant,
+  ) -> RateCheck {
+    match self.conns.get_mut(&conn) {
+      Some(data) => data.inbound.check(&self.packet_limits, category, time),
+      None => RateCheck::Dropped,
+    }
+  }
+
+  fn queue(&mut self, conn: ConnectionId, message: Arc<Vec<u8>>, droppable: bool) {
+    let data = match self.conns.get_mut(&conn) {
+      Some(data) => data,
+      None => return,
+    };
+
+    let queued = data.queued();
+    if droppable && queued >= self.limits.high_water_mark {
+      self.dropped += 1;
+      return;
+    }
+
+    let len = message.len() as u64;
+    let result = if queued >= self.limits.max_queued {
+      Err(TrySendError::Full(message))
+    } else {
+      data.send.try_send(message)
+    };
+
+    match result {
+      Ok(()) => data.bytes_sent += len,
+      Err(TrySendError::Full(_)) if droppable => self.dropped += 1,
+      Err(TrySendError::Full(_)) => {
+        warn!(
+          "Closing connection {} since its send queue is full ({} messages)",
+          conn, queued
+        );
+        self.close(conn);
+      }
+      // The connection task has already exited.
+      Err(TrySendError::Closed(_)) => (),
+    }
+  }
+
+  pub fn send_to_conn(&mut self, conn: ConnectionId, message: Arc<Vec<u8>>) {
+    self.queue(conn, message, false);
+  }
+
+  pub fn send_to(&mut self, ent: Entity, message: Arc<Vec<u8>>) {
+    if let Some(&conn) = self.primary.get(&ent) {
+      self.send_to_conn(conn, message);
+    }
+  }
+
+  /// Send a non-critical message to a connection. The message will be dropped
+  /// if the connection's send queue is over the high-water mark.
+  pub fn send_droppable_to_conn(&mut self, conn: ConnectionId, message: Arc<Vec<u8>>) {
+    self.queue(conn, message, true);
+  }
+
+  /// Send a non-critical message to the primary connection of an entity. See
+  /// [`send_droppable_to_conn`](Self::send_droppable_to_conn).
+  pub fn send_droppable_to(&mut self, ent: Entity, message: Arc<Vec<u8>>) {
+    if let Some(&conn) = self.primary.get(&ent) {
+      self.send_droppable_to_conn(conn, message);
+    }
+  }
+
+  /// The number of messages queued for a connection that have not yet been
+  /// written to the socket.
+  pub fn queue_depth(&self, conn: ConnectionId) -> Option<usize> {
+    self.conns.get(&conn).map(|data| data.queued())
+  }
+
+  /// The total number of bytes sent on each open connection.
+  pub fn bytes_sent(&self) -> impl Iterator<Item = (ConnectionId, u64)> + '_ {
+    self
+      .conns
+      .iter()
+      .map(|(&conn, data)| (conn, data.bytes_sent))
+  }
+
+  /// Disconnect any connections that have been over the high-water mark for
+  /// longer than the backlog timeout and return statistics about the send
+  /// queues of the remaining connections.
+  pub(crate) fn evict_backlogged(&mut self, now: Instant) -> QueueStats {
+    let mut stats = QueueStats {
+      dropped: self.dropped,
+      ..Default::default()
+    };
+    let mut evicted = Vec::new();
+
+    for conn in std::mem::take(&mut self.replay_closes) {
+      self.close(conn);
+    }
+
+    for (&conn, data) in self.conns.iter_mut() {
+      let queued = data.queued();
+      if queued < self.limits.high_water_mark {
+        data.backlogged_since = None;
+      } else {
+        let since = *data.backlogged_since.get_or_insert(now);
+        if now.saturating_duration_since(since) > self.limits.backlog_timeout {
+          evicted.push(conn);
+          continue;
+        }
+      }
+
+      stats.queued += queued;
+      stats.max_depth = stats.max_depth.max(queued);
+    }
+
+    for conn in evicted {
+      warn!("Closing connection {} since it is too far behind", conn);
+      self.close(conn);
+    }
+
+    stats
+  }
+
+  pub fn socket_addr(&self, conn: ConnectionId) -> Option<SocketAddr> {
+    self.conns.get(&conn).map(|x| x.addr)
+  }
+
+  pub fn associate(&mut self, ent: Entity, conn: ConnectionId) {
+    self.known.insert(conn, ent);
+    self.primary.entry(ent).or_insert_with(|| conn);
+  }
+
+  pub fn associated(&self, conn: ConnectionId) -> Option<Entity> {
+    self.known.get(&conn).copied()
+  }
+
+  pub fn mark_primary(&mut self, ent: Entity, conn: ConnectionId) {
+    self.primary.insert(ent, conn);
+  }
+
+  /// Get the primary connection for an entity, if it has one.
+  pub fn primary(&self, ent: Entity) -> Option<ConnectionId> {
+    self.primary.get(&ent).copied()
+  }
+
+  /// Close a connection from the server side.
+  ///
+  /// Any packets that have already been sent to the connection will still be
+  /// delivered before the connection is closed. The connection will be treated
+  /// as closed starting with the next call to `next_packet` so if it was the
+  /// primary connection for a player then that player will leave the game
+  /// without being given a chance to resume their session.
+  pub fn close(&mut self, conn: ConnectionId) {
+    // Dropping the sender causes the connection task to shut down once it has
+    // flushed the remaining messages.
+    if self.conns.remove(&conn).is_some() {
+      if let Some(&ent) = self.known.get(&conn) {
+        if self.primary.get(&ent) == Some(&conn) {
+          self.evicted.insert(ent);
+        }
+      }
+
+      self.closing.push_back(conn);
+
+      if let Some(recorder) = &mut self.recorder {
+        if let Err(e) = recorder.server_closed(conn) {
+          warn!("Unable to write session recording, stopping: {}", e);
+          self.recorder = None;
+        }
+      }
+    }
+  }
+
+  /// Close connections from a [`Replay`] that were closed by the recorded
+  /// server after it had processed the inbound events for the frame.
+  pub(crate) fn close_after_input(&mut self, conns: Vec<ConnectionId>) {
+    self.replay_closes.extend(conns);
+  }
+
+  /// Close all connections associated with an entity.
+  pub fn close_entity(&mut self, ent: Entity) {
+    let conns: Vec<_> = self
+      .known
+      .iter()
+      .filter(|(_, &known)| known == ent)
+      .map(|(&conn, _)| conn)
+      .collect();
+
+    for conn in conns {
+      self.close(conn);
+    }
+  }
+
+  /// Whether the primary connection of `ent` was closed by the server. This
+  /// resets once it has been checked.
+  pub(crate) fn take_evicted(&mut self, ent: Entity) -> bool {
+    self.evicted.remove(&ent)
+  }
+
+  /// Get a connection associated with an entity that isn't its primary
+  /// connection, if there is one.
+  pub fn backup(&self, ent: Entity) -> Option<ConnectionId> {
+    let primary = self.primary(ent);
+
+    self
+      .known
+      .iter()
+      .filter(|&(&conn, &known)| known == ent && Some(conn) != primary)
+      .map(|(&conn, _)| conn)
+      .min_by_key(|conn| conn.0)
+  }
+
+  fn closed(&mut self, conn: ConnectionId) -> ConnectionEvent {
+    ConnectionEvent::Closed(match self.known.remove(&conn) {
+      Some(ent) => match self.primary.get(&ent) {
+        Some(&econn) if econn == conn => {
+          self.primary.remove(&ent);
+          Some(ent)
+        }
+        _ => None,
+      },
+      _ => None,
+    })
+  }
+
+  pub(crate) fn next_packet(&mut self) -> Option<(ConnectionId, ConnectionEvent)> {
+    let (conn, evt) = match self.next_event() {
+      Some(event) => event,
+      None => {
+        if let Some(recorder) = &mut self.recorder {
+          recorder.drained();
+        }
+        return None;
+      }
+    };
+
+    if let Some(recorder) = &mut self.recorder {
+      let addr = self.conns.get(&conn).map(|data| data.addr);
+      if let Err(e) = recorder.event(conn, &evt, addr) {
+        warn!("Unable to write session recording, stopping: {}", e);
+        self.recorder = None;
+      }
+    }
+
+    Some((conn, evt))
+  }
+
+  fn next_event(&mut self) -> Option<(ConnectionId, ConnectionEvent)> {
+    if self.replaying {
+      self.closing.clear();
+    }
+
+    if let Some(conn) = self.closing.pop_front() {
+      return Some((conn, self.closed(conn)));
+    }
+
+    let (conn, evt) = self.recv.try_recv().ok()?;
+
+    Some((
+      conn,
+      match evt {
+        InternalEvent::Opened(data) => {
+          self.conns.insert(conn, data);
+          ConnectionEvent::Opened
+        }
+        InternalEvent::Data { data, time } => ConnectionEvent::Data { data, time },
+        InternalEvent::Closed => self.closed(conn),
+        InternalEvent::ServerClosed => {
+          self.close(conn);
+          return self.next_event();
+        }
+      },
+    ))
+  }
+}
+
+impl Drop for ConnectionMgr {
+  fn drop(&mut self) {
+    self.shutdown.store(true, Ordering::Relaxed);
+    if let Some(handle) = self.handle.take() {
+      let _ = handle.join();
+    }
+  }
+}
+
+fn server_thread(
+  addr: SocketAddr,
+  send: Sender<(ConnectionId, InternalEvent)>,
+  shutdown: Arc<AtomicBool>,
+  options: NetworkOptions,
+) {
+  use tokio::runtime::Builder;
+
+  #[cfg(feature = "mt-network")]
+  let mut builder = Builder::new_multi_thread();
+  #[cfg(not(feature = "mt-network"))]
+  let mut builder = Builder::new_current_thread();
+
+  let rt = builder
+    .enable_all()
+    .build()
+    .expect("Failed to initialize tokio runtime");
+
+  if let Err(e) = rt.block_on(run_server(addr, send, shutdown.clone(), options)) {
+    error!("Websocket server shutting down with error: {}", e);
+  }
+
+  shutdown.store(true, Ordering::Relaxed);
+}
+
+async fn run_server(
+  addr: SocketAddr,
+  send: Sender<(ConnectionId, InternalEvent)>,
+  shutdown: Arc<AtomicBool>,
+  options: NetworkOptions,
+) -> std::io::Result<()> {
+  let socket = TcpListener::bind(&addr).await?;
+  match options.tls {
+    Some(_) => info!("Listening on {} with TLS", addr),
+    None => info!("Listening on {}", addr),
+  }
+
+  let mut connid: usize = 0;
+
+  while !shutdown.load(Ordering::Relaxed) {
+    let send = send.clone();
+    let options = options.clone();
+    let conn = ConnectionId(connid);
+    connid += 1;
+
+    tokio::select! {
+      res = socket.accept() => {
+        let (mut stream, peer) = res?;
+
+        tokio::spawn(async move {
+          let addr = match options.proxy.accept(&mut stream, peer).await {
+            Ok(addr) => addr,
+            Err(e) => {
+              debug!("Invalid PROXY protocol header from {}: {}", peer, e);
+              return;
+            }
+          };
+
+          // The connection counts towards the per-IP limit from here on so
+          // that clients can't get around it by never finishing the handshake.
+          // If the client address comes from the request headers then it is
+          // counted once they have been read instead.
+          let open = match options.proxy.uses_forwarded(addr) {
+            true => None,
+            false => match options.limits.open(addr.ip()) {
+              Some(open) => Some(open),
+              None => {
+                info!("{} - Too many connections, closing", addr.ip());
+                if options.tls.is_none() {
+                  let _ = respond(&mut stream, TOO_MANY_REQUESTS).await;
+                }
+                return;
+              }
+            },
+          };
+
+          let _ = match &options.tls {
+            Some(tls) => match tls.acceptor().accept(stream).await {
+              Ok(stream) => run_connection(stream, addr, open, conn, &send, &options).await,
+              Err(e) => {
+                debug!("TLS handshake with {} failed: {}", addr, e);
+                return;
+              }
+            },
+            None => run_connection(stream, addr, open, conn, &send, &options).await,
+          };
+          let _ = send.send((conn, InternalEvent::Closed));
+        });
+      }
+      _ = tokio::time::sleep(std::time::Duration::from_secs(1)) => ()
+    }
+  }
+
+  Ok(())
+}
+
+async fn run_connection<S>(
+  stream: S,
+  mut addr: SocketAddr,
+  open: Option<OpenConnection>,
+  conn: ConnectionId,
+  events: &Sender<(ConnectionId, InternalEvent)>,
+  options: &NetworkOptions,
+) -> std::io::Result<()>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  // The connection counts towards the per-IP limit until this is dropped.
+  let (mut ws_stream, _open) = match websocket_handshake(stream, &mut addr, open, options).await? {
+    Some(accepted) => accepted,
+    None => return Ok(()),
+  };
+
+  let (tx, mut rx) = channel(QueueLimits::default().max_queued);
+
+  if events
+    .send((conn, InternalEvent::Opened(ConnectionData::new(tx, addr))))
+    .is_err()
+  {
+    return Ok(());
+  }
+
+  loop {
+    tokio::select! {
+      read = ws_stream.next() => {
+        let msg = match read {
+          Some(Ok(read)) => read,
+          _ => return Ok(())
+        };
+
+        if msg.is_binary() || msg.is_text() {
+          let evt = InternalEvent::Data {
+            data: msg.into_data(),
+            time: Instant::now()
+          };
+
+          if events.send((conn, evt)).is_err() {
+            return Ok(())
+          }
+        } else {
+          match msg {
+            Message::Ping(data) => {
+              let _ =  ws_stream.send(Message::Pong(data)).await;
+            }
+            Message::Pong(_) => (),
+            Message::Close(_) => return Ok(()),
+            _ => unreachable!()
+          }
+        }
+      }
+      write = rx.recv() => {
+        let write = match write {
+          Some(write) => write,
+          None => return Ok(())
+        };
+
+        let data = match Arc::try_unwrap(write) {
+          Ok(data) => data,
+          Err(e) => (*e).clone()
+        };
+
+        if ws_stream.send(Message::binary(data)).await.is_err() {
+          return Ok(())
+        }
+      }
+    }
+  }
+}
+
+impl fmt::Display for ConnectionId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+fn log_request(addr: &SocketAddr, code: u16, request: &httparse::Request) {
+  use crate::util::escapes::StringEscape;
+
+  info!(
+    "{} - \"{} {} HTTP/1.{}\" {} \"{}\"",
+    addr.ip(),
+    request.method.unwrap_or("\"\""),
+    request.path.unwrap_or("\"\""),
+    request.version.unwrap_or(0),
+    code,
+    get_header(request, "User-Agent")
+      .unwrap_or_default()
+      .escaped(),
+  );
+}
+
+fn get_header<'h, 'b>(request: &httparse::Request<'h, 'b>, name: &str) -> Option<&'b [u8]> {
+  request
+    .headers
+    .iter()
+    .filter(|h| h.name.eq_ignore_ascii_case(name))
+    .map(|h| h.value)
+    .next()
+}
+
+fn has_header(request: &httparse::Request, name: &str, value: &str) -> bool {
+  request
+    .headers
+    .iter()
+    .any(|h| h.name.eq_ignore_ascii_case(name) && h.value.eq_ignore_ascii_case(value.as_bytes()))
+}
+
+/// Write a response to a request that isn't being upgraded to a websocket
+/// and then close the stream.
+async fn respond<S>(stream: &mut S, response: &[u8]) -> std::io::Result<()>
+where
+  S: AsyncWrite + Unpin,
+{
+  stream.write_all(response).await?;
+  stream.shutdown().await
+}
+
+const TOO_MANY_REQUESTS: &[u8] = b"HTTP/1.0 429 Too Many Requests\r\n\r\n";
+
+/// The largest HTTP request that will be accepted for a websocket upgrade.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Perform the websocket handshake. `open` is the connection's slot in the
+/// per-IP connection limit, or `None` if the client address will only be known
+/// once the request headers have been read.
+async fn websocket_handshake<S>(
+  mut stream: S,
+  addr: &mut SocketAddr,
+  open: Option<OpenConnection>,
+  options: &NetworkOptions,
+) -> std::io::Result<Option<(WebSocketStream<S>, OpenConnection)>>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  use std::io::Error;
+
+  use httparse::Request;
+  use tungstenite::error::ProtocolError;
+  use tungstenite::protocol::Role;
+
+  const BAD_REQUEST: &[u8] = b"HTTP/1.0 400 Bad Request\r\n\r\n";
+  const BAD_PROTOCOL: &[u8] = b"HTTP/1.0 405 Method Not Allowed\r\n\r\n";
+  const FORBIDDEN: &[u8] = b"HTTP/1.0 403 Forbidden\r\n\r\n";
+  const TOO_LARGE: &[u8] = b"HTTP/1.0 431 Request Header Fields Too Large\r\n\r\n";
+
+  let mut buf = Vec::new();
+
+  let open = loop {
+    // The client closed the connection before finishing its request.
+    if stream.read_buf(&mut buf).await? == 0 {
+      return Ok(None);
+    }
+
+    let mut headers = [EMPTY_HEADER; 32];
+    let mut request = Request::new(&mut headers);
+
+    let bytes = match request.parse(&buf) {
+      Ok(Status::Complete(bytes)) => bytes,
+      Ok(Status::Partial) if buf.len() >= MAX_REQUEST_SIZE => {
+        info!("{} - Request headers too large", addr.ip());
+        respond(&mut stream, TOO_LARGE).await?;
+        return Ok(None);
+      }
+      Ok(Status::Partial) => continue,
+      Err(e) => {
+        log_request(addr, 400, &request);
+        respond(&mut stream, BAD_REQUEST).await?;
+        return Err(Error::new(ErrorKind::Other, e));
+      }
+    };
+
+    *addr = options.proxy.forwarded(&request, *addr);
+
+    if options.bans.is_banned(&BanTarget::Ip(addr.ip())) {
+      log_request(addr, 403, &request);
+      respond(&mut stream, FORBIDDEN).await?;
+      return Ok(None);
+    }
+
+    if request.method != Some("GET") {
+      log_request(addr, 405, &request);
+      respond(&mut stream, BAD_PROTOCOL).await?;
+      return Err(Error::new(ErrorKind::Other, ProtocolError::WrongHttpMethod));
+    }
+
+    let has_connection_upgrade = request
+      .headers
+      .iter()
+      .filter(|h| h.name.eq_ignore_ascii_case("Connection"))
+      .any(|h| {
+        h.value
+          .split(|&c| c == b' ' || c == b',')
+          .any(|p| p.eq_ignore_ascii_case(b"Upgrade"))
+      });
+    let has_upgrade_websocket = has_header(&request, "Upgrade", "websocket");
+
+    if !has_connection_upgrade && !has_upgrade_websocket {
+      let (code, response) = options.status.respond(request.path.unwrap_or("/"));
+      log_request(addr, code, &request);
+      respond(&mut stream, &response).await?;
+      return Ok(None);
+    }
+
+    if !has_connection_upgrade
+      || !has_upgrade_websocket
+      || !has_header(&request, "Sec-Websocket-Version", "13")
+    {
+      log_request(addr, 400, &request);
+      respond(&mut stream, BAD_REQUEST).await?;
+      return Err(Error::new(
+        ErrorKind::Other,
+        ProtocolError::MissingConnectionUpgradeHeader,
+      ));
+    }
+
+    let key = match get_header(&request, "Sec-Websocket-Key") {
+      Some(key) => key,
+      None => {
+        log_request(addr, 400, &request);
+        respond(&mut stream, BAD_REQUEST).await?;
+        return Err(Error::new(
+          ErrorKind::Other,
+          ProtocolError::MissingSecWebSocketKey,
+        ));
+      }
+    };
+
+    let origin = get_header(&request, "Origin").map(String::from_utf8_lossy);
+    if let Some(origin) = origin {
+      if !options.origins.is_allowed(&origin) {
+        log_request(addr, 403, &request);
+        respond(&mut stream, FORBIDDEN).await?;
+        return Ok(None);
+      }
+    }
+
+    let open = match open {
+      Some(open) => open,
+      None => match options.limits.open(addr.ip()) {
+        Some(open) => open,
+        None => {
+          log_request(addr, 429, &request);
+          respond(&mut stream, TOO_MANY_REQUESTS).await?;
+          return Ok(None);
+        }
+      },
+    };
+
+    let response = format!(
+      "HTTP/1.1 101 Switching Protocols\r\n\
+      Connection: Upgrade\r\n\
+      Upgrade: websocket\r\n\
+      Sec-WebSocket-Accept: {}\r\n\
+      \r\n",
+      tungstenite::handshake::derive_accept_key(key)
+    );
+    log_request(addr, 101, &request);
+    stream.write_all(response.as_bytes()).await?;
+
+    buf.drain(..bytes);
+    break open;
+  };
+
+  let wss = WebSocketStream::from_partially_read(stream, buf, Role::Server, None).await;
+  Ok(Some((wss, open)))
+}
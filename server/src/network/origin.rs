@@ -0,0 +1,78 @@
+use std::sync::{Arc, RwLock};
+
+/// The list of page origins that are allowed to open websocket connections to
+/// the server.
+///
+/// Browsers send the origin of the page that opened a websocket in the
+/// `Origin` header so checking it stops other sites from embedding the server.
+/// By default all origins are allowed. Once a list has been set, upgrade
+/// requests with an origin that doesn't match any entry are rejected with
+/// `403 Forbidden`. Requests without an `Origin` header are not made by
+/// browsers and are always allowed.
+///
+/// Entries are either exact origins (e.g. `https://airmash.online`) or
+/// contain `*` wildcards which match any sequence of characters (e.g.
+/// `https://*.airmash.online`). Matching ignores case.
+///
+/// This is shared with the networking thread. Cloning it gives another handle
+/// to the same underlying list.
+#[derive(Clone, Debug, Default)]
+pub struct AllowedOrigins {
+  inner: Arc<RwLock<Option<Vec<String>>>>,
+}
+
+impl AllowedOrigins {
+  /// Only allow origins that match one of `patterns`.
+  pub fn set<I, S>(&self, patterns: I)
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    let patterns = patterns
+      .into_iter()
+      .map(|pattern| pattern.as_ref().trim().to_ascii_lowercase())
+      .filter(|pattern| !pattern.is_empty())
+      .collect();
+
+    *self.inner.write().unwrap() = Some(patterns);
+  }
+
+  /// Remove the list and allow connections from any origin.
+  pub fn allow_all(&self) {
+    *self.inner.write().unwrap() = None;
+  }
+
+  /// The current list of allowed origins, or `None` if all origins are
+  /// allowed.
+  pub fn patterns(&self) -> Option<Vec<String>> {
+    self.inner.read().unwrap().clone()
+  }
+
+  pub fn is_allowed(&self, origin: &str) -> bool {
+    let origin = origin.trim().to_ascii_lowercase();
+
+    match &*self.inner.read().unwrap() {
+      Some(patterns) => patterns
+        .iter()
+        .any(|pattern| wildcard_match(pattern.as_bytes(), origin.as_bytes())),
+      None => true,
+    }
+  }
+}
+
+/// Match `text` against `pattern` where `*` in the pattern matches any
+/// sequence of characters.
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+  match pattern.iter().position(|&c| c == b'*') {
+    None => pattern == text,
+    Some(star) => {
+      let (prefix, rest) = (&pattern[..star], &pattern[star + 1..]);
+      if !text.starts_with(prefix) {
+        return false;
+      }
+
+      let text = &text[prefix.len()..];
+      (0..=text.len()).any(|skip| wildcard_match(rest, &text[skip..]))
+    }
+  }
+}
@@ -0,0 +1,310 @@
+//! Resolving the real address of clients that connect through a reverse proxy
+//! or load balancer.
+//!
+//! Two mechanisms are supported, both of which are only honoured for
+//! connections coming from a trusted proxy:
+//! - The [PROXY protocol] (v1 and v2), where the proxy sends a header with the
+//!   client's address before any other data on the connection.
+//! - The `X-Forwarded-For` and `X-Real-IP` HTTP headers on the websocket
+//!   upgrade request.
+//!
+//! [PROXY protocol]: https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt
+
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// A range of IP addresses in CIDR notation (e.g. `10.0.0.0/8`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IpCidr {
+  addr: IpAddr,
+  prefix: u8,
+}
+
+impl IpCidr {
+  /// Create a new range. Returns `None` if the prefix is longer than the
+  /// address.
+  pub fn new(addr: IpAddr, prefix: u8) -> Option<Self> {
+    let max = match addr {
+      IpAddr::V4(_) => 32,
+      IpAddr::V6(_) => 128,
+    };
+
+    if prefix > max {
+      return None;
+    }
+
+    Some(Self { addr, prefix })
+  }
+
+  /// Parse a comma-separated list of ranges (e.g. `10.0.0.0/8,::1`).
+  pub fn parse_list(list: &str) -> Result<Vec<Self>, String> {
+    list
+      .split(',')
+      .filter(|range| !range.trim().is_empty())
+      .map(str::parse)
+      .collect()
+  }
+
+  /// Whether `ip` falls within this range.
+  pub fn contains(&self, ip: IpAddr) -> bool {
+    match (self.addr, canonical(ip)) {
+      (IpAddr::V4(net), IpAddr::V4(ip)) => {
+        let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+        u32::from(net) & mask == u32::from(ip) & mask
+      }
+      (IpAddr::V6(net), IpAddr::V6(ip)) => {
+        let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+        u128::from(net) & mask == u128::from(ip) & mask
+      }
+      _ => false,
+    }
+  }
+}
+
+impl FromStr for IpCidr {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || format!("invalid address range `{}`", s);
+
+    let (addr, prefix) = match s.split_once('/') {
+      Some((addr, prefix)) => (addr, Some(prefix)),
+      None => (s, None),
+    };
+
+    let addr: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+    let prefix = match prefix {
+      Some(prefix) => prefix.trim().parse().map_err(|_| invalid())?,
+      None if addr.is_ipv4() => 32,
+      None => 128,
+    };
+
+    Self::new(addr, prefix).ok_or_else(invalid)
+  }
+}
+
+impl fmt::Display for IpCidr {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}/{}", self.addr, self.prefix)
+  }
+}
+
+/// Settings for resolving client addresses behind a proxy. See
+/// [`ProxyConfig`].
+#[derive(Clone, Debug, Default)]
+pub struct ProxySettings {
+  /// Expect connections from trusted proxies to start with a PROXY protocol
+  /// header. Connections from trusted proxies without one are closed.
+  pub proxy_protocol: bool,
+  /// Use the `X-Forwarded-For` and `X-Real-IP` headers sent by trusted
+  /// proxies.
+  pub forwarded_headers: bool,
+  /// The proxies that are trusted to report the address of the client.
+  pub trusted: Vec<IpCidr>,
+}
+
+impl ProxySettings {
+  fn is_trusted(&self, ip: IpAddr) -> bool {
+    self.trusted.iter().any(|cidr| cidr.contains(ip))
+  }
+}
+
+/// How to find the address of clients that connect through a reverse proxy.
+///
+/// By default the address of the socket is used as is. This is shared with the
+/// networking thread and changes apply to all new connections. Cloning it
+/// gives another handle to the same underlying configuration.
+#[derive(Clone, Debug, Default)]
+pub struct ProxyConfig {
+  inner: Arc<RwLock<ProxySettings>>,
+}
+
+impl ProxyConfig {
+  pub fn set(&self, settings: ProxySettings) {
+    *self.inner.write().unwrap() = settings;
+  }
+
+  pub fn settings(&self) -> ProxySettings {
+    self.inner.read().unwrap().clone()
+  }
+
+  /// Read the PROXY protocol header from a new connection if it is expected
+  /// from `peer` and return the client address.
+  pub(crate) async fn accept<S>(&self, stream: &mut S, peer: SocketAddr) -> io::Result<SocketAddr>
+  where
+    S: AsyncRead + Unpin,
+  {
+    let expected = {
+      let settings = self.inner.read().unwrap();
+      settings.proxy_protocol && settings.is_trusted(peer.ip())
+    };
+
+    match expected {
+      true => read_proxy_header(stream, peer).await,
+      false => Ok(peer),
+    }
+  }
+
+  /// Whether the client address of connections from `peer` will be taken
+  /// from the forwarding headers of their request.
+  pub(crate) fn uses_forwarded(&self, peer: SocketAddr) -> bool {
+    let settings = self.inner.read().unwrap();
+    settings.forwarded_headers && settings.is_trusted(peer.ip())
+  }
+
+  /// Find the client address using the forwarding headers of a request made
+  /// by `peer`.
+  pub(crate) fn forwarded(&self, request: &httparse::Request, peer: SocketAddr) -> SocketAddr {
+    let settings = self.inner.read().unwrap();
+    if !settings.forwarded_headers || !settings.is_trusted(peer.ip()) {
+      return peer;
+    }
+
+    // Each proxy appends the address that it received the request from so the
+    // client is the last address that isn't one of our own proxies.
+    let forwarded_for = super::get_header(request, "X-Forwarded-For")
+      .and_then(|value| std::str::from_utf8(value).ok())
+      .and_then(|value| {
+        let addrs: Option<Vec<IpAddr>> = value
+          .split(',')
+          .map(|addr| addr.trim().parse().ok())
+          .collect();
+        let addrs = addrs?;
+
+        addrs
+          .iter()
+          .rev()
+          .find(|&&addr| !settings.is_trusted(addr))
+          .or_else(|| addrs.first())
+          .copied()
+      });
+
+    let real_ip = || {
+      super::get_header(request, "X-Real-IP")
+        .and_then(|value| std::str::from_utf8(value).ok())
+        .and_then(|value| value.trim().parse().ok())
+    };
+
+    match forwarded_for.or_else(real_ip) {
+      Some(ip) => SocketAddr::new(ip, peer.port()),
+      None => peer,
+    }
+  }
+}
+
+/// Map IPv4 addresses that were accepted on an IPv6 socket back to IPv4.
+fn canonical(ip: IpAddr) -> IpAddr {
+  match ip {
+    IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+      Some(v4) => IpAddr::V4(v4),
+      None => ip,
+    },
+    ip => ip,
+  }
+}
+
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+/// The longest possible v1 header, including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+
+async fn read_proxy_header<S>(stream: &mut S, peer: SocketAddr) -> io::Result<SocketAddr>
+where
+  S: AsyncRead + Unpin,
+{
+  // Both versions of the header are at least this long so this never reads
+  // past the end of the header.
+  let mut start = [0u8; 12];
+  stream.read_exact(&mut start).await?;
+
+  if &start == V2_SIGNATURE {
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+
+    let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body).await?;
+
+    return parse_v2(header[0], header[1], &body, peer);
+  }
+
+  if !start.starts_with(b"PROXY ") {
+    return Err(invalid_header("missing PROXY protocol header"));
+  }
+
+  let mut line = start.to_vec();
+  while !line.ends_with(b"\r\n") {
+    if line.len() >= V1_MAX_LEN {
+      return Err(invalid_header("PROXY protocol header is too long"));
+    }
+
+    line.push(stream.read_u8().await?);
+  }
+
+  parse_v1(&line, peer)
+}
+
+fn parse_v1(line: &[u8], peer: SocketAddr) -> io::Result<SocketAddr> {
+  let invalid = || invalid_header("malformed PROXY protocol v1 header");
+
+  let line = std::str::from_utf8(line).map_err(|_| invalid())?;
+  let mut parts = line.trim_end_matches("\r\n").split(' ').skip(1);
+
+  match parts.next() {
+    Some("TCP4") | Some("TCP6") => (),
+    // The proxy doesn't know where the connection came from (e.g. health
+    // checks) so fall back to the address of the proxy itself.
+    Some("UNKNOWN") => return Ok(peer),
+    _ => return Err(invalid()),
+  }
+
+  let src: IpAddr = parts
+    .next()
+    .and_then(|s| s.parse().ok())
+    .ok_or_else(invalid)?;
+  let _dst = parts.next().ok_or_else(invalid)?;
+  let port: u16 = parts
+    .next()
+    .and_then(|s| s.parse().ok())
+    .ok_or_else(invalid)?;
+
+  Ok(SocketAddr::new(src, port))
+}
+
+fn parse_v2(ver_cmd: u8, family: u8, body: &[u8], peer: SocketAddr) -> io::Result<SocketAddr> {
+  if ver_cmd >> 4 != 2 {
+    return Err(invalid_header("unsupported PROXY protocol version"));
+  }
+
+  match ver_cmd & 0xF {
+    // LOCAL connections are made by the proxy itself.
+    0x0 => return Ok(peer),
+    0x1 => (),
+    _ => return Err(invalid_header("unsupported PROXY protocol command")),
+  }
+
+  match family >> 4 {
+    0x1 if body.len() >= 12 => {
+      let ip = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+      let port = u16::from_be_bytes([body[8], body[9]]);
+      Ok(SocketAddr::new(ip.into(), port))
+    }
+    0x2 if body.len() >= 36 => {
+      let mut octets = [0u8; 16];
+      octets.copy_from_slice(&body[..16]);
+      let port = u16::from_be_bytes([body[32], body[33]]);
+      Ok(SocketAddr::new(Ipv6Addr::from(octets).into(), port))
+    }
+    0x1 | 0x2 => Err(invalid_header("truncated PROXY protocol v2 header")),
+    // Unix sockets and unspecified families carry no usable address.
+    _ => Ok(peer),
+  }
+}
+
+fn invalid_header(msg: &str) -> io::Error {
+  io::Error::new(ErrorKind::InvalidData, msg)
+}
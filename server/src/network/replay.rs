@@ -0,0 +1,368 @@
+//! Recording and replaying of the inbound side of a server session.
+//!
+//! A [`Recorder`] writes every connection event that the server processes to
+//! a file, along with the time of the frame in which it was processed and the
+//! seed of the [`GameRng`]. A [`Replay`] reads that file back and feeds the
+//! same events through the [`ConnectionMgr`] at the same frame times. As long
+//! as the server is set up with the same configuration, this reproduces the
+//! original session exactly, which is useful for debugging crash and desync
+//! reports.
+//!
+//! Connections that the server closes itself because their send queue fell
+//! too far behind depend on how quickly the client was reading, which a
+//! replay has no way of knowing. Every server-side close is therefore recorded
+//! as well and is applied at the same point when replaying.
+//!
+//! All data is stored relative to the [`StartTime`] of the game so a replay
+//! can be run at any time and as fast as the server can process it.
+//!
+//! [`StartTime`]: crate::resource::StartTime
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::{ConnectionData, ConnectionEvent, ConnectionId, ConnectionMgr, InternalEvent};
+use crate::event::ServerStartup;
+use crate::resource::GameRng;
+use crate::AirmashGame;
+
+const MAGIC: &[u8; 8] = b"AMREPLAY";
+const VERSION: u8 = 2;
+
+const TAG_FRAME: u8 = 0;
+const TAG_OPENED: u8 = 1;
+const TAG_DATA: u8 = 2;
+const TAG_CLOSED: u8 = 3;
+const TAG_SERVER_CLOSED: u8 = 4;
+
+/// A single event that was received on a connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordedEvent {
+  Opened(SocketAddr),
+  /// Data along with the time it was received, relative to the start time of
+  /// the server.
+  Data(Duration, Vec<u8>),
+  Closed,
+  /// The server closed the connection. `after_input` is set if this happened
+  /// after all the inbound events for the frame had been processed.
+  ServerClosed {
+    after_input: bool,
+  },
+}
+
+/// All the events processed within a single frame.
+#[derive(Clone, Debug, Default)]
+pub struct RecordedFrame {
+  /// Time of the frame, relative to the start time of the server.
+  pub time: Duration,
+  pub events: Vec<(ConnectionId, RecordedEvent)>,
+}
+
+/// Writes the inbound events processed by a [`ConnectionMgr`] to a file.
+///
+/// Use [`AirmashGame::record_session`] to start recording.
+pub struct Recorder {
+  out: BufWriter<File>,
+  start: Instant,
+  /// Whether all the inbound events for the current frame have been read.
+  drained: bool,
+}
+
+impl Recorder {
+  fn create(path: &Path, seed: u64, start: Instant) -> io::Result<Self> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(MAGIC)?;
+    out.write_all(&[VERSION])?;
+    out.write_all(&seed.to_le_bytes())?;
+
+    Ok(Self {
+      out,
+      start,
+      drained: false,
+    })
+  }
+
+  fn offset(&self, time: Instant) -> u64 {
+    time.saturating_duration_since(self.start).as_nanos() as u64
+  }
+
+  /// Mark the start of a new frame. The previous frame is flushed to disk so
+  /// that the recording is usable even if the server crashes.
+  pub(crate) fn frame(&mut self, time: Instant) -> io::Result<()> {
+    self.drained = false;
+    self.out.flush()?;
+    self.out.write_all(&[TAG_FRAME])?;
+    self.out.write_all(&self.offset(time).to_le_bytes())
+  }
+
+  pub(crate) fn event(
+    &mut self,
+    conn: ConnectionId,
+    event: &ConnectionEvent,
+    addr: Option<SocketAddr>,
+  ) -> io::Result<()> {
+    let conn = (conn.0 as u64).to_le_bytes();
+
+    match event {
+      ConnectionEvent::Opened => {
+        let addr = addr
+          .map(|a| a.to_string())
+          .unwrap_or_else(|| "0.0.0.0:0".to_owned());
+        self.out.write_all(&[TAG_OPENED])?;
+        self.out.write_all(&conn)?;
+        self.out.write_all(&[addr.len() as u8])?;
+        self.out.write_all(addr.as_bytes())
+      }
+      ConnectionEvent::Data { data, time } => {
+        self.out.write_all(&[TAG_DATA])?;
+        self.out.write_all(&conn)?;
+        self.out.write_all(&self.offset(*time).to_le_bytes())?;
+        self.out.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.out.write_all(data)
+      }
+      ConnectionEvent::Closed(_) => {
+        self.out.write_all(&[TAG_CLOSED])?;
+        self.out.write_all(&conn)
+      }
+    }
+  }
+
+  /// Mark that there are no more inbound events for the current frame.
+  pub(crate) fn drained(&mut self) {
+    self.drained = true;
+  }
+
+  /// Record that the server closed `conn`.
+  pub(crate) fn server_closed(&mut self, conn: ConnectionId) -> io::Result<()> {
+    self.out.write_all(&[TAG_SERVER_CLOSED])?;
+    self.out.write_all(&(conn.0 as u64).to_le_bytes())?;
+    self.out.write_all(&[self.drained as u8])
+  }
+
+  pub(crate) fn flush(&mut self) -> io::Result<()> {
+    self.out.flush()
+  }
+}
+
+impl AirmashGame {
+  /// Start recording all inbound connection events to a file so that the
+  /// session can later be reproduced with a [`Replay`].
+  ///
+  /// This reseeds the [`GameRng`] so it should be called before the server
+  /// starts running, otherwise anything that happened before the recording
+  /// started will be missing from the replay.
+  pub fn record_session(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+    let seed = self.resources.read::<GameRng>().seed();
+    let recorder = Recorder::create(path.as_ref(), seed, self.start_time())?;
+
+    self.resources.insert(GameRng::new(seed));
+    self
+      .resources
+      .write::<ConnectionMgr>()
+      .set_recorder(Some(recorder));
+    Ok(())
+  }
+}
+
+/// A recorded session that can be played back.
+#[derive(Clone, Debug, Default)]
+pub struct Replay {
+  seed: u64,
+  frames: Vec<RecordedFrame>,
+}
+
+impl Replay {
+  /// Load a recording written by a [`Recorder`].
+  ///
+  /// A recording that was cut short (e.g. because the server crashed while it
+  /// was being written) is loaded up to the last complete event.
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    Self::read(BufReader::new(File::open(path)?))
+  }
+
+  fn read(mut input: impl Read) -> io::Result<Self> {
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(invalid_data("not an airmash recording"));
+    }
+
+    let version = read_u8(&mut input)?;
+    if version != VERSION {
+      return Err(invalid_data(&format!(
+        "unsupported recording version {}",
+        version
+      )));
+    }
+
+    let seed = read_u64(&mut input)?;
+    let mut frames: Vec<RecordedFrame> = Vec::new();
+
+    loop {
+      let tag = match read_u8(&mut input) {
+        Ok(tag) => tag,
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+        Err(e) => return Err(e),
+      };
+
+      match Self::read_record(&mut input, tag) {
+        Ok(Record::Frame(time)) => frames.push(RecordedFrame {
+          time,
+          events: Vec::new(),
+        }),
+        Ok(Record::Event(conn, event)) => match frames.last_mut() {
+          Some(frame) => frame.events.push((conn, event)),
+          None => return Err(invalid_data("event recorded before the first frame")),
+        },
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+        Err(e) => return Err(e),
+      }
+    }
+
+    Ok(Self { seed, frames })
+  }
+
+  fn read_record(input: &mut impl Read, tag: u8) -> io::Result<Record> {
+    if tag == TAG_FRAME {
+      return Ok(Record::Frame(Duration::from_nanos(read_u64(input)?)));
+    }
+
+    let conn = ConnectionId(read_u64(input)? as usize);
+    let event = match tag {
+      TAG_OPENED => {
+        let len = read_u8(input)? as usize;
+        let addr = String::from_utf8(read_bytes(input, len)?)
+          .map_err(|_| invalid_data("invalid socket address"))?;
+        let addr = addr
+          .parse()
+          .map_err(|_| invalid_data("invalid socket address"))?;
+        RecordedEvent::Opened(addr)
+      }
+      TAG_DATA => {
+        let time = Duration::from_nanos(read_u64(input)?);
+        let len = u32::from_le_bytes(read_array(input)?) as usize;
+        RecordedEvent::Data(time, read_bytes(input, len)?)
+      }
+      TAG_CLOSED => RecordedEvent::Closed,
+      TAG_SERVER_CLOSED => RecordedEvent::ServerClosed {
+        after_input: read_u8(input)? != 0,
+      },
+      tag => return Err(invalid_data(&format!("unknown record type {}", tag))),
+    };
+
+    Ok(Record::Event(conn, event))
+  }
+
+  /// The seed of the [`GameRng`] used in the recorded session.
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+
+  /// The recorded frames, in the order they were run.
+  pub fn frames(&self) -> &[RecordedFrame] {
+    &self.frames
+  }
+
+  /// Play back the recording.
+  ///
+  /// The game should be set up in the same way as the recorded server (same
+  /// config, game mode, and so on) but must not have been started yet. This
+  /// replaces the connection manager of the game, keeping its queue and packet
+  /// limits, dispatches [`ServerStartup`], and then runs every recorded frame
+  /// as fast as possible. Packets sent by the server are discarded.
+  ///
+  /// Connections that the server closed after the inbound events for a frame
+  /// had been processed are closed when the send queues are checked in that
+  /// frame.
+  pub fn run(&self, game: &mut AirmashGame) {
+    self.run_with(game, |_| ());
+  }
+
+  /// Play back the recording, calling `after_frame` after each frame has been
+  /// run. This is useful for inspecting the game state while replaying.
+  pub fn run_with<F>(&self, game: &mut AirmashGame, mut after_frame: F)
+  where
+    F: FnMut(&mut AirmashGame),
+  {
+    let (mut connmgr, sender) = ConnectionMgr::replaying();
+    if let Some(old) = game.resources.get::<ConnectionMgr>() {
+      connmgr.set_queue_limits(old.queue_limits());
+      connmgr.set_packet_limits(old.packet_limits());
+    }
+    game.resources.insert(connmgr);
+    game.resources.insert(GameRng::new(self.seed));
+    game.dispatch(ServerStartup);
+
+    let start = game.start_time();
+    for frame in &self.frames {
+      let mut late = Vec::new();
+      for (conn, event) in &frame.events {
+        match event {
+          RecordedEvent::ServerClosed { after_input: true } => late.push(*conn),
+          event => {
+            let _ = sender.send((*conn, to_internal(event, start)));
+          }
+        }
+      }
+      game
+        .resources
+        .write::<ConnectionMgr>()
+        .close_after_input(late);
+
+      game.run_once(start + frame.time);
+      after_frame(game);
+    }
+  }
+}
+
+enum Record {
+  Frame(Duration),
+  Event(ConnectionId, RecordedEvent),
+}
+
+fn to_internal(event: &RecordedEvent, start: Instant) -> InternalEvent {
+  match event {
+    RecordedEvent::Opened(addr) => {
+      // Nothing reads the other end of the channel so everything sent to this
+      // connection is discarded.
+      let (send, _) = tokio::sync::mpsc::channel(1);
+      InternalEvent::Opened(ConnectionData::new(send, *addr))
+    }
+    RecordedEvent::Data(time, data) => InternalEvent::Data {
+      data: data.clone(),
+      time: start + *time,
+    },
+    RecordedEvent::Closed => InternalEvent::Closed,
+    RecordedEvent::ServerClosed { .. } => InternalEvent::ServerClosed,
+  }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+  io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn read_array<const N: usize>(input: &mut impl Read) -> io::Result<[u8; N]> {
+  let mut buf = [0u8; N];
+  input.read_exact(&mut buf)?;
+  Ok(buf)
+}
+
+fn read_u8(input: &mut impl Read) -> io::Result<u8> {
+  Ok(read_array::<1>(input)?[0])
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+  Ok(u64::from_le_bytes(read_array(input)?))
+}
+
+fn read_bytes(input: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+  let mut buf = Vec::new();
+  input.take(len as u64).read_to_end(&mut buf)?;
+  match buf.len() == len {
+    true => Ok(buf),
+    false => Err(ErrorKind::UnexpectedEof.into()),
+  }
+}
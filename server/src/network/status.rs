@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// How long the game thread can go without publishing a new status before the
+/// `/health` endpoint starts reporting the server as unhealthy.
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+const JSON_CONTENT_TYPE: &str = "application/json; charset=utf-8";
+/// Content type of the Prometheus text exposition format.
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Summary of the server state served on the `/status` endpoint.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ServerStatus {
+  pub region: String,
+  pub game_type: String,
+  /// Server uptime in seconds.
+  pub uptime: u64,
+  /// The number of players currently in the game.
+  pub players: u32,
+  /// The number of players on each team.
+  pub teams: BTreeMap<u16, u32>,
+  /// Scores for game modes that have team scores (e.g. CTF).
+  #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+  pub scores: BTreeMap<String, u32>,
+}
+
+/// Information about a single player served on the `/players` endpoint.
+#[derive(Clone, Debug, Serialize)]
+pub struct PlayerStatus {
+  pub id: u16,
+  pub name: String,
+  pub team: u16,
+  pub score: u32,
+  /// Ping in milliseconds.
+  pub ping: u64,
+}
+
+#[derive(Default)]
+struct StatusData {
+  status: ServerStatus,
+  players: Vec<PlayerStatus>,
+  metrics: String,
+  published: Option<Instant>,
+}
+
+/// Status information published by the game thread for use by the HTTP
+/// endpoints on the networking thread.
+#[derive(Clone, Default)]
+pub(crate) struct SharedStatus {
+  data: Arc<RwLock<StatusData>>,
+}
+
+impl SharedStatus {
+  pub(crate) fn publish(&self, status: ServerStatus, players: Vec<PlayerStatus>, metrics: String) {
+    let mut data = self.data.write().unwrap();
+    data.status = status;
+    data.players = players;
+    data.metrics = metrics;
+    data.published = Some(Instant::now());
+  }
+
+  pub(crate) fn last_published(&self) -> Option<Instant> {
+    self.data.read().unwrap().published
+  }
+
+  /// Generate the HTTP response for a GET request to `path`.
+  pub(crate) fn respond(&self, path: &str) -> (u16, Vec<u8>) {
+    let path = path.split('?').next().unwrap_or(path);
+    let data = self.data.read().unwrap();
+
+    if path == "/metrics" {
+      return (200, response(200, METRICS_CONTENT_TYPE, &data.metrics));
+    }
+
+    let (code, body) = match path {
+      "/" | "/status" => (200, serde_json::to_string(&data.status)),
+      "/players" => (200, serde_json::to_string(&data.players)),
+      "/health" => {
+        let healthy = data
+          .published
+          .map(|published| published.elapsed() < HEALTH_TIMEOUT)
+          .unwrap_or(false);
+
+        if healthy {
+          (200, Ok("{\"status\":\"ok\"}".to_owned()))
+        } else {
+          (503, Ok("{\"status\":\"unavailable\"}".to_owned()))
+        }
+      }
+      _ => (404, Ok("{\"error\":\"not found\"}".to_owned())),
+    };
+    let body = body.unwrap_or_else(|_| "{}".to_owned()) + "\n";
+
+    (code, response(code, JSON_CONTENT_TYPE, &body))
+  }
+}
+
+fn response(code: u16, content_type: &str, body: &str) -> Vec<u8> {
+  format!(
+    "HTTP/1.0 {} {}\r\n\
+    Content-Type: {}\r\n\
+    Content-Length: {}\r\n\
+    \r\n\
+    {}",
+    code,
+    reason(code),
+    content_type,
+    body.len(),
+    body
+  )
+  .into_bytes()
+}
+
+fn reason(code: u16) -> &'static str {
+  match code {
+    200 => "OK",
+    404 => "Not Found",
+    503 => "Service Unavailable",
+    _ => "",
+  }
+}
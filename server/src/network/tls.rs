@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+/// TLS settings for serving `wss://` connections directly.
+///
+/// The certificate chain and private key are loaded from PEM files. Calling
+/// [`reload`] will load them again from the same files so that certificates
+/// can be rotated without restarting the server. Connections that have already
+/// been established are unaffected by a reload.
+///
+/// Cloning this gives another handle to the same underlying configuration.
+///
+/// [`reload`]: crate::network::TlsConfig::reload
+#[derive(Clone)]
+pub struct TlsConfig {
+  cert_path: PathBuf,
+  key_path: PathBuf,
+  acceptor: Arc<RwLock<TlsAcceptor>>,
+}
+
+impl TlsConfig {
+  /// Load a certificate chain and private key from PEM files.
+  pub fn load(cert_path: impl AsRef<Path>, key_path: impl AsRef<Path>) -> io::Result<Self> {
+    let cert_path = cert_path.as_ref().to_owned();
+    let key_path = key_path.as_ref().to_owned();
+    let acceptor = load_acceptor(&cert_path, &key_path)?;
+
+    Ok(Self {
+      cert_path,
+      key_path,
+      acceptor: Arc::new(RwLock::new(acceptor)),
+    })
+  }
+
+  /// Reload the certificate chain and private key from their files. If there
+  /// is an error then the current certificate will continue to be used.
+  pub fn reload(&self) -> io::Result<()> {
+    let acceptor = load_acceptor(&self.cert_path, &self.key_path)?;
+    *self.acceptor.write().unwrap() = acceptor;
+    Ok(())
+  }
+
+  pub(crate) fn acceptor(&self) -> TlsAcceptor {
+    self.acceptor.read().unwrap().clone()
+  }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn load_acceptor(cert_path: &Path, key_path: &Path) -> io::Result<TlsAcceptor> {
+  use rustls_pemfile::Item;
+
+  let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))?;
+  if certs.is_empty() {
+    return Err(invalid_data(format!(
+      "{} contains no certificates",
+      cert_path.display()
+    )));
+  }
+
+  let key = rustls_pemfile::read_all(&mut BufReader::new(File::open(key_path)?))?
+    .into_iter()
+    .find_map(|item| match item {
+      Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => Some(key),
+      _ => None,
+    })
+    .ok_or_else(|| invalid_data(format!("{} contains no private key", key_path.display())))?;
+
+  let config = ServerConfig::builder()
+    .with_safe_defaults()
+    .with_no_client_auth()
+    .with_single_cert(
+      certs.into_iter().map(Certificate).collect(),
+      PrivateKey(key),
+    )
+    .map_err(|e| invalid_data(e.to_string()))?;
+
+  Ok(TlsAcceptor::from(Arc::new(config)))
+}
@@ -0,0 +1,264 @@
+//! Reloading the game config while the server is running.
+//!
+//! The resolved [`Config`] hands out `&'static` references to its prototypes
+//! which get copied into components such as [`PlanePrototypeRef`] and
+//! [`MissilePrototypeRef`]. Swapping out the config means pointing all of
+//! those components at the prototype with the same name in the new config.
+//!
+//! The old config is kept around in [`RetiredConfigs`] until the end of the
+//! frame. Once no prototype component refers to it any more it is freed.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::component::{EnergyRegen, HealthRegen};
+use crate::config::{GamePrototype, MissilePrototypeRef, MobPrototypeRef, PlanePrototypeRef};
+use crate::event::{ConfigReload, FrameEnd, FrameStart};
+use crate::resource::{Config, ConfigPath};
+use crate::AirmashGame;
+
+/// Set when a reload signal is received. See
+/// [`AirmashGame::reload_config_on_signal`].
+static RELOAD_SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle_reload_signal(_: libc::c_int) {
+  RELOAD_SIGNALLED.store(true, Ordering::Relaxed);
+}
+
+/// Configs that have been replaced by [`AirmashGame::set_config`] but which
+/// are still referred to by some entity.
+#[derive(Default)]
+pub struct RetiredConfigs(Vec<Config>);
+
+impl RetiredConfigs {
+  /// The number of replaced configs that have not been freed yet.
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+/// Run a Lua config script on top of the default config and validate the
+/// result.
+fn load_script(path: &Path) -> Result<Config, String> {
+  let script = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+  let mut proto = GamePrototype::default();
+  proto
+    .patch(&script)
+    .map_err(|e| format!("error while running config file: {}", e))?;
+
+  Config::new(proto).map_err(|e| format!("invalid config: {}", e))
+}
+
+/// Check that every prototype that is in use by an entity also exists within
+/// `config`.
+fn validate_in_use(game: &AirmashGame, config: &Config) -> Result<(), String> {
+  for (_, plane) in game.world.query::<&PlanePrototypeRef>().iter() {
+    if !config.planes.contains_key(&*plane.name) {
+      return Err(format!("plane `{}` is in use but was removed", plane.name));
+    }
+  }
+
+  for (_, missile) in game.world.query::<&MissilePrototypeRef>().iter() {
+    if !config.missiles.contains_key(&*missile.name) {
+      return Err(format!(
+        "missile `{}` is in use but was removed",
+        missile.name
+      ));
+    }
+  }
+
+  for (_, mob) in game.world.query::<&MobPrototypeRef>().iter() {
+    if !config.mobs.contains_key(&*mob.name) {
+      return Err(format!("mob `{}` is in use but was removed", mob.name));
+    }
+  }
+
+  Ok(())
+}
+
+/// Whether any prototype reference held by an entity points into `config`.
+fn is_referenced(game: &AirmashGame, config: &Config) -> bool {
+  let mut planes = game.world.query::<&PlanePrototypeRef>();
+  let mut missiles = game.world.query::<&MissilePrototypeRef>();
+  let mut mobs = game.world.query::<&MobPrototypeRef>();
+
+  planes.iter().any(|(_, &plane)| {
+    config
+      .planes
+      .get(&*plane.name)
+      .is_some_and(|&proto| std::ptr::eq(proto, plane))
+  }) || missiles.iter().any(|(_, &missile)| {
+    config
+      .missiles
+      .get(&*missile.name)
+      .is_some_and(|&proto| std::ptr::eq(proto, missile))
+  }) || mobs.iter().any(|(_, &mob)| {
+    config
+      .mobs
+      .get(&*mob.name)
+      .is_some_and(|&proto| std::ptr::eq(proto, mob))
+  })
+}
+
+/// Point all prototype references held by entities at the prototype with the
+/// same name within `config`.
+fn migrate_entities(game: &mut AirmashGame, config: &Config) {
+  let query = game.world.query_mut::<(
+    &mut PlanePrototypeRef,
+    Option<&mut EnergyRegen>,
+    Option<&mut HealthRegen>,
+  )>();
+  for (_, (plane, energy_regen, health_regen)) in query {
+    let new = config.planes[&*plane.name];
+
+    // Regen rates are temporarily overridden by some specials (e.g. predator
+    // boost). Those will be reset by the special itself so only update the
+    // ones that are still at the value given by the plane.
+    if let Some(regen) = energy_regen {
+      if regen.0 == plane.energy_regen {
+        regen.0 = new.energy_regen;
+      }
+    }
+    if let Some(regen) = health_regen {
+      if regen.0 == plane.health_regen {
+        regen.0 = new.health_regen;
+      }
+    }
+
+    *plane = new;
+  }
+
+  for (_, missile) in game.world.query_mut::<&mut MissilePrototypeRef>() {
+    *missile = config.missiles[&*missile.name];
+  }
+
+  for (_, mob) in game.world.query_mut::<&mut MobPrototypeRef>() {
+    *mob = config.mobs[&*mob.name];
+  }
+}
+
+impl AirmashGame {
+  /// Load the game config from a Lua script, applied on top of the default
+  /// config.
+  ///
+  /// The path is remembered so that the config can later be reloaded with
+  /// [`reload_config`](AirmashGame::reload_config). If there is already a
+  /// config then it is replaced as if by [`set_config`].
+  ///
+  /// [`set_config`]: AirmashGame::set_config
+  pub fn load_config(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+    let path = path.as_ref();
+    let config = load_script(path)?;
+
+    self.set_config(config)?;
+    self.resources.insert(ConfigPath(Some(path.to_owned())));
+    Ok(())
+  }
+
+  /// Re-run the config script that was loaded by
+  /// [`load_config`](AirmashGame::load_config) and swap it in.
+  ///
+  /// Returns `false` if the config was not loaded from a file. If the script
+  /// fails or produces an invalid config then the current config is left
+  /// untouched.
+  pub fn reload_config(&mut self) -> Result<bool, String> {
+    let path = match self.resources.get::<ConfigPath>() {
+      Some(path) => match &path.0 {
+        Some(path) => path.clone(),
+        None => return Ok(false),
+      },
+      None => return Ok(false),
+    };
+
+    let config = load_script(&path)?;
+    self.set_config(config)?;
+    Ok(true)
+  }
+
+  /// Replace the current config.
+  ///
+  /// All entities are migrated to the prototypes with the same name in the
+  /// new config. This fails, leaving the current config in place, if any
+  /// prototype that is in use by an entity does not exist in the new config.
+  ///
+  /// A [`ConfigReload`] event is dispatched if a config was replaced.
+  ///
+  /// # Memory
+  /// The replaced config is freed at the end of a frame once no
+  /// [`PlanePrototypeRef`], [`MissilePrototypeRef`] or [`MobPrototypeRef`]
+  /// component refers to it. Prototype references kept anywhere else (in
+  /// resources, tasks, and so on) must not outlive the frame in which a
+  /// [`ConfigReload`] event is dispatched.
+  pub fn set_config(&mut self, config: Config) -> Result<(), String> {
+    if let Err(e) = validate_in_use(self, &config) {
+      // SAFETY: None of the prototypes in the rejected config have been handed
+      //         out so nothing can refer to them.
+      unsafe { config.reclaim() };
+      return Err(e);
+    }
+    migrate_entities(self, &config);
+
+    // Whoever called this may still be holding on to references to the old
+    // prototypes so it is only freed once the frame is over.
+    if let Some(old) = self.resources.insert(config) {
+      self.resources.write::<RetiredConfigs>().0.push(old);
+      self.dispatch(ConfigReload);
+    }
+
+    Ok(())
+  }
+
+  /// Reload the config whenever the process receives SIGHUP.
+  ///
+  /// This does nothing on platforms other than unix.
+  pub fn reload_config_on_signal(&self) {
+    #[cfg(unix)]
+    unsafe {
+      let handler = handle_reload_signal as extern "C" fn(libc::c_int);
+      libc::signal(libc::SIGHUP, handler as libc::sighandler_t);
+    }
+  }
+}
+
+#[handler]
+fn reload_on_signal(_: &FrameStart, game: &mut AirmashGame) {
+  if !RELOAD_SIGNALLED.swap(false, Ordering::Relaxed) {
+    return;
+  }
+
+  match game.reload_config() {
+    Ok(true) => info!("Reloaded the config"),
+    Ok(false) => warn!("Received a reload signal but the config was not loaded from a file"),
+    Err(e) => warn!("Unable to reload the config: {}", e),
+  }
+}
+
+#[handler]
+fn reclaim_retired_configs(_: &FrameEnd, game: &mut AirmashGame) {
+  let retired = std::mem::take(&mut game.resources.write::<RetiredConfigs>().0);
+  if retired.is_empty() {
+    return;
+  }
+
+  let mut kept = Vec::new();
+  for config in retired {
+    if is_referenced(game, &config) {
+      kept.push(config);
+      continue;
+    }
+
+    // SAFETY: Migrating entities moved every prototype component over to the
+    //         new config, anything that was still pointing at this one has
+    //         since gone away, and other references only last for the frame
+    //         in which the config was replaced.
+    unsafe { config.reclaim() };
+  }
+
+  game.resources.write::<RetiredConfigs>().0.extend(kept);
+}
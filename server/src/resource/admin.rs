@@ -0,0 +1,76 @@
+use std::io;
+use std::path::Path;
+
+use crate::component::AdminRole;
+
+/// Secret tokens that players can use to authenticate for admin commands.
+///
+/// By default there are no tokens so nobody is able to authenticate. Tokens
+/// are usually loaded from a file at startup via [`AdminTokens::load`]. The
+/// file has one token per line in the form `<role> <token>`. Empty lines and
+/// lines starting with `#` are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct AdminTokens {
+  tokens: Vec<(Vec<u8>, AdminRole)>,
+}
+
+impl AdminTokens {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Add a new token which grants `role` when used to authenticate.
+  pub fn insert(&mut self, token: impl Into<Vec<u8>>, role: AdminRole) {
+    self.tokens.push((token.into(), role));
+  }
+
+  /// Parse a set of tokens from the contents of a token file.
+  pub fn parse(contents: &str) -> Result<Self, String> {
+    let mut tokens = Self::new();
+
+    for (lineno, line) in contents.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let (role, token) = match line.split_once(char::is_whitespace) {
+        Some((role, token)) => (role, token.trim()),
+        None => return Err(format!("line {}: expected `<role> <token>`", lineno + 1)),
+      };
+
+      let role = role
+        .parse()
+        .map_err(|_| format!("line {}: unknown role `{}`", lineno + 1, role))?;
+      tokens.insert(token, role);
+    }
+
+    Ok(tokens)
+  }
+
+  /// Load a set of tokens from a token file.
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    let contents = std::fs::read_to_string(path)?;
+    Self::parse(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+  }
+
+  /// Get the role granted by `token`, if it is a valid token.
+  pub fn authenticate(&self, token: &[u8]) -> Option<AdminRole> {
+    self
+      .tokens
+      .iter()
+      .filter(|(candidate, _)| constant_time_eq(candidate, token))
+      .map(|&(_, role)| role)
+      .max()
+  }
+}
+
+/// Compare two byte strings without exiting early so that the comparison
+/// doesn't leak how much of a token was correct.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  if a.len() != b.len() {
+    return false;
+  }
+
+  a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
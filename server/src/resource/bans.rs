@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Something that can be banned from the server.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BanTarget {
+  /// Ban all connections coming from an IP address.
+  Ip(IpAddr),
+  /// Ban a session token. Logins presenting this token will be rejected.
+  Session(String),
+}
+
+/// The list of active bans.
+///
+/// This is shared with the networking thread so that banned IPs can be
+/// rejected before the websocket handshake completes. Cloning it gives another
+/// handle to the same underlying ban list.
+///
+/// If a file has been set via [`load`] then the list will be written back to
+/// that file every time it is modified. Each line of the file has the form
+/// `<ip|session> <value> <expiry>` where the expiry is either `never` or a unix
+/// timestamp in seconds.
+///
+/// [`load`]: crate::resource::BanList::load
+#[derive(Clone, Debug, Default)]
+pub struct BanList {
+  inner: Arc<RwLock<BanListInner>>,
+}
+
+#[derive(Debug, Default)]
+struct BanListInner {
+  bans: HashMap<BanTarget, Option<SystemTime>>,
+  path: Option<PathBuf>,
+}
+
+impl BanList {
+  /// Load bans from `path` and persist all future changes to that file. If the
+  /// file doesn't exist then it will be created on the next change.
+  pub fn load(&self, path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    let contents = match std::fs::read_to_string(path) {
+      Ok(contents) => contents,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+      Err(e) => return Err(e),
+    };
+
+    let mut bans = HashMap::new();
+    for (lineno, line) in contents.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let (target, expiry) = parse_line(line).ok_or_else(|| {
+        io::Error::new(
+          io::ErrorKind::InvalidData,
+          format!("{}:{}: invalid ban entry", path.display(), lineno + 1),
+        )
+      })?;
+      bans.insert(target, expiry);
+    }
+
+    let mut inner = self.inner.write().unwrap();
+    inner.bans.extend(bans);
+    inner.path = Some(path.to_owned());
+
+    Ok(())
+  }
+
+  /// Ban `target` until `expiry`, or forever if there is no expiry.
+  pub fn ban(&self, target: BanTarget, expiry: Option<SystemTime>) {
+    let mut inner = self.inner.write().unwrap();
+    inner.bans.insert(target, expiry);
+    inner.save();
+  }
+
+  /// Remove a ban. Returns whether `target` was banned.
+  pub fn unban(&self, target: &BanTarget) -> bool {
+    let mut inner = self.inner.write().unwrap();
+    let removed = inner.bans.remove(target).is_some();
+    if removed {
+      inner.save();
+    }
+    removed
+  }
+
+  /// Check whether `target` is currently banned.
+  pub fn is_banned(&self, target: &BanTarget) -> bool {
+    let inner = self.inner.read().unwrap();
+    match inner.bans.get(target) {
+      Some(Some(expiry)) => *expiry > SystemTime::now(),
+      Some(None) => true,
+      None => false,
+    }
+  }
+}
+
+impl BanListInner {
+  fn save(&mut self) {
+    let now = SystemTime::now();
+    self
+      .bans
+      .retain(|_, expiry| expiry.map(|e| e > now).unwrap_or(true));
+
+    let path = match &self.path {
+      Some(path) => path,
+      None => return,
+    };
+
+    let mut contents = String::new();
+    for (target, expiry) in &self.bans {
+      let (kind, value) = match target {
+        BanTarget::Ip(ip) => ("ip", ip.to_string()),
+        BanTarget::Session(session) => ("session", session.clone()),
+      };
+      let expiry = match expiry {
+        Some(expiry) => expiry
+          .duration_since(UNIX_EPOCH)
+          .unwrap_or_default()
+          .as_secs()
+          .to_string(),
+        None => "never".to_owned(),
+      };
+
+      let _ = writeln!(contents, "{} {} {}", kind, value, expiry);
+    }
+
+    if let Err(e) = std::fs::write(path, contents) {
+      error!("Unable to save ban list to {}: {}", path.display(), e);
+    }
+  }
+}
+
+fn parse_line(line: &str) -> Option<(BanTarget, Option<SystemTime>)> {
+  let mut parts = line.split_whitespace();
+  let kind = parts.next()?;
+  let value = parts.next()?;
+  let expiry = parts.next()?;
+
+  if parts.next().is_some() {
+    return None;
+  }
+
+  let target = match kind {
+    "ip" => BanTarget::Ip(value.parse().ok()?),
+    "session" => BanTarget::Session(value.to_owned()),
+    _ => return None,
+  };
+  let expiry = match expiry {
+    "never" => None,
+    secs => Some(UNIX_EPOCH + Duration::from_secs(secs.parse().ok()?)),
+  };
+
+  Some((target, expiry))
+}
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+/// Limits on how quickly players are allowed to send chat messages.
+///
+/// Each player has a bucket of `burst` messages which refills at a rate of
+/// `rate` messages per second. Sending a message when the bucket is empty, or
+/// sending the same message more than `max_repeats` times in a row within
+/// `repeat_window`, counts as a violation and the message is dropped.
+///
+/// Once a player has accumulated `violations_before_mute` violations they will
+/// be automatically muted. The duration of the mute is taken from
+/// `mute_durations`, with each subsequent mute using the next entry. Once the
+/// end of the list is reached the last duration is used for all further mutes.
+#[derive(Clone, Debug)]
+pub struct ChatLimits {
+  /// The maximum number of messages that can be sent in quick succession.
+  pub burst: u32,
+
+  /// The sustained number of messages per second that a player can send.
+  pub rate: f32,
+
+  /// The number of times a message can be repeated before further repetitions
+  /// are dropped.
+  pub max_repeats: u32,
+
+  /// How long to remember the last message for the purposes of detecting
+  /// repeated messages.
+  pub repeat_window: Duration,
+
+  /// The number of violations that result in the player being muted.
+  pub violations_before_mute: u32,
+
+  /// How long it takes for previous violations to be forgotten.
+  pub violation_timeout: Duration,
+
+  /// How long players are muted for each time they get muted.
+  pub mute_durations: Vec<Duration>,
+}
+
+impl Default for ChatLimits {
+  fn default() -> Self {
+    Self {
+      burst: 4,
+      rate: 0.5,
+      max_repeats: 2,
+      repeat_window: Duration::from_secs(30),
+      violations_before_mute: 3,
+      violation_timeout: Duration::from_secs(30),
+      mute_durations: vec![
+        Duration::from_secs(30),
+        Duration::from_secs(2 * 60),
+        Duration::from_secs(10 * 60),
+        Duration::from_secs(60 * 60),
+      ],
+    }
+  }
+}
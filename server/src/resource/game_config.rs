@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 /// Flags to enable and/or disable engine features.
 ///
 /// By default these configs are set as would be needed for an FFA gamemode.
@@ -39,12 +41,19 @@ pub struct GameConfig {
   /// This is set to false by default.
   pub always_upgraded: bool,
 
-  /// Whether admin commands are enabled.
+  /// How long players restored from a snapshot have to reconnect before their
+  /// ID and name are released.
   ///
-  /// This is set to false by default.
+  /// This is set to 2 minutes by default.
+  pub reconnect_window: Duration,
+
+  /// How long a player stays in the game after their connection drops. If a
+  /// new connection sends a `Backup` packet with the player's session token
+  /// within this time then it takes over the player. Setting this to zero
+  /// makes players leave as soon as their connection drops.
   ///
-  /// TODO: This should be replaced with authenticating for admin commands.
-  pub admin_enabled: bool,
+  /// This is set to 10 seconds by default.
+  pub resume_window: Duration,
 }
 
 impl Default for GameConfig {
@@ -55,7 +64,8 @@ impl Default for GameConfig {
       allow_damage: true,
       spawn_upgrades: true,
       always_upgraded: false,
-      admin_enabled: false,
+      reconnect_window: Duration::from_secs(120),
+      resume_window: Duration::from_secs(10),
     }
   }
 }
@@ -0,0 +1,133 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// The window over which [`Limits::logins_per_ip`] is counted.
+const LOGIN_WINDOW: Duration = Duration::from_secs(60);
+
+/// Caps on how many connections and players the server accepts. `None` means
+/// that there is no cap.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+  /// The maximum number of websocket connections that can be open from a
+  /// single IP address at once. Further connections are rejected with
+  /// `429 Too Many Requests` during the handshake.
+  pub connections_per_ip: Option<u32>,
+
+  /// The maximum number of logins that a single IP address can make within a
+  /// minute.
+  pub logins_per_ip: Option<u32>,
+
+  /// The maximum number of players that can be in the game at once. Players
+  /// that have disconnected but can still resume count towards this.
+  pub max_players: Option<u32>,
+}
+
+/// The connection and login limits along with the state needed to enforce
+/// them.
+///
+/// This is shared with the networking thread so that connections can be
+/// rejected before the websocket handshake completes. Cloning it gives another
+/// handle to the same underlying limits.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionLimits {
+  inner: Arc<RwLock<ConnectionLimitsInner>>,
+}
+
+#[derive(Debug, Default)]
+struct ConnectionLimitsInner {
+  limits: Limits,
+  open: HashMap<IpAddr, u32>,
+  logins: HashMap<IpAddr, VecDeque<Instant>>,
+}
+
+impl ConnectionLimits {
+  pub fn limits(&self) -> Limits {
+    self.inner.read().unwrap().limits
+  }
+
+  /// Replace the current limits. Connections that are already open are not
+  /// affected.
+  pub fn set(&self, limits: Limits) {
+    self.inner.write().unwrap().limits = limits;
+  }
+
+  /// The number of websocket connections currently open from `ip`.
+  pub fn connections(&self, ip: IpAddr) -> u32 {
+    self
+      .inner
+      .read()
+      .unwrap()
+      .open
+      .get(&ip)
+      .copied()
+      .unwrap_or(0)
+  }
+
+  /// Register a new connection from `ip`. Returns `None` if there are already
+  /// too many connections from that address.
+  ///
+  /// The connection counts as open until the returned guard is dropped.
+  pub(crate) fn open(&self, ip: IpAddr) -> Option<OpenConnection> {
+    let mut inner = self.inner.write().unwrap();
+    let max = inner.limits.connections_per_ip;
+    let count = inner.open.get(&ip).copied().unwrap_or(0);
+
+    if max.map(|max| count >= max).unwrap_or(false) {
+      return None;
+    }
+
+    inner.open.insert(ip, count + 1);
+    Some(OpenConnection {
+      limits: self.clone(),
+      ip,
+    })
+  }
+
+  /// Record a login attempt from `ip` at `now`. Returns `false` if `ip` has
+  /// already made too many logins within the last minute.
+  pub(crate) fn login(&self, ip: IpAddr, now: Instant) -> bool {
+    let mut inner = self.inner.write().unwrap();
+    let max = inner.limits.logins_per_ip;
+
+    inner.logins.retain(|_, times| {
+      while let Some(&time) = times.front() {
+        if now.saturating_duration_since(time) < LOGIN_WINDOW {
+          break;
+        }
+        times.pop_front();
+      }
+
+      !times.is_empty()
+    });
+
+    let times = inner.logins.entry(ip).or_default();
+    if max.map(|max| times.len() >= max as usize).unwrap_or(false) {
+      return false;
+    }
+
+    times.push_back(now);
+    true
+  }
+}
+
+/// A websocket connection that counts towards
+/// [`Limits::connections_per_ip`].
+pub(crate) struct OpenConnection {
+  limits: ConnectionLimits,
+  ip: IpAddr,
+}
+
+impl Drop for OpenConnection {
+  fn drop(&mut self) {
+    let mut inner = self.limits.inner.write().unwrap();
+
+    if let Some(count) = inner.open.get_mut(&self.ip) {
+      *count -= 1;
+      if *count == 0 {
+        inner.open.remove(&self.ip);
+      }
+    }
+  }
+}
@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::protocol::{ClientPacket, ServerPacket};
+
+/// Upper bounds (in seconds) of the buckets used for timing histograms.
+const BUCKETS: [f64; 10] = [
+  0.0005, 0.001, 0.002, 0.004, 0.008, 0.016, 0.032, 0.064, 0.128, 0.256,
+];
+
+/// A histogram of durations with fixed buckets.
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+  counts: [u64; BUCKETS.len()],
+  count: u64,
+  sum: f64,
+}
+
+impl Histogram {
+  pub fn observe(&mut self, duration: Duration) {
+    let secs = duration.as_secs_f64();
+    for (count, &bound) in self.counts.iter_mut().zip(BUCKETS.iter()) {
+      if secs <= bound {
+        *count += 1;
+      }
+    }
+
+    self.count += 1;
+    self.sum += secs;
+  }
+
+  /// The total number of observations.
+  pub fn count(&self) -> u64 {
+    self.count
+  }
+
+  /// The sum of all observations, in seconds.
+  pub fn sum(&self) -> f64 {
+    self.sum
+  }
+
+  fn render(&self, out: &mut String, name: &str, labels: &str) {
+    let sep = if labels.is_empty() { "" } else { "," };
+
+    for (count, bound) in self.counts.iter().zip(BUCKETS.iter()) {
+      let _ = writeln!(
+        out,
+        "{}_bucket{{{}{}le=\"{}\"}} {}",
+        name, labels, sep, bound, count
+      );
+    }
+    let _ = writeln!(
+      out,
+      "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+      name, labels, sep, self.count
+    );
+
+    let labels = if labels.is_empty() {
+      String::new()
+    } else {
+      format!("{{{}}}", labels)
+    };
+    let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+    let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+  }
+}
+
+/// Counters and histograms describing the performance of the server.
+///
+/// These are exported in the Prometheus text format on the `/metrics` HTTP
+/// endpoint of the game port.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+  /// Frames that take longer than this are counted as overruns.
+  pub frame_budget: Duration,
+
+  /// Time taken by each frame as a whole.
+  pub frame_duration: Histogram,
+  /// Time taken by each stage within [`system::update`].
+  ///
+  /// [`system::update`]: crate::system::update
+  pub stage_duration: BTreeMap<&'static str, Histogram>,
+  /// The number of frames that took longer than `frame_budget`.
+  pub frame_overruns: u64,
+
+  /// Packets received from clients, by packet type.
+  pub packets_in: BTreeMap<&'static str, u64>,
+  /// Packets sent to clients, by packet type.
+  pub packets_out: BTreeMap<&'static str, u64>,
+  /// Bytes sent to each currently open connection.
+  pub bytes_sent: BTreeMap<usize, u64>,
+  /// The number of times each event type has been dispatched.
+  pub events: BTreeMap<&'static str, u64>,
+
+  /// The total number of players killed.
+  pub kills: u64,
+  /// The number of missiles currently in the game.
+  pub missiles: u64,
+  /// The number of players currently in the game.
+  pub players: u64,
+}
+
+impl Default for Metrics {
+  fn default() -> Self {
+    Self {
+      frame_budget: Duration::from_millis(16),
+      frame_duration: Histogram::default(),
+      stage_duration: BTreeMap::new(),
+      frame_overruns: 0,
+      packets_in: BTreeMap::new(),
+      packets_out: BTreeMap::new(),
+      bytes_sent: BTreeMap::new(),
+      events: BTreeMap::new(),
+      kills: 0,
+      missiles: 0,
+      players: 0,
+    }
+  }
+}
+
+impl Metrics {
+  pub fn record_frame(&mut self, duration: Duration) {
+    self.frame_duration.observe(duration);
+    if duration > self.frame_budget {
+      self.frame_overruns += 1;
+    }
+  }
+
+  pub fn record_stage(&mut self, stage: &'static str, duration: Duration) {
+    self
+      .stage_duration
+      .entry(stage)
+      .or_default()
+      .observe(duration);
+  }
+
+  pub fn record_packet_in(&mut self, packet: &'static str) {
+    *self.packets_in.entry(packet).or_default() += 1;
+  }
+
+  pub fn record_packets_out(&mut self, packet: &'static str, count: u64) {
+    *self.packets_out.entry(packet).or_default() += count;
+  }
+
+  pub fn record_event(&mut self, event: &'static str) {
+    *self.events.entry(event).or_default() += 1;
+  }
+
+  /// Render all metrics in the Prometheus text exposition format.
+  pub fn render(&self) -> String {
+    let mut out = String::new();
+
+    header(
+      &mut out,
+      "airmash_frame_duration_seconds",
+      "histogram",
+      "Time taken to run a single frame.",
+    );
+    self
+      .frame_duration
+      .render(&mut out, "airmash_frame_duration_seconds", "");
+
+    header(
+      &mut out,
+      "airmash_stage_duration_seconds",
+      "histogram",
+      "Time taken by each stage of the update loop.",
+    );
+    for (stage, hist) in &self.stage_duration {
+      let labels = format!("stage=\"{}\"", stage);
+      hist.render(&mut out, "airmash_stage_duration_seconds", &labels);
+    }
+
+    header(
+      &mut out,
+      "airmash_frame_overruns_total",
+      "counter",
+      "Frames that took longer than the frame budget.",
+    );
+    let _ = writeln!(out, "airmash_frame_overruns_total {}", self.frame_overruns);
+
+    header(
+      &mut out,
+      "airmash_packets_received_total",
+      "counter",
+      "Packets received from clients.",
+    );
+    for (packet, count) in &self.packets_in {
+      let _ = writeln!(
+        out,
+        "airmash_packets_received_total{{type=\"{}\"}} {}",
+        packet, count
+      );
+    }
+
+    header(
+      &mut out,
+      "airmash_packets_sent_total",
+      "counter",
+      "Packets sent to clients.",
+    );
+    for (packet, count) in &self.packets_out {
+      let _ = writeln!(
+        out,
+        "airmash_packets_sent_total{{type=\"{}\"}} {}",
+        packet, count
+      );
+    }
+
+    header(
+      &mut out,
+      "airmash_connection_bytes_sent_total",
+      "counter",
+      "Bytes sent on each open connection.",
+    );
+    for (conn, bytes) in &self.bytes_sent {
+      let _ = writeln!(
+        out,
+        "airmash_connection_bytes_sent_total{{connection=\"{}\"}} {}",
+        conn, bytes
+      );
+    }
+
+    header(
+      &mut out,
+      "airmash_events_dispatched_total",
+      "counter",
+      "Events dispatched, by event type.",
+    );
+    for (event, count) in &self.events {
+      let _ = writeln!(
+        out,
+        "airmash_events_dispatched_total{{event=\"{}\"}} {}",
+        event, count
+      );
+    }
+
+    header(
+      &mut out,
+      "airmash_kills_total",
+      "counter",
+      "Players killed.",
+    );
+    let _ = writeln!(out, "airmash_kills_total {}", self.kills);
+
+    header(
+      &mut out,
+      "airmash_missiles",
+      "gauge",
+      "Missiles currently in the game.",
+    );
+    let _ = writeln!(out, "airmash_missiles {}", self.missiles);
+
+    header(
+      &mut out,
+      "airmash_players",
+      "gauge",
+      "Players currently in the game.",
+    );
+    let _ = writeln!(out, "airmash_players {}", self.players);
+
+    out
+  }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+  let _ = writeln!(out, "# HELP {} {}", name, help);
+  let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// The name of a client packet type, for use as a metric label.
+pub(crate) fn client_packet_name(packet: &ClientPacket) -> &'static str {
+  match packet {
+    ClientPacket::Login(_) => "Login",
+    ClientPacket::Backup(_) => "Backup",
+    ClientPacket::Horizon(_) => "Horizon",
+    ClientPacket::Ack => "Ack",
+    ClientPacket::Pong(_) => "Pong",
+    ClientPacket::Key(_) => "Key",
+    ClientPacket::Command(_) => "Command",
+    ClientPacket::ScoreDetailed => "ScoreDetailed",
+    ClientPacket::Chat(_) => "Chat",
+    ClientPacket::TeamChat(_) => "TeamChat",
+    ClientPacket::Whisper(_) => "Whisper",
+    ClientPacket::Say(_) => "Say",
+    ClientPacket::VoteMute(_) => "VoteMute",
+    ClientPacket::LocalPing(_) => "LocalPing",
+    _ => "Unknown",
+  }
+}
+
+/// The name of a server packet type, for use as a metric label.
+pub(crate) fn server_packet_name(packet: &ServerPacket) -> &'static str {
+  match packet {
+    ServerPacket::Login(_) => "Login",
+    ServerPacket::Login2(_) => "Login2",
+    ServerPacket::Backup => "Backup",
+    ServerPacket::Ping(_) => "Ping",
+    ServerPacket::PingResult(_) => "PingResult",
+    ServerPacket::Ack => "Ack",
+    ServerPacket::Error(_) => "Error",
+    ServerPacket::CommandReply(_) => "CommandReply",
+    ServerPacket::PlayerNew(_) => "PlayerNew",
+    ServerPacket::PlayerLeave(_) => "PlayerLeave",
+    ServerPacket::PlayerUpdate(_) => "PlayerUpdate",
+    ServerPacket::PlayerFire(_) => "PlayerFire",
+    ServerPacket::PlayerRespawn(_) => "PlayerRespawn",
+    ServerPacket::PlayerFlag(_) => "PlayerFlag",
+    ServerPacket::PlayerHit(_) => "PlayerHit",
+    ServerPacket::PlayerKill(_) => "PlayerKill",
+    ServerPacket::PlayerUpgrade(_) => "PlayerUpgrade",
+    ServerPacket::PlayerType(_) => "PlayerType",
+    ServerPacket::PlayerPowerup(_) => "PlayerPowerup",
+    ServerPacket::PlayerLevel(_) => "PlayerLevel",
+    ServerPacket::PlayerReteam(_) => "PlayerReteam",
+    ServerPacket::GameFlag(_) => "GameFlag",
+    ServerPacket::GameSpectate(_) => "GameSpectate",
+    ServerPacket::GamePlayersAlive(_) => "GamePlayersAlive",
+    ServerPacket::GameFirewall(_) => "GameFirewall",
+    ServerPacket::EventRepel(_) => "EventRepel",
+    ServerPacket::EventBoost(_) => "EventBoost",
+    ServerPacket::EventBounce(_) => "EventBounce",
+    ServerPacket::EventStealth(_) => "EventStealth",
+    ServerPacket::EventLeaveHorizon(_) => "EventLeaveHorizon",
+    ServerPacket::MobUpdate(_) => "MobUpdate",
+    ServerPacket::MobUpdate2(_) => "MobUpdate2",
+    ServerPacket::MobUpdateStationary(_) => "MobUpdateStationary",
+    ServerPacket::MobDespawn(_) => "MobDespawn",
+    ServerPacket::MobDespawnCoords(_) => "MobDespawnCoords",
+    ServerPacket::ScoreUpdate(_) => "ScoreUpdate",
+    ServerPacket::ScoreBoard(_) => "ScoreBoard",
+    ServerPacket::ScoreDetailedFFA(_) => "ScoreDetailedFFA",
+    ServerPacket::ScoreDetailedCTF(_) => "ScoreDetailedCTF",
+    ServerPacket::ScoreDetailedBTR(_) => "ScoreDetailedBTR",
+    ServerPacket::ChatTeam(_) => "ChatTeam",
+    ServerPacket::ChatPublic(_) => "ChatPublic",
+    ServerPacket::ChatSay(_) => "ChatSay",
+    ServerPacket::ChatWhisper(_) => "ChatWhisper",
+    ServerPacket::ChatVoteMutePassed(_) => "ChatVoteMutePassed",
+    ServerPacket::ChatVoteMuted => "ChatVoteMuted",
+    ServerPacket::ServerMessage(_) => "ServerMessage",
+    ServerPacket::ServerCustom(_) => "ServerCustom",
+    _ => "Unknown",
+  }
+}
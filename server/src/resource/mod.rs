@@ -1,6 +1,7 @@
 //! All resource types used within the server.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
 use std::time::Instant;
 
 use bstr::BString;
@@ -8,12 +9,35 @@ use hecs::Entity;
 
 pub mod collision;
 
+mod admin;
+mod bans;
+mod chat_limits;
 mod game_config;
+mod limits;
+mod metrics;
+mod output_dirs;
+mod profiler;
+mod rng;
 mod stats;
+mod word_filter;
 
+pub use self::admin::AdminTokens;
+pub use self::bans::{BanList, BanTarget};
+pub use self::chat_limits::ChatLimits;
 pub use self::game_config::GameConfig;
+pub(crate) use self::limits::OpenConnection;
+pub use self::limits::{ConnectionLimits, Limits};
+pub(crate) use self::metrics::{client_packet_name, server_packet_name};
+pub use self::metrics::{Histogram, Metrics};
+pub use self::output_dirs::OutputDirs;
+pub use self::profiler::{ProfileStats, Profiler};
+pub use self::rng::GameRng;
 pub use self::stats::ServerStats;
+pub use self::word_filter::{FilterAction, WordFilter};
+pub use crate::command::CommandRegistry;
 pub use crate::protocol::GameType;
+pub use crate::reload::RetiredConfigs;
+pub use crate::snapshot::SnapshotRegistry;
 pub use crate::TaskScheduler;
 
 pub type Config = crate::config::GameConfig;
@@ -48,6 +72,15 @@ def_wrapper_resources! {
   ##[nocopy]
   pub type RegionName = String;
 
+  /// The Lua script that the current [`Config`] was loaded from, if any.
+  ///
+  /// This is set by [`AirmashGame::load_config`] and used when reloading the
+  /// config.
+  ///
+  /// [`AirmashGame::load_config`]: crate::AirmashGame::load_config
+  ##[nocopy]
+  pub type ConfigPath = Option<PathBuf>;
+
   /// Record of the names of players currently within the server.
   ///
   /// This is used to avoid assigning the same name to multiple players when
@@ -58,4 +91,18 @@ def_wrapper_resources! {
   /// Mapping of user-facing ID to existing entities.
   ##[nocopy]
   pub type EntityMapping = HashMap<u16, Entity>;
+
+  /// Votes that have been cast to mute players.
+  ///
+  /// This maps the player being voted against to the set of players that have
+  /// voted to mute them.
+  ##[nocopy]
+  pub type MuteVotes = HashMap<Entity, HashSet<Entity>>;
+
+  /// Scores of each team for game modes that have team scores (e.g. CTF).
+  ///
+  /// These are reported on the `/status` HTTP endpoint. Game modes are
+  /// responsible for keeping this up to date.
+  ##[nocopy]
+  pub type TeamScores = BTreeMap<String, u32>;
 }
@@ -0,0 +1,57 @@
+use std::path::{Path, PathBuf};
+
+/// Directories that admin commands are allowed to write files into.
+///
+/// Admin commands only accept a bare file name which is resolved against the
+/// directory for that kind of file, so players can never pick where on the
+/// server a file is written. Commands that write files are disabled unless
+/// their directory has been set.
+#[derive(Clone, Debug, Default)]
+pub struct OutputDirs {
+  /// Where `profile dump` writes profiling reports.
+  pub profiles: Option<PathBuf>,
+  /// Where `demo start` records demos.
+  pub demos: Option<PathBuf>,
+  /// Where the `snapshot` command saves snapshots.
+  pub snapshots: Option<PathBuf>,
+}
+
+impl OutputDirs {
+  /// Resolve the name of a profiling report against [`profiles`].
+  ///
+  /// [`profiles`]: OutputDirs::profiles
+  pub fn profile(&self, name: &str) -> Result<PathBuf, String> {
+    resolve(self.profiles.as_deref(), name, "profile-dir")
+  }
+
+  /// Resolve the name of a demo against [`demos`].
+  ///
+  /// [`demos`]: OutputDirs::demos
+  pub fn demo(&self, name: &str) -> Result<PathBuf, String> {
+    resolve(self.demos.as_deref(), name, "demo-dir")
+  }
+
+  /// Resolve the name of a snapshot against [`snapshots`].
+  ///
+  /// [`snapshots`]: OutputDirs::snapshots
+  pub fn snapshot(&self, name: &str) -> Result<PathBuf, String> {
+    resolve(self.snapshots.as_deref(), name, "snapshot-dir")
+  }
+}
+
+/// Resolve a file name given by a player against `dir`. The name must be made
+/// up of letters, digits, `-`, `_` and `.` and must not start with a `.`.
+fn resolve(dir: Option<&Path>, name: &str, flag: &str) -> Result<PathBuf, String> {
+  let dir = dir.ok_or_else(|| format!("Writing files is disabled, see --{}", flag))?;
+
+  let valid = !name.is_empty()
+    && !name.starts_with('.')
+    && name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+  if !valid {
+    return Err(format!("`{}` is not a valid file name", name));
+  }
+
+  Ok(dir.join(name))
+}
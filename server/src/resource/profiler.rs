@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Timing statistics for a single stage or event handler.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ProfileStats {
+  pub calls: u64,
+  pub total: Duration,
+  pub max: Duration,
+}
+
+impl ProfileStats {
+  fn record(&mut self, duration: Duration) {
+    self.calls += 1;
+    self.total += duration;
+    self.max = self.max.max(duration);
+  }
+
+  /// The average time taken per call.
+  pub fn mean(&self) -> Duration {
+    match self.calls {
+      0 => Duration::ZERO,
+      calls => self.total / calls as u32,
+    }
+  }
+}
+
+/// Opt-in profiler for the main update loop.
+///
+/// When enabled this records how long each stage of [`system::update`] takes,
+/// how long each event handler takes, and how many frames
+/// [`run_until_shutdown`] had to skip because the server was falling behind.
+/// Handler timings include the time taken by any events that the handler
+/// dispatches itself.
+///
+/// Profiling is disabled by default since timing every event handler has a
+/// noticeable overhead. It can be controlled in-game with the admin `profile`
+/// command.
+///
+/// [`system::update`]: crate::system::update
+/// [`run_until_shutdown`]: crate::AirmashGame::run_until_shutdown
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+  started: Option<Instant>,
+  frames: u64,
+  skipped_frames: u64,
+  frame: ProfileStats,
+  stages: BTreeMap<&'static str, ProfileStats>,
+  handlers: HashMap<&'static str, ProfileStats>,
+}
+
+impl Profiler {
+  pub fn enabled(&self) -> bool {
+    self.started.is_some()
+  }
+
+  /// Clear any existing results and start profiling.
+  pub fn start(&mut self) {
+    self.reset();
+    self.started = Some(Instant::now());
+  }
+
+  /// Stop profiling. Results collected so far are kept until the profiler is
+  /// started again or reset.
+  pub fn stop(&mut self) {
+    self.started = None;
+  }
+
+  /// Clear all results collected so far.
+  pub fn reset(&mut self) {
+    let started = self.started.map(|_| Instant::now());
+    *self = Self {
+      started,
+      ..Default::default()
+    };
+  }
+
+  pub fn record_frame(&mut self, duration: Duration) {
+    if self.enabled() {
+      self.frames += 1;
+      self.frame.record(duration);
+    }
+  }
+
+  pub fn record_skipped_frame(&mut self) {
+    if self.enabled() {
+      self.skipped_frames += 1;
+    }
+  }
+
+  pub fn record_stage(&mut self, stage: &'static str, duration: Duration) {
+    if self.enabled() {
+      self.stages.entry(stage).or_default().record(duration);
+    }
+  }
+
+  pub fn record_handler(&mut self, handler: &'static str, duration: Duration) {
+    if self.enabled() {
+      self.handlers.entry(handler).or_default().record(duration);
+    }
+  }
+
+  /// The number of frames that have been profiled.
+  pub fn frames(&self) -> u64 {
+    self.frames
+  }
+
+  /// The number of frames that were skipped because the server was falling
+  /// behind.
+  pub fn skipped_frames(&self) -> u64 {
+    self.skipped_frames
+  }
+
+  /// Timing statistics for each stage of the update loop.
+  pub fn stages(&self) -> impl Iterator<Item = (&'static str, &ProfileStats)> {
+    self.stages.iter().map(|(&name, stats)| (name, stats))
+  }
+
+  /// Timing statistics for a single event handler.
+  pub fn handler(&self, handler: &str) -> Option<&ProfileStats> {
+    self.handlers.get(handler)
+  }
+
+  /// The event handlers that have taken up the most time, in decreasing
+  /// order of total time.
+  pub fn slowest_handlers(&self, count: usize) -> Vec<(&'static str, &ProfileStats)> {
+    let mut handlers: Vec<_> = self
+      .handlers
+      .iter()
+      .map(|(&name, stats)| (name, stats))
+      .collect();
+    handlers.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.total));
+    handlers.truncate(count);
+    handlers
+  }
+
+  /// Generate a human-readable report listing all stages and the `handlers`
+  /// slowest event handlers.
+  pub fn report(&self, handlers: usize) -> String {
+    let mut out = String::new();
+    let elapsed = self.started.map(|s| s.elapsed()).unwrap_or_default();
+
+    let _ = writeln!(
+      out,
+      "{} frames ({} skipped), mean {:?}, max {:?}{}",
+      self.frames,
+      self.skipped_frames,
+      self.frame.mean(),
+      self.frame.max,
+      match self.started {
+        Some(_) => format!(", running for {}s", elapsed.as_secs()),
+        None => " (stopped)".to_owned(),
+      }
+    );
+
+    let _ = writeln!(out, "Stages:");
+    for (name, stats) in self.stages() {
+      let _ = writeln!(
+        out,
+        "  {:<12} mean {:>10?} max {:>10?}",
+        name,
+        stats.mean(),
+        stats.max
+      );
+    }
+
+    let _ = writeln!(out, "Slowest handlers:");
+    for (name, stats) in self.slowest_handlers(handlers) {
+      let _ = writeln!(
+        out,
+        "  {} - {} calls, total {:?}, mean {:?}, max {:?}",
+        name,
+        stats.calls,
+        stats.total,
+        stats.mean(),
+        stats.max
+      );
+    }
+
+    out
+  }
+
+  /// Write a full report, including every event handler, to a file.
+  pub fn dump(&self, path: impl AsRef<Path>) -> io::Result<()> {
+    std::fs::write(path, self.report(usize::MAX))
+  }
+}
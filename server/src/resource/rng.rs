@@ -0,0 +1,55 @@
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+/// Seeded random number generator used by all game systems.
+///
+/// Game code should use this instead of `rand::random` or `thread_rng` so
+/// that a recorded session can be replayed with the same random choices. It
+/// implements [`RngCore`] so the methods from [`rand::Rng`] can be used
+/// directly on it.
+///
+/// By default this is seeded from the system entropy source.
+#[derive(Clone, Debug)]
+pub struct GameRng {
+  seed: u64,
+  rng: StdRng,
+}
+
+impl GameRng {
+  /// Create a new random number generator with a fixed seed.
+  pub fn new(seed: u64) -> Self {
+    Self {
+      seed,
+      rng: StdRng::seed_from_u64(seed),
+    }
+  }
+
+  /// The seed that this generator was created with.
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+}
+
+impl Default for GameRng {
+  fn default() -> Self {
+    Self::new(rand::random())
+  }
+}
+
+impl RngCore for GameRng {
+  fn next_u32(&mut self) -> u32 {
+    self.rng.next_u32()
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    self.rng.next_u64()
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    self.rng.fill_bytes(dest)
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+    self.rng.try_fill_bytes(dest)
+  }
+}
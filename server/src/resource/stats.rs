@@ -2,4 +2,13 @@
 #[derive(Debug, Default)]
 pub struct ServerStats {
   pub num_players: u32,
+
+  /// The total number of outgoing messages that are queued but have not yet
+  /// been sent, across all connections.
+  pub queued_messages: usize,
+  /// The number of outgoing messages queued on the most backlogged connection.
+  pub max_queue_depth: usize,
+  /// The number of non-critical messages that have been dropped because a
+  /// connection's send queue was too full.
+  pub dropped_messages: u64,
 }
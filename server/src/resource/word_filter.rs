@@ -0,0 +1,158 @@
+use std::borrow::Cow;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use regex::bytes::{Captures, Regex};
+
+/// What to do with text that matches a filter rule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilterAction {
+  /// Replace every character of the matched text with `*`.
+  Mask,
+  /// Replace the matched text with some other text.
+  Replace(String),
+  /// Drop the whole message.
+  Drop,
+}
+
+#[derive(Clone, Debug)]
+struct FilterRule {
+  pattern: Regex,
+  action: FilterAction,
+}
+
+/// Filter for chat messages and player names.
+///
+/// By default the filter is empty and allows everything through. Rules are
+/// usually loaded from a file via [`WordFilter::load`]. Each line of the file
+/// has the form `<action> <pattern> [replacement]` where
+/// - `action` is one of `mask`, `replace`, or `drop`,
+/// - `pattern` is either a word, which is matched case-insensitively against
+///   whole words, or a regex prefixed with `re:`, and,
+/// - `replacement` is the rest of the line and is only used by `replace`.
+///
+/// Empty lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct WordFilter {
+  rules: Vec<FilterRule>,
+  path: Option<PathBuf>,
+}
+
+impl WordFilter {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Add a new rule that matches a single word.
+  pub fn add_word(&mut self, word: &str, action: FilterAction) {
+    let pattern = format!(r"(?i)\b{}\b", regex::escape(word));
+    self.rules.push(FilterRule {
+      pattern: Regex::new(&pattern).expect("escaped word was not a valid regex"),
+      action,
+    });
+  }
+
+  /// Add a new rule that matches a regex.
+  pub fn add_regex(&mut self, pattern: &str, action: FilterAction) -> Result<(), regex::Error> {
+    self.rules.push(FilterRule {
+      pattern: Regex::new(pattern)?,
+      action,
+    });
+    Ok(())
+  }
+
+  /// Parse a filter from the contents of a filter file.
+  pub fn parse(contents: &str) -> Result<Self, String> {
+    let mut filter = Self::new();
+
+    for (lineno, line) in contents.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let mut parts = line.splitn(3, char::is_whitespace);
+      let action = parts.next().unwrap_or_default();
+      let pattern = match parts.next() {
+        Some(pattern) => pattern,
+        None => return Err(format!("line {}: expected a pattern", lineno + 1)),
+      };
+      let rest = parts.next().map(str::trim);
+
+      let action = match (action, rest) {
+        ("mask", None) => FilterAction::Mask,
+        ("drop", None) => FilterAction::Drop,
+        ("replace", Some(replacement)) => FilterAction::Replace(replacement.to_owned()),
+        ("replace", None) => return Err(format!("line {}: expected a replacement", lineno + 1)),
+        ("mask" | "drop", Some(_)) => {
+          return Err(format!(
+            "line {}: unexpected text after pattern",
+            lineno + 1
+          ))
+        }
+        (action, _) => return Err(format!("line {}: unknown action `{}`", lineno + 1, action)),
+      };
+
+      match pattern.strip_prefix("re:") {
+        Some(regex) => filter
+          .add_regex(regex, action)
+          .map_err(|e| format!("line {}: {}", lineno + 1, e))?,
+        None => filter.add_word(pattern, action),
+      }
+    }
+
+    Ok(filter)
+  }
+
+  /// Load a filter from a filter file. The path is remembered so that the
+  /// filter can later be reloaded via [`WordFilter::reload`].
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)?;
+    let mut filter =
+      Self::parse(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    filter.path = Some(path.to_owned());
+
+    Ok(filter)
+  }
+
+  /// Reload the filter from the file it was originally loaded from. If there
+  /// is an error then the current rules are left unchanged.
+  ///
+  /// Returns `Ok(false)` if this filter was not loaded from a file.
+  pub fn reload(&mut self) -> io::Result<bool> {
+    let path = match &self.path {
+      Some(path) => path,
+      None => return Ok(false),
+    };
+
+    *self = Self::load(path)?;
+    Ok(true)
+  }
+
+  /// Apply the filter to `text`. Returns `None` if the text should be dropped
+  /// entirely.
+  pub fn filter<'t>(&self, text: &'t [u8]) -> Option<Cow<'t, [u8]>> {
+    let mut text = Cow::Borrowed(text);
+
+    for rule in &self.rules {
+      let replaced = match &rule.action {
+        FilterAction::Drop if rule.pattern.is_match(&text) => return None,
+        FilterAction::Drop => continue,
+        FilterAction::Mask => rule.pattern.replace_all(&text, |caps: &Captures| {
+          let len = String::from_utf8_lossy(&caps[0]).chars().count();
+          vec![b'*'; len]
+        }),
+        FilterAction::Replace(replacement) => rule
+          .pattern
+          .replace_all(&text, regex::bytes::NoExpand(replacement.as_bytes())),
+      };
+
+      if let Cow::Owned(replaced) = replaced {
+        text = Cow::Owned(replaced);
+      }
+    }
+
+    Some(text)
+  }
+}
@@ -0,0 +1,472 @@
+//! Saving and restoring the state of a running game.
+//!
+//! A [`Snapshot`] contains the players, mobs and missiles within the world
+//! along with the resources needed to keep entity IDs and player names
+//! consistent. Game modes can store their own state (e.g. flags and team
+//! scores in CTF) by registering an extension with the [`SnapshotRegistry`].
+//!
+//! Snapshots are meant to be taken when the server shuts down and restored
+//! when it starts up again. Restored mobs and missiles are spawned right away
+//! with their original IDs. Players are not spawned until they reconnect and
+//! log in with the token from the server's original `Login` packet as their
+//! session (the client's `Login.session`), at which point they get back the
+//! same ID, team, score, and so on. Until then their ID is reserved by a
+//! zombie entity which expires after the reconnect window in [`GameConfig`].
+//!
+//! Anything with a timestamp is stored relative to the time at which the
+//! snapshot was taken. Active powerups, admin roles, and other transient
+//! player state are not saved.
+//!
+//! [`GameConfig`]: crate::resource::GameConfig
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use bstr::BString;
+use hecs::Entity;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::component::*;
+use crate::config::{MissilePrototypeRef, MobPrototypeRef, PlanePrototypeRef};
+use crate::event::EntitySpawn;
+use crate::resource::{Config, EntityMapping, GameConfig, TakenNames};
+use crate::{AirmashGame, Vector2};
+
+const VERSION: u32 = 1;
+
+/// Saves the state of a game mode into a snapshot.
+pub type SaveExtension = fn(&AirmashGame) -> serde_json::Value;
+/// Restores the state of a game mode from a snapshot.
+pub type RestoreExtension = fn(&mut AirmashGame, serde_json::Value) -> Result<(), String>;
+
+/// Game mode specific state that is saved within snapshots.
+///
+/// Each extension is stored under its name within [`Snapshot::extensions`]
+/// and is only restored if an extension with the same name is registered
+/// when the snapshot is restored.
+#[derive(Clone, Default)]
+pub struct SnapshotRegistry {
+  extensions: BTreeMap<&'static str, (SaveExtension, RestoreExtension)>,
+}
+
+impl SnapshotRegistry {
+  pub fn register(&mut self, name: &'static str, save: SaveExtension, restore: RestoreExtension) {
+    self.extensions.insert(name, (save, restore));
+  }
+}
+
+/// The saved state of a player.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+  /// The bits of the player's [`Entity`].
+  pub entity: u64,
+  pub session: Uuid,
+  pub name: String,
+  pub plane: String,
+  pub flag: FlagCode,
+  pub team: u16,
+  pub level: u8,
+  pub score: u32,
+  pub earnings: u32,
+  pub kills: u32,
+  pub deaths: u32,
+  pub captures: u32,
+  pub damage: f32,
+  pub upgrades: [u8; 4],
+  pub pos: Vector2,
+  pub vel: Vector2,
+  pub rot: f32,
+  pub energy: f32,
+  pub health: f32,
+  pub alive: bool,
+  pub spectating: bool,
+  pub muted: bool,
+}
+
+/// The saved state of a stationary mob.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MobSnapshot {
+  pub entity: u64,
+  pub proto: String,
+  pub pos: Vector2,
+  /// How much longer the mob would have existed for.
+  pub lifetime: Duration,
+}
+
+/// The saved state of a missile.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MissileSnapshot {
+  pub entity: u64,
+  pub proto: String,
+  /// The bits of the [`Entity`] of the player that owns the missile.
+  pub owner: u64,
+  pub team: u16,
+  pub pos: Vector2,
+  pub vel: Vector2,
+  pub accel: Vector2,
+  /// How long ago the missile was fired.
+  pub age: Duration,
+  pub start: Vector2,
+  pub maxdist: f32,
+}
+
+/// The saved state of a game. See the [module docs](self) for details.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+  pub players: Vec<PlayerSnapshot>,
+  pub mobs: Vec<MobSnapshot>,
+  pub missiles: Vec<MissileSnapshot>,
+  /// The contents of the [`EntityMapping`] resource, as entity bits.
+  pub entity_mapping: Vec<(u16, u64)>,
+  /// The contents of the [`TakenNames`] resource.
+  pub taken_names: Vec<String>,
+  /// State saved by the extensions in the [`SnapshotRegistry`].
+  pub extensions: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile {
+  version: u32,
+  #[serde(flatten)]
+  snapshot: Snapshot,
+}
+
+impl Snapshot {
+  /// Load a snapshot written by [`Snapshot::save`].
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    let file: SnapshotFile = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    if file.version != VERSION {
+      return Err(io::Error::new(
+        ErrorKind::InvalidData,
+        format!("unsupported snapshot version {}", file.version),
+      ));
+    }
+
+    Ok(file.snapshot)
+  }
+
+  pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(
+      &mut out,
+      &SnapshotFile {
+        version: VERSION,
+        snapshot: self.clone(),
+      },
+    )?;
+    out.flush()
+  }
+}
+
+/// Players from a restored snapshot that have not reconnected yet, by session.
+#[derive(Default)]
+pub(crate) struct RestoredPlayers {
+  players: HashMap<Uuid, (PlayerSnapshot, Instant)>,
+}
+
+/// Marker for a player that has reconnected after a snapshot was restored.
+/// Their saved state is applied once the player has joined.
+pub(crate) struct RestoredPlayer(pub Box<PlayerSnapshot>);
+
+impl AirmashGame {
+  /// Register a game mode extension with the [`SnapshotRegistry`].
+  pub fn register_snapshot_extension(
+    &mut self,
+    name: &'static str,
+    save: SaveExtension,
+    restore: RestoreExtension,
+  ) {
+    self
+      .resources
+      .write::<SnapshotRegistry>()
+      .register(name, save, restore);
+  }
+
+  /// Take a snapshot of the current state of the game.
+  pub fn snapshot(&self) -> Snapshot {
+    let now = self.this_frame();
+    let mut snapshot = Snapshot::default();
+
+    let mut query = self
+      .world
+      .query::<(
+        &Session,
+        &Name,
+        &PlanePrototypeRef,
+        &FlagCode,
+        &Team,
+        &Level,
+        (
+          &Score,
+          &Earnings,
+          &KillCount,
+          &DeathCount,
+          &Captures,
+          &TotalDamage,
+        ),
+        &Upgrades,
+        (&Position, &Velocity, &Rotation, &Energy, &Health),
+        (&IsAlive, &IsSpectating, &IsMuted),
+      )>()
+      .with::<IsPlayer>();
+    for (ent, (session, name, plane, flag, team, level, stats, upgrades, physics, state)) in
+      query.iter()
+    {
+      let (score, earnings, kills, deaths, captures, damage) = stats;
+      let (pos, vel, rot, energy, health) = physics;
+      let (alive, spectating, muted) = state;
+
+      snapshot.players.push(PlayerSnapshot {
+        entity: ent.to_bits().get(),
+        session: session.0,
+        name: name.0.to_string(),
+        plane: plane.name.to_string(),
+        flag: *flag,
+        team: team.0,
+        level: level.0,
+        score: score.0,
+        earnings: earnings.0,
+        kills: kills.0,
+        deaths: deaths.0,
+        captures: captures.0,
+        damage: damage.0,
+        upgrades: [
+          upgrades.speed,
+          upgrades.defense,
+          upgrades.energy,
+          upgrades.missile,
+        ],
+        pos: pos.0,
+        vel: vel.0,
+        rot: rot.0,
+        energy: energy.0,
+        health: health.0,
+        alive: alive.0,
+        spectating: spectating.0,
+        muted: muted.0,
+      });
+    }
+
+    let mut query = self
+      .world
+      .query::<(&MobPrototypeRef, &Position, &Expiry)>()
+      .with::<IsMob>();
+    for (ent, (proto, pos, expiry)) in query.iter() {
+      snapshot.mobs.push(MobSnapshot {
+        entity: ent.to_bits().get(),
+        proto: proto.name.to_string(),
+        pos: pos.0,
+        lifetime: expiry.0.saturating_duration_since(now),
+      });
+    }
+
+    let mut query = self
+      .world
+      .query::<(
+        &MissilePrototypeRef,
+        &Owner,
+        &Team,
+        &Position,
+        &Velocity,
+        &Accel,
+        &SpawnTime,
+        &MissileTrajectory,
+      )>()
+      .with::<IsMissile>();
+    for (ent, (proto, owner, team, pos, vel, accel, spawn, trajectory)) in query.iter() {
+      snapshot.missiles.push(MissileSnapshot {
+        entity: ent.to_bits().get(),
+        proto: proto.name.to_string(),
+        owner: owner.0.to_bits().get(),
+        team: team.0,
+        pos: pos.0,
+        vel: vel.0,
+        accel: accel.0,
+        age: now.saturating_duration_since(spawn.0),
+        start: trajectory.start,
+        maxdist: trajectory.maxdist,
+      });
+    }
+
+    snapshot.entity_mapping = self
+      .resources
+      .read::<EntityMapping>()
+      .iter()
+      .map(|(&id, ent)| (id, ent.to_bits().get()))
+      .collect();
+    snapshot.entity_mapping.sort_unstable();
+    snapshot.taken_names = self
+      .resources
+      .read::<TakenNames>()
+      .iter()
+      .map(|name| name.to_string())
+      .collect();
+    snapshot.taken_names.sort_unstable();
+
+    let registry = self.resources.read::<SnapshotRegistry>();
+    for (&name, (save, _)) in &registry.extensions {
+      snapshot.extensions.insert(name.to_owned(), save(self));
+    }
+
+    snapshot
+  }
+
+  /// Take a snapshot of the game and write it to a file.
+  pub fn save_snapshot(&self, path: impl AsRef<Path>) -> io::Result<()> {
+    self.snapshot().save(path)
+  }
+
+  /// Restore the state of the game from a snapshot.
+  ///
+  /// This should be called after the game mode has been set up but before the
+  /// server starts running. Entities whose IDs are already in use or which
+  /// refer to prototypes that no longer exist in the config are skipped.
+  ///
+  /// Lifetimes and reconnect deadlines are measured from the time of the
+  /// current frame.
+  ///
+  /// # Errors
+  /// Returns an error if an extension fails to restore its state. Everything
+  /// else will have been restored by then.
+  pub fn restore_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+    let now = self.this_frame();
+    let window = self.resources.read::<GameConfig>().reconnect_window;
+    let in_use: std::collections::HashSet<u32> =
+      self.world.iter().map(|e| e.entity().id()).collect();
+    let available = |bits: u64| Entity::from_bits(bits).filter(|ent| !in_use.contains(&ent.id()));
+
+    let mut spawned = Vec::new();
+    let mut restored = RestoredPlayers::default();
+    for player in &snapshot.players {
+      let entity = match available(player.entity) {
+        Some(entity) => entity,
+        None => continue,
+      };
+
+      // Reserve the ID of the player until they reconnect.
+      self
+        .world
+        .spawn_at(entity, (Expiry(now + window), IsZombie));
+      restored
+        .players
+        .insert(player.session, (player.clone(), now + window));
+    }
+
+    let config = self.resources.read::<Config>();
+    for mob in &snapshot.mobs {
+      let (entity, &proto) = match (available(mob.entity), config.mobs.get(&*mob.proto)) {
+        (Some(entity), Some(proto)) => (entity, proto),
+        _ => continue,
+      };
+
+      self.world.spawn_at(
+        entity,
+        (
+          proto.server_type,
+          proto,
+          Position(mob.pos),
+          Expiry(now + mob.lifetime),
+          IsMob,
+        ),
+      );
+      spawned.push(entity);
+    }
+
+    for missile in &snapshot.missiles {
+      let entity = available(missile.entity);
+      let owner = Entity::from_bits(missile.owner);
+      let (entity, owner, &proto) = match (entity, owner, config.missiles.get(&*missile.proto)) {
+        (Some(entity), Some(owner), Some(proto)) => (entity, owner, proto),
+        _ => continue,
+      };
+
+      let mut builder = crate::defaults::build_default_missile();
+      builder
+        .add(Position(missile.pos))
+        .add(Velocity(missile.vel))
+        .add(Accel(missile.accel))
+        .add(proto)
+        .add(Owner(owner))
+        .add(Team(missile.team))
+        .add(SpawnTime(now.checked_sub(missile.age).unwrap_or(now)))
+        .add(proto.server_type)
+        .add(MissileTrajectory {
+          start: missile.start,
+          maxdist: missile.maxdist,
+        });
+      self.world.spawn_at(entity, builder.build());
+      spawned.push(entity);
+    }
+    drop(config);
+
+    {
+      let mut mapping = self.resources.write::<EntityMapping>();
+      for &(id, bits) in &snapshot.entity_mapping {
+        match Entity::from_bits(bits) {
+          Some(entity) if self.world.contains(entity) => {
+            mapping.insert(id, entity);
+          }
+          _ => (),
+        }
+      }
+    }
+    self.resources.write::<TakenNames>().extend(
+      snapshot
+        .taken_names
+        .iter()
+        .map(|name| BString::from(name.as_str())),
+    );
+    self.resources.insert(restored);
+
+    for entity in spawned {
+      self.dispatch(EntitySpawn { entity });
+    }
+
+    let registry = self.resources.read::<SnapshotRegistry>().clone();
+    for (&name, (_, restore)) in &registry.extensions {
+      if let Some(data) = snapshot.extensions.get(name) {
+        restore(self, data.clone()).map_err(|e| format!("{}: {}", name, e))?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Take the saved state of a player from a restored snapshot, along with
+  /// the entity reserved for them, if the session belongs to a player that has
+  /// not reconnected yet and whose ID is still reserved.
+  ///
+  /// Players that did not reconnect in time are released at the same time so
+  /// that their names can be used again.
+  pub(crate) fn take_restored_player(&mut self, session: &str) -> Option<(Entity, PlayerSnapshot)> {
+    let now = self.this_frame();
+    let mut restored = self.resources.get_mut::<RestoredPlayers>()?;
+    if restored.players.is_empty() {
+      return None;
+    }
+
+    let mut names = self.resources.write::<TakenNames>();
+    restored.players.retain(|_, (player, deadline)| {
+      let keep = *deadline >= now;
+      if !keep {
+        names.remove(&BString::from(player.name.as_str()));
+      }
+      keep
+    });
+
+    let session: Uuid = session.parse().ok()?;
+    let (player, _) = restored.players.remove(&session)?;
+    let entity = Entity::from_bits(player.entity)?;
+
+    if self.world.get::<IsZombie>(entity).is_err() {
+      names.remove(&BString::from(player.name.as_str()));
+      return None;
+    }
+
+    Some((entity, player))
+  }
+}
@@ -1,133 +1,509 @@
-use bstr::BString;
-use smallvec::SmallVec;
+use std::convert::TryFrom;
+use std::net::IpAddr;
+use std::time::SystemTime;
 
+use crate::command::*;
 use crate::component::*;
-use crate::event::PacketEvent;
-use crate::protocol::client::Command;
-use crate::protocol::server::CommandReply;
-use crate::protocol::CommandReplyType;
-use crate::resource::GameConfig;
-use crate::{AirmashGame, Vector2};
-
-#[handler]
-fn teleport(event: &PacketEvent<Command>, game: &mut AirmashGame) {
-  #[derive(Copy, Clone, Debug, PartialEq)]
-  pub struct ParsedCommand {
-    pub id: Option<u16>,
-    pub pos_x: f32,
-    pub pos_y: f32,
+use crate::network::{ConnectionMgr, DemoTarget, TlsConfig};
+use crate::protocol::server::Error;
+use crate::protocol::ErrorType;
+use crate::resource::{
+  AdminTokens, BanList, BanTarget, ConnectionLimits, OutputDirs, Profiler, WordFilter,
+};
+use crate::snapshot::Snapshot;
+use crate::{AirmashGame, Entity, Vector2};
+
+pub(super) fn register_commands(registry: &mut CommandRegistry) {
+  registry.register(
+    CommandSpec::new("auth", authenticate)
+      .arg("token", ArgType::String)
+      .help("Authenticate to use admin commands"),
+  );
+  registry.register(
+    CommandSpec::new("teleport", teleport)
+      .arg("player", ArgType::Integer)
+      .arg("x|place", ArgType::String)
+      .optional("y", ArgType::Number)
+      .permission(AdminRole::Admin)
+      .help("Teleport a player (0 for yourself) to a position or named place"),
+  );
+  registry.register(
+    CommandSpec::new("kick", kick)
+      .arg("player", ArgType::Player)
+      .permission(AdminRole::Moderator)
+      .help("Disconnect a player from the server"),
+  );
+  registry.register(
+    CommandSpec::new("ban-ip", |ctx, game| ban(ctx, game, true))
+      .arg("player", ArgType::Player)
+      .optional("duration", ArgType::String)
+      .permission(AdminRole::Admin)
+      .help("Ban the IP address of a player, optionally for a duration (e.g. 1h)"),
+  );
+  registry.register(
+    CommandSpec::new("ban-session", |ctx, game| ban(ctx, game, false))
+      .arg("player", ArgType::Player)
+      .optional("duration", ArgType::String)
+      .permission(AdminRole::Admin)
+      .help("Ban the login session token of a player, optionally for a duration (e.g. 1h)"),
+  );
+  registry.register(
+    CommandSpec::new("unban", unban)
+      .arg("ip|session", ArgType::String)
+      .permission(AdminRole::Admin)
+      .help("Remove a ban on an IP address or session"),
+  );
+  registry.register(
+    CommandSpec::new("limit", limit)
+      .arg("connections|logins|players", ArgType::String)
+      .optional("max|none", ArgType::String)
+      .permission(AdminRole::Admin)
+      .help("Show or change the cap on connections or logins per IP, or on total players"),
+  );
+  registry.register(
+    CommandSpec::new("reload-filter", reload_filter)
+      .permission(AdminRole::Admin)
+      .help("Reload the chat filter from its file"),
+  );
+  registry.register(
+    CommandSpec::new("reload-tls", reload_tls)
+      .permission(AdminRole::Admin)
+      .help("Reload the TLS certificate and key from their files"),
+  );
+  registry.register(
+    CommandSpec::new("reload-config", reload_config)
+      .permission(AdminRole::Owner)
+      .help("Re-run the config script and apply it"),
+  );
+  registry.register(
+    CommandSpec::new("profile", profile)
+      .arg("start|stop|reset|report|dump", ArgType::String)
+      .optional("count|file", ArgType::String)
+      .permission(AdminRole::Admin)
+      .help("Control the frame profiler, show its results, or write them to a file in the profile directory"),
+  );
+  registry.register(
+    CommandSpec::new("trace", trace)
+      .arg("on|off", ArgType::String)
+      .permission(AdminRole::Admin)
+      .help("Log every dispatched event and how long each of its handlers takes"),
+  );
+  registry.register(
+    CommandSpec::new("demo", demo)
+      .arg("start|stop", ArgType::String)
+      .optional("file", ArgType::String)
+      .optional("player", ArgType::Player)
+      .permission(AdminRole::Admin)
+      .help("Record a demo in the demo directory from the view of a player, or a spectator if none is given"),
+  );
+  registry.register(
+    CommandSpec::new("snapshot", snapshot)
+      .arg("file", ArgType::String)
+      .permission(AdminRole::Owner)
+      .help("Save a snapshot of the game to a file in the snapshot directory"),
+  );
+  registry.register(
+    CommandSpec::new("shutdown", shutdown)
+      .permission(AdminRole::Owner)
+      .help("Shut down the server after the current frame"),
+  );
+}
+
+fn authenticate(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  let token = ctx.args.string("token").unwrap_or_default();
+  let role = game
+    .resources
+    .read::<AdminTokens>()
+    .authenticate(token.as_bytes());
+
+  match role {
+    Some(role) => {
+      info!("Player {:?} authenticated as {}", ctx.player, role);
+
+      let _ = game.world.insert_one(ctx.player, role);
+      ctx.reply(game, format!("Authenticated as {}", role));
+      Ok(())
+    }
+    None => {
+      warn!("Player {:?} failed to authenticate", ctx.player);
+
+      Err("Invalid token".into())
+    }
   }
+}
 
-  fn named_positions(s: &[u8]) -> Option<Vector2> {
+fn teleport(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  fn named_positions(s: &str) -> Option<Vector2> {
     let (x, y) = match s {
-      b"blue-flag" => (-9670.0, -1470.0),
-      b"red-flag" => (8600.0, -940.0),
-      b"greenland-spa-and-lounge" => (-5000.0, -7000.0),
-      b"greenland" => (-5000.0, -7000.0),
-      b"crimea" => (2724.0, -2321.0),
+      "blue-flag" => (-9670.0, -1470.0),
+      "red-flag" => (8600.0, -940.0),
+      "greenland-spa-and-lounge" => (-5000.0, -7000.0),
+      "greenland" => (-5000.0, -7000.0),
+      "crimea" => (2724.0, -2321.0),
       // The exact origin of how this name was
       // determined is shrouded in mystery.
-      b"mt-detect" => (3550.0, -850.0),
-      b"red-spawn" => (7818.0, -2930.0),
-      b"blue-spawn" => (-8878.0, -2971.0),
+      "mt-detect" => (3550.0, -850.0),
+      "red-spawn" => (7818.0, -2930.0),
+      "blue-spawn" => (-8878.0, -2971.0),
       _ => return None,
     };
 
     Some(Vector2::new(x, y))
   }
 
-  fn parse_command_data(s: &BString) -> Result<ParsedCommand, String> {
-    let args: SmallVec<[_; 3]> = s.split(|&x| x == b' ').collect();
-
-    fn parse_arg<T: std::str::FromStr>(bytes: &[u8], err: &'static str) -> Result<T, &'static str> {
-      std::str::from_utf8(bytes)
-        .map_err(|_| err)?
-        .parse()
-        .map_err(|_| err)
+  let place = ctx.args.string("x|place").unwrap_or_default();
+  let pos = match ctx.args.number("y") {
+    Some(y) => {
+      let x: f32 = place.parse().map_err(|_| "Couldn't parse position")?;
+      Vector2::new(x, y)
     }
+    None => named_positions(place).ok_or("Unknown named position")?,
+  };
 
-    let id = match parse_arg(args[0], "Player ID was not a number")? {
-      0 => None,
-      x => Some(x),
-    };
+  if pos.x.abs() > 16384.0 {
+    return Err(format!("{} is out of bounds", pos.x).into());
+  }
+  if pos.y.abs() > 8192.0 {
+    return Err(format!("{} is out of bounds", pos.y).into());
+  }
 
-    let command = if args.len() == 3 {
-      ParsedCommand {
-        id,
-        pos_x: parse_arg(args[1], "Couldn't parse position")?,
-        pos_y: parse_arg(args[2], "Couldn't parse position")?,
-      }
-    } else {
-      let pos = match named_positions(args[1]) {
-        Some(pos) => pos,
-        None => return Err("Unknown named position".to_string()),
-      };
+  let target = match ctx.args.integer("player").unwrap_or_default() {
+    0 => ctx.player,
+    id => u16::try_from(id)
+      .ok()
+      .and_then(|id| game.find_entity_by_id(id))
+      .ok_or("Unknown entity")?,
+  };
 
-      ParsedCommand {
-        id,
-        pos_x: pos.x,
-        pos_y: pos.y,
-      }
-    };
+  let start_time = game.start_time();
+
+  if let Ok(mut position) = game.world.get_mut::<Position>(target) {
+    position.0 = pos;
+  }
+
+  // If we've teleported a player then have an update happen right away.
+  if let Ok(mut last_update) = game.world.get_mut::<LastUpdateTime>(target) {
+    last_update.0 = start_time;
+  }
+
+  Ok(())
+}
+
+/// Send an error to a player and then close all their connections.
+fn disconnect(game: &mut AirmashGame, player: Entity, error: ErrorType) {
+  game.send_to(player, Error { error });
+  game.resources.write::<ConnectionMgr>().close_entity(player);
+}
 
-    if command.pos_x.abs() > 16384.0 {
-      return Err(format!("{} is out of bounds", command.pos_x));
+/// Players can only be kicked or banned by someone with a higher role than
+/// their own.
+fn check_outranks(ctx: &CommandContext, game: &AirmashGame, target: Entity) -> CommandResult {
+  let role = |player| game.world.get::<AdminRole>(player).ok().map(|role| *role);
+
+  if role(target) >= role(ctx.player) {
+    return Err("You cannot do that to a player with an equal or higher role".into());
+  }
+
+  Ok(())
+}
+
+fn kick(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  let player = ctx.args.player("player").ok_or(CommandError::Ignore)?;
+  check_outranks(ctx, game, player)?;
+
+  info!("Player {:?} kicked {:?}", ctx.player, player);
+
+  disconnect(game, player, ErrorType::Kicked);
+  ctx.reply(game, format!("Kicked player {}", player.id()));
+
+  Ok(())
+}
+
+fn ban(ctx: &CommandContext, game: &mut AirmashGame, by_ip: bool) -> CommandResult {
+  let player = ctx.args.player("player").ok_or(CommandError::Ignore)?;
+  check_outranks(ctx, game, player)?;
+  let expiry = match ctx.args.string("duration") {
+    Some(duration) => {
+      let duration = humantime::parse_duration(duration).map_err(|_| "Invalid ban duration")?;
+      Some(SystemTime::now() + duration)
     }
-    if command.pos_y.abs() > 8192.0 {
-      return Err(format!("{} is out of bounds", command.pos_y));
+    None => None,
+  };
+
+  let target = if by_ip {
+    let connmgr = game.resources.read::<ConnectionMgr>();
+    let addr = connmgr
+      .primary(player)
+      .and_then(|conn| connmgr.socket_addr(conn))
+      .ok_or("Player has no active connection")?;
+
+    BanTarget::Ip(addr.ip())
+  } else {
+    let session = game
+      .world
+      .get::<LoginSession>(player)
+      .map_err(|_| CommandError::Ignore)?;
+    if session.0.is_empty() || session.0 == "none" {
+      return Err("Player did not log in with a session token".into());
+    }
+
+    BanTarget::Session(session.0.to_string())
+  };
+
+  info!("Player {:?} banned {:?} ({:?})", ctx.player, player, target);
+
+  game.resources.read::<BanList>().ban(target, expiry);
+  disconnect(game, player, ErrorType::Banned);
+  ctx.reply(game, format!("Banned player {}", player.id()));
+
+  Ok(())
+}
+
+fn unban(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  let value = ctx.args.string("ip|session").unwrap_or_default();
+  let target = match value.parse::<IpAddr>() {
+    Ok(ip) => BanTarget::Ip(ip),
+    Err(_) => BanTarget::Session(value.to_owned()),
+  };
+
+  if !game.resources.read::<BanList>().unban(&target) {
+    return Err(format!("{} is not banned", value).into());
+  }
+
+  ctx.reply(game, format!("Unbanned {}", value));
+
+  Ok(())
+}
+
+fn limit(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  let name = ctx
+    .args
+    .string("connections|logins|players")
+    .unwrap_or_default();
+  let handle = game.resources.read::<ConnectionLimits>().clone();
+  let mut limits = handle.limits();
+
+  let (cap, description) = match name {
+    "connections" => (&mut limits.connections_per_ip, "connections per IP"),
+    "logins" => (&mut limits.logins_per_ip, "logins per IP per minute"),
+    "players" => (&mut limits.max_players, "players"),
+    _ => return Err(format!("Unknown limit `{}`", name).into()),
+  };
+
+  let message = match ctx.args.string("max|none") {
+    Some(value) => {
+      *cap = match value {
+        "none" => None,
+        value => Some(value.parse().map_err(|_| "Invalid limit")?),
+      };
+
+      info!(
+        "Player {:?} set the {} limit to {:?}",
+        ctx.player, name, cap
+      );
+      format!("Set the limit on {} to {}", description, display_cap(*cap))
     }
+    None => format!("The limit on {} is {}", description, display_cap(*cap)),
+  };
+
+  handle.set(limits);
+  ctx.reply(game, message);
+
+  Ok(())
+}
 
-    Ok(command)
+fn display_cap(cap: Option<u32>) -> String {
+  match cap {
+    Some(cap) => cap.to_string(),
+    None => "none".to_owned(),
   }
+}
+
+fn reload_filter(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  let reloaded = game.resources.write::<WordFilter>().reload();
 
-  if !game.resources.read::<GameConfig>().admin_enabled {
-    return;
+  match reloaded {
+    Ok(true) => {
+      info!("Player {:?} reloaded the chat filter", ctx.player);
+      ctx.reply(game, "Reloaded chat filter");
+      Ok(())
+    }
+    Ok(false) => Err("The chat filter was not loaded from a file".into()),
+    Err(e) => {
+      warn!("Unable to reload the chat filter: {}", e);
+      Err(format!("Unable to reload the chat filter: {}", e).into())
+    }
   }
+}
 
-  if event.packet.com != "teleport" {
-    return;
+fn reload_tls(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  let tls = game
+    .resources
+    .get::<TlsConfig>()
+    .map(|tls| tls.clone())
+    .ok_or("TLS is not enabled on this server")?;
+
+  match tls.reload() {
+    Ok(()) => {
+      info!("Player {:?} reloaded the TLS certificate", ctx.player);
+      ctx.reply(game, "Reloaded TLS certificate");
+      Ok(())
+    }
+    Err(e) => {
+      warn!("Unable to reload the TLS certificate: {}", e);
+      Err(format!("Unable to reload the TLS certificate: {}", e).into())
+    }
   }
+}
 
-  let command = match parse_command_data(&event.packet.data) {
-    Ok(command) => command,
+fn reload_config(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  match game.reload_config() {
+    Ok(true) => {
+      info!("Player {:?} reloaded the config", ctx.player);
+      ctx.reply(game, "Reloaded config");
+      Ok(())
+    }
+    Ok(false) => Err("The config was not loaded from a file".into()),
     Err(e) => {
-      game.send_to(
-        event.entity,
-        CommandReply {
-          ty: CommandReplyType::ShowInConsole,
-          text: e.into(),
-        },
-      );
-      return;
+      warn!("Unable to reload the config: {}", e);
+      Err(format!("Unable to reload the config: {}", e).into())
     }
-  };
+  }
+}
 
-  let target = match command.id {
-    Some(id) => match game.find_entity_by_id(id) {
-      Some(ent) => ent,
-      None => {
-        game.send_to(
-          event.entity,
-          CommandReply {
-            ty: CommandReplyType::ShowInConsole,
-            text: "Unknown entity".into(),
-          },
-        );
-        return;
+fn profile(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  let action = ctx
+    .args
+    .string("start|stop|reset|report|dump")
+    .unwrap_or_default();
+  let arg = ctx.args.string("count|file");
+
+  let message = {
+    let mut profiler = game.resources.write::<Profiler>();
+
+    match action {
+      "start" => {
+        profiler.start();
+        "Started profiling".to_owned()
+      }
+      "stop" => {
+        profiler.stop();
+        "Stopped profiling".to_owned()
+      }
+      "reset" => {
+        profiler.reset();
+        "Cleared profiling results".to_owned()
       }
-    },
-    None => event.entity,
+      "report" => {
+        let count = match arg {
+          Some(count) => count.parse().map_err(|_| "Invalid handler count")?,
+          None => 5,
+        };
+        profiler.report(count)
+      }
+      "dump" => {
+        let name = arg.ok_or("A file to write the results to is required")?;
+        let path = game.resources.read::<OutputDirs>().profile(name)?;
+        profiler.dump(&path).map_err(|e| {
+          warn!(
+            "Unable to write profiling results to {}: {}",
+            path.display(),
+            e
+          );
+          format!("Unable to write profiling results: {}", e)
+        })?;
+        format!("Wrote profiling results to {}", name)
+      }
+      _ => return Err(format!("Unknown profile action `{}`", action).into()),
+    }
   };
 
-  let start_time = game.start_time();
+  info!("Player {:?} ran profile {}", ctx.player, action);
+  ctx.reply(game, message);
 
-  if let Ok(mut pos) = game.world.get_mut::<Position>(target) {
-    pos.x = command.pos_x;
-    pos.y = command.pos_y;
-  }
+  Ok(())
+}
 
-  // If we've teleported a player then have an update happen right away.
-  if let Ok(mut last_update) = game.world.get_mut::<LastUpdateTime>(target) {
-    last_update.0 = start_time;
+fn trace(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  let action = ctx.args.string("on|off").unwrap_or_default();
+  let enabled = match action {
+    "on" => true,
+    "off" => false,
+    _ => return Err(format!("Unknown trace action `{}`", action).into()),
+  };
+
+  game.set_event_tracing(enabled);
+
+  info!("Player {:?} turned event tracing {}", ctx.player, action);
+  ctx.reply(game, format!("Event tracing is now {}", action));
+
+  Ok(())
+}
+
+fn demo(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  let action = ctx.args.string("start|stop").unwrap_or_default();
+
+  let message = match action {
+    "start" => {
+      let name = ctx
+        .args
+        .string("file")
+        .ok_or("A file to record the demo to is required")?;
+      let path = game.resources.read::<OutputDirs>().demo(name)?;
+      let target = match ctx.args.player("player") {
+        Some(player) => DemoTarget::Player(player),
+        None => DemoTarget::Spectator,
+      };
+
+      game.record_demo(&path, target).map_err(|e| {
+        warn!("Unable to record demo to {}: {}", path.display(), e);
+        format!("Unable to record demo: {}", e)
+      })?;
+      format!("Recording demo to {}", name)
+    }
+    "stop" => {
+      game.stop_demo();
+      "Stopped recording demo".to_owned()
+    }
+    _ => return Err(format!("Unknown demo action `{}`", action).into()),
+  };
+
+  info!("Player {:?} ran demo {}", ctx.player, action);
+  ctx.reply(game, message);
+
+  Ok(())
+}
+
+fn snapshot(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  let name = ctx.args.string("file").unwrap_or_default();
+  let path = game.resources.read::<OutputDirs>().snapshot(name)?;
+
+  // Only ever replace older snapshots, never some other file that happens to
+  // be in the directory.
+  if path.exists() && Snapshot::load(&path).is_err() {
+    return Err(format!("`{}` already exists and is not a snapshot", name).into());
   }
+
+  game.save_snapshot(&path).map_err(|e| {
+    warn!("Unable to save snapshot to {}: {}", path.display(), e);
+    format!("Unable to save snapshot: {}", e)
+  })?;
+
+  info!(
+    "Player {:?} saved a snapshot to {}",
+    ctx.player,
+    path.display()
+  );
+  ctx.reply(game, format!("Saved snapshot to {}", name));
+
+  Ok(())
+}
+
+fn shutdown(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
+  info!("Player {:?} shut down the server", ctx.player);
+
+  ctx.reply(game, "Shutting down");
+  game.shutdown();
+
+  Ok(())
 }
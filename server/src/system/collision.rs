@@ -321,8 +321,9 @@ fn collide_player_mob(game: &mut AirmashGame) {
   drop(mobs);
   drop(players);
 
+  // The mob is despawned by the pickup handler so that it can stay on the map if
+  // the pickup gets cancelled.
   for event in events {
     game.dispatch(event);
-    game.despawn(event.mob);
   }
 }
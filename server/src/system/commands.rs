@@ -1,20 +1,46 @@
+use std::convert::TryFrom;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
 
-use bstr::{BString, ByteSlice};
-
+use crate::command::*;
 use crate::component::*;
 use crate::config::PlanePrototypeRef;
-use crate::event::{PacketEvent, PlayerChangePlane, PlayerRespawn, PlayerSpectate};
-use crate::protocol::client::Command;
+use crate::event::{BeforeRespawn, PlayerChangePlane, PlayerRespawn, PlayerSpectate};
 use crate::protocol::server::PlayerFlag;
 use crate::protocol::{server as s, ErrorType, PlaneType, UpgradeType};
 use crate::resource::{Config, GameConfig, ThisFrame};
 use crate::util::spectate::*;
 use crate::AirmashGame;
 
-#[handler]
-fn on_respawn_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
+pub(super) fn register_commands(registry: &mut CommandRegistry) {
+  registry.register(
+    CommandSpec::new("respawn", on_respawn_command)
+      .arg("plane", ArgType::Integer)
+      .help("Respawn as a different plane (1-5)"),
+  );
+  registry.register(
+    CommandSpec::new("flag", on_flag_command)
+      .optional("flag", ArgType::String)
+      .help("Change your flag"),
+  );
+  registry.register(
+    CommandSpec::new("spectate", on_spectate_command)
+      .arg("player", ArgType::Integer)
+      .help("Spectate a player (-1 for next, -2 for previous, -3 to force)"),
+  );
+  registry.register(
+    CommandSpec::new("upgrade", on_upgrade_command)
+      .arg("type", ArgType::Integer)
+      .help("Apply an upgrade (1-4)"),
+  );
+  registry.register(
+    CommandSpec::new("uptime", on_uptime_command)
+      .optional("format", ArgType::String)
+      .help("Show how long the server has been running"),
+  );
+}
+
+fn on_respawn_command(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
   fn respawn_allowed(
     alive: bool,
     respawn_allowed: bool,
@@ -66,9 +92,8 @@ fn on_respawn_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
     true
   }
 
-  fn parse_plane(s: &BString) -> Result<PlaneType, ()> {
-    let s = s.to_str().map_err(|_| ())?;
-    let num: u8 = s.parse().map_err(|_| ())?;
+  fn parse_plane(num: i64) -> Result<PlaneType, ()> {
+    let num = u8::try_from(num).map_err(|_| ())?;
     match PlaneType::from(num) {
       PlaneType::Unknown(_) => Err(()),
       plane => Ok(plane),
@@ -76,46 +101,30 @@ fn on_respawn_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
   }
 
   if !game.resources.read::<GameConfig>().allow_respawn {
-    return;
+    return Err(CommandError::Ignore);
   }
 
-  if event.packet.com != "respawn" {
-    return;
-  }
-
-  let newplane = match parse_plane(&event.packet.data) {
-    Ok(newplane) => newplane,
-    Err(_) => return,
-  };
+  let newplane =
+    parse_plane(ctx.args.integer("plane").unwrap_or_default()).map_err(|_| "Unknown plane type")?;
 
   let this_frame = game.resources.read::<ThisFrame>().0;
   let config = game.resources.read::<Config>();
 
-  let mut query = match game.world.query_one::<(
-    &RespawnAllowed,
-    &mut IsAlive,
-    &Health,
-    &LastActionTime,
-    &mut PlanePrototypeRef,
-  )>(event.entity)
+  let mut query = match game
+    .world
+    .query_one::<(&RespawnAllowed, &IsAlive, &Health, &LastActionTime)>(ctx.player)
   {
     Ok(query) => query.with::<IsPlayer>(),
-    Err(_) => return,
+    Err(_) => return Ok(()),
   };
 
-  let (&allowed, alive, &health, &last_action, proto) = match query.get() {
+  let (&allowed, alive, &health, &last_action) = match query.get() {
     Some(query) => query,
-    None => return,
+    None => return Ok(()),
   };
 
   if !respawn_allowed(alive.0, allowed.0, health.0, last_action.0, this_frame) {
-    game.send_to(
-      event.entity,
-      s::Error {
-        error: ErrorType::IdleRequiredBeforeRespawn,
-      },
-    );
-    return;
+    return Err(ErrorType::IdleRequiredBeforeRespawn.into());
   }
 
   let pname = match newplane {
@@ -130,66 +139,84 @@ fn on_respawn_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
     Some(proto) => *proto,
     None => {
       game.send_to(
-        event.entity,
+        ctx.player,
         s::ServerMessage {
           ty: crate::protocol::ServerMessageType::Banner,
           duration: 5000,
           text: format!("{:?} is not available on this server", newplane).into(),
         },
       );
-      return;
+      return Ok(());
     }
   };
 
-  let old_proto = std::mem::replace(proto, new_proto);
-  let prev_alive = std::mem::replace(&mut alive.0, true);
+  let prev_alive = alive.0;
 
   drop(query);
   drop(config);
 
+  let before = game.dispatch_pre(BeforeRespawn {
+    player: ctx.player,
+    alive: prev_alive,
+    pos: None,
+    cancelled: false,
+  });
+  if before.cancelled {
+    return Ok(());
+  }
+
+  let (alive, proto) = match game
+    .world
+    .query_one_mut::<(&mut IsAlive, &mut PlanePrototypeRef)>(ctx.player)
+  {
+    Ok(query) => query,
+    Err(_) => return Ok(()),
+  };
+  let old_proto = std::mem::replace(proto, new_proto);
+  alive.0 = true;
+
   // We need to make sure to update the plane type before respawning the player as
   // otherwise using Q and E for strafing in a mohawk stops working. See issue
   // #201 for a complete description of the issue and a root cause analysis behind
   // why this is the solution.
   if old_proto.server_type != new_proto.server_type {
     game.dispatch(PlayerChangePlane {
-      player: event.entity,
+      player: ctx.player,
       old_proto,
     });
   }
 
   game.dispatch(PlayerRespawn {
-    player: event.entity,
+    player: ctx.player,
     alive: prev_alive,
+    pos: before.pos,
   });
-}
 
-#[handler]
-fn on_flag_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
-  if event.packet.com != "flag" {
-    return;
-  }
+  Ok(())
+}
 
+fn on_flag_command(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
   let (flag, _) = match game
     .world
-    .query_one_mut::<(&mut FlagCode, &IsPlayer)>(event.entity)
+    .query_one_mut::<(&mut FlagCode, &IsPlayer)>(ctx.player)
   {
     Ok(query) => query,
-    Err(_) => return,
+    Err(_) => return Ok(()),
   };
 
-  let newflag = FlagCode::from_str(event.packet.data.to_str().unwrap_or("UN"))
-    .unwrap_or(FlagCode::UnitedNations);
+  let newflag =
+    FlagCode::from_str(ctx.args.string("flag").unwrap_or("UN")).unwrap_or(FlagCode::UnitedNations);
   *flag = newflag;
 
   game.send_to_all(PlayerFlag {
-    id: event.entity.id() as _,
+    id: ctx.player.id() as _,
     flag: newflag,
   });
+
+  Ok(())
 }
 
-#[handler]
-fn on_spectate_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
+fn on_spectate_command(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
   fn can_spectate(
     is_spec: bool,
     is_alive: bool,
@@ -216,9 +243,7 @@ fn on_spectate_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
     true
   }
 
-  fn parse_spectate_data(s: &str) -> Result<SpectateTarget, ()> {
-    let arg: i32 = s.parse().map_err(|_| ())?;
-
+  fn parse_spectate_data(arg: i64) -> Result<SpectateTarget, ()> {
     if arg < -3 || arg > u16::MAX as _ {
       return Err(());
     }
@@ -231,14 +256,8 @@ fn on_spectate_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
     })
   }
 
-  if event.packet.com != "spectate" {
-    return;
-  }
-
-  let tgt = match parse_spectate_data(event.packet.data.to_str().unwrap_or("")) {
-    Ok(tgt) => tgt,
-    Err(_) => return,
-  };
+  let tgt = parse_spectate_data(ctx.args.integer("player").unwrap_or_default())
+    .map_err(|_| "Invalid spectate target")?;
 
   let this_frame = game.this_frame();
   let mut query = match game.world.query_one::<(
@@ -247,77 +266,63 @@ fn on_spectate_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
     &mut Spectating,
     &Health,
     &LastActionTime,
-  )>(event.entity)
+  )>(ctx.player)
   {
     Ok(query) => query.with::<IsPlayer>(),
-    Err(_) => return,
+    Err(_) => return Ok(()),
   };
   let (spec, alive, target, health, last_action) = match query.get() {
     Some(query) => query,
-    None => return,
+    None => return Ok(()),
   };
 
   if !can_spectate(spec.0, alive.0, health.0, last_action.0, this_frame) {
-    game.send_to(
-      event.entity,
-      s::Error {
-        error: ErrorType::IdleRequiredBeforeSpectate,
-      },
-    );
-    return;
+    return Err(ErrorType::IdleRequiredBeforeSpectate.into());
   }
 
-  target.0 = spectate_target(event.entity, target.0, tgt, game);
+  target.0 = spectate_target(ctx.player, target.0, tgt, game);
   spec.0 = true;
 
   drop(query);
 
   let was_alive = std::mem::replace(
-    &mut game.world.get_mut::<IsAlive>(event.entity).unwrap().0,
+    &mut game.world.get_mut::<IsAlive>(ctx.player).unwrap().0,
     false,
   );
 
   game.dispatch(PlayerSpectate {
-    player: event.entity,
+    player: ctx.player,
     was_alive,
   });
+
+  Ok(())
 }
 
-#[handler]
-fn on_upgrade_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
+fn on_upgrade_command(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
   use crate::protocol::server::PlayerUpgrade;
 
-  if event.packet.com != "upgrade" {
-    return;
-  }
-
   let (upgrades, prev, _) = match game
     .world
-    .query_one_mut::<(&mut Upgrades, &mut PrevUpgrades, &IsPlayer)>(event.entity)
+    .query_one_mut::<(&mut Upgrades, &mut PrevUpgrades, &IsPlayer)>(ctx.player)
   {
     Ok(query) => query,
-    Err(_) => return,
-  };
-
-  let upgrade_num: u8 = match event.packet.data.to_str_lossy().parse() {
-    Ok(upgrade_num) => upgrade_num,
-    Err(_) => return,
+    Err(_) => return Ok(()),
   };
 
   if upgrades.unused == 0 {
-    return;
+    return Ok(());
   }
 
-  let (count, ty) = match upgrade_num {
+  let (count, ty) = match ctx.args.integer("type").unwrap_or_default() {
     1 => (&mut upgrades.speed, UpgradeType::Speed),
     2 => (&mut upgrades.defense, UpgradeType::Defense),
     3 => (&mut upgrades.energy, UpgradeType::Energy),
     4 => (&mut upgrades.missile, UpgradeType::Missile),
-    _ => return,
+    _ => return Err("Unknown upgrade type".into()),
   };
 
   if *count == 5 {
-    return;
+    return Ok(());
   }
 
   *count += 1;
@@ -333,31 +338,24 @@ fn on_upgrade_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
     missile: upgrades.missile,
   };
 
-  game.force_update(event.entity);
-  game.send_to(event.entity, packet);
-}
+  game.force_update(ctx.player);
+  game.send_to(ctx.player, packet);
 
-#[handler]
-fn on_uptime_command(event: &PacketEvent<Command>, game: &mut AirmashGame) {
-  if event.packet.com != "uptime" {
-    return;
-  }
+  Ok(())
+}
 
+fn on_uptime_command(ctx: &CommandContext, game: &mut AirmashGame) -> CommandResult {
   let start_time = game.start_time();
   let this_frame = game.this_frame();
   let uptime = this_frame.saturating_duration_since(start_time);
 
-  let message = if event.packet.data == "raw" {
+  let message = if ctx.args.string("format") == Some("raw") {
     uptime.as_secs().to_string()
   } else {
     humantime::format_duration(Duration::from_secs(uptime.as_secs())).to_string()
   };
 
-  game.send_to(
-    event.entity,
-    s::CommandReply {
-      ty: airmash_protocol::CommandReplyType::ShowInConsole,
-      text: message.into(),
-    },
-  );
+  ctx.reply(game, message);
+
+  Ok(())
 }
@@ -1,27 +1,106 @@
-use crate::component::{IsPlayer, Position, SpecialActive, Team};
+use bstr::{BStr, BString};
+
+use crate::component::*;
 use crate::config::PlanePrototypeRef;
 use crate::event::PacketEvent;
 use crate::protocol::client::{Chat, Say, TeamChat, Whisper};
-use crate::protocol::server as s;
-use crate::AirmashGame;
+use crate::protocol::{server as s, ErrorType, ServerMessageType};
+use crate::resource::{ChatLimits, WordFilter};
+use crate::{AirmashGame, Entity};
+
+/// Check whether a player is allowed to chat. If the player has been muted or
+/// is sending messages too quickly then they will be notified that their
+/// message was dropped.
+fn can_chat(game: &mut AirmashGame, player: Entity, text: &BStr) -> bool {
+  let this_frame = game.this_frame();
+  let limits = game.resources.read::<ChatLimits>();
+
+  let (muted, throttle, _) = match game
+    .world
+    .query_one_mut::<(&IsMuted, &mut ChatThrottle, &IsPlayer)>(player)
+  {
+    Ok(query) => query,
+    Err(_) => return false,
+  };
+
+  if muted.0 {
+    drop(limits);
+    game.send_to(player, s::ChatVoteMuted);
+    return false;
+  }
+
+  let check = throttle.check(&limits, this_frame, text);
+  drop(limits);
+
+  match check {
+    ChatCheck::Allowed => return true,
+    ChatCheck::Throttled | ChatCheck::StillMuted => (),
+    ChatCheck::Muted(duration) => {
+      info!(
+        "Player {:?} has been muted for {:?} for flooding chat",
+        player, duration
+      );
+
+      game.send_to(
+        player,
+        s::ServerMessage {
+          ty: ServerMessageType::Banner,
+          duration: 5000,
+          text: format!(
+            "You have been muted for {} for flooding chat",
+            humantime::format_duration(duration)
+          )
+          .into(),
+        },
+      );
+    }
+  }
+
+  game.send_to(
+    player,
+    s::Error {
+      error: ErrorType::ChatThrottled,
+    },
+  );
+
+  false
+}
+
+/// Run a message through the [`WordFilter`]. Returns `None` if the message
+/// should be dropped.
+fn filter_text(game: &AirmashGame, text: &BString) -> Option<BString> {
+  game
+    .resources
+    .read::<WordFilter>()
+    .filter(text)
+    .map(|text| text.into_owned().into())
+}
 
 #[handler]
 fn on_chat(event: &PacketEvent<Chat>, game: &mut AirmashGame) {
-  if game.world.get::<IsPlayer>(event.entity).is_err() {
+  if !can_chat(game, event.entity, event.packet.text.as_ref()) {
     return;
   }
+  let text = match filter_text(game, &event.packet.text) {
+    Some(text) => text,
+    None => return,
+  };
 
   game.send_to_all(s::ChatPublic {
     id: event.entity.id() as _,
-    text: event.packet.text.clone(),
+    text,
   });
 }
 
 #[handler]
 fn on_team_chat(event: &PacketEvent<TeamChat>, game: &mut AirmashGame) {
-  if game.world.get::<IsPlayer>(event.entity).is_err() {
+  if !can_chat(game, event.entity, event.packet.text.as_ref()) {
     return;
   }
+  let text = match filter_text(game, &event.packet.text) {
+    Some(text) => text,
+    None => return,
+  };
 
   let team = game.world.get::<Team>(event.entity).unwrap();
 
@@ -29,16 +108,20 @@ fn on_team_chat(event: &PacketEvent<TeamChat>, game: &mut AirmashGame) {
     team.0,
     s::ChatTeam {
       id: event.entity.id() as _,
-      text: event.packet.text.clone(),
+      text,
     },
   );
 }
 
 #[handler]
 fn on_whisper(event: &PacketEvent<Whisper>, game: &mut AirmashGame) {
-  if game.world.get::<IsPlayer>(event.entity).is_err() {
+  if !can_chat(game, event.entity, event.packet.text.as_ref()) {
     return;
   }
+  let text = match filter_text(game, &event.packet.text) {
+    Some(text) => text,
+    None => return,
+  };
 
   let target = match game.find_entity_by_id(event.packet.id) {
     Some(entity) => entity,
@@ -52,7 +135,7 @@ fn on_whisper(event: &PacketEvent<Whisper>, game: &mut AirmashGame) {
   let packet = s::ChatWhisper {
     to: target.id() as _,
     from: event.entity.id() as _,
-    text: event.packet.text.clone(),
+    text,
   };
 
   if event.entity != target {
@@ -63,6 +146,14 @@ fn on_whisper(event: &PacketEvent<Whisper>, game: &mut AirmashGame) {
 
 #[handler]
 fn on_say(event: &PacketEvent<Say>, game: &mut AirmashGame) {
+  if !can_chat(game, event.entity, event.packet.text.as_ref()) {
+    return;
+  }
+  let text = match filter_text(game, &event.packet.text) {
+    Some(text) => text,
+    None => return,
+  };
+
   let (&pos, &plane, &special, &team, _) = match game.world.query_one_mut::<(
     &Position,
     &PlanePrototypeRef,
@@ -77,7 +168,7 @@ fn on_say(event: &PacketEvent<Say>, game: &mut AirmashGame) {
 
   let packet = s::ChatSay {
     id: event.entity.id() as _,
-    text: event.packet.text.clone(),
+    text,
   };
 
   if plane.special.is_stealth() && special.0 {
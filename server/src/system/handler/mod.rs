@@ -4,7 +4,6 @@
 //! so the actual source of this module is just a list of other modules.
 
 mod chat;
-mod on_command;
 mod on_event_boost;
 mod on_event_bounce;
 mod on_event_horizon;
@@ -25,6 +24,8 @@ mod on_player_mob_collision;
 mod on_player_powerup;
 mod on_player_repel;
 mod on_player_respawn;
+mod on_player_resume;
 mod on_player_score_update;
 mod on_player_spawn;
 mod on_player_spectate;
+mod on_vote_mute;
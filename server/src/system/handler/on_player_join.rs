@@ -1,53 +1,15 @@
-use airmash_protocol::{FlagCode, GameType, PlayerStatus};
+use airmash_protocol::{FlagCode, PlayerStatus};
 use bstr::BString;
 
 use crate::component::*;
 use crate::config::PlanePrototypeRef;
 use crate::event::PlayerJoin;
-use crate::resource::{RegionName, ServerStats};
+use crate::resource::{Config, ServerStats};
+use crate::snapshot::RestoredPlayer;
 use crate::world::AirmashGame;
 
 #[handler(priority = crate::priority::LOGIN)]
 fn send_login_packet(event: &PlayerJoin, game: &mut AirmashGame) {
-  use crate::protocol::server::{Login, LoginPlayer};
-
-  let mut query = game
-    .world
-    .query::<(
-      &IsAlive,
-      &Level,
-      &Name,
-      &PlanePrototypeRef,
-      &Team,
-      &Position,
-      &Rotation,
-      &FlagCode,
-      &Upgrades,
-      &Effects,
-    )>()
-    .with::<IsPlayer>();
-  let players = query
-    .into_iter()
-    .map(
-      |(ent, (alive, level, name, plane, team, pos, rot, flag, upgrades, effects))| LoginPlayer {
-        id: ent.id() as u16,
-        status: PlayerStatus::from(*alive),
-        level: level.0,
-        name: name.0.clone(),
-        ty: plane.server_type,
-        team: team.0,
-        pos: pos.into(),
-        rot: rot.0,
-        flag: *flag,
-        upgrades: crate::util::get_server_upgrades(upgrades, effects),
-      },
-    )
-    .collect::<Vec<_>>();
-
-  let clock = crate::util::get_current_clock(game);
-  let game_ty = *game.resources.read::<GameType>();
-  let room = game.resources.read::<RegionName>().clone();
-
   let mut query = match game.world.query_one::<(&Team, &Session)>(event.player) {
     Ok(query) => query.with::<IsPlayer>(),
     Err(_) => return,
@@ -56,16 +18,12 @@ fn send_login_packet(event: &PlayerJoin, game: &mut AirmashGame) {
   debug!("Sending login packet to player id {:?}", event.player);
 
   if let Some((team, session)) = query.get() {
-    let packet = Login {
-      success: true,
-      id: event.player.id() as u16,
-      team: team.0,
-      clock,
-      token: BString::from(format!("{}", session.0)),
-      ty: game_ty,
-      room: room.0.into(),
-      players,
-    };
+    let packet = crate::util::get_login_packet(
+      game,
+      event.player.id() as u16,
+      team.0,
+      BString::from(format!("{}", session.0)),
+    );
 
     game.send_to(event.player, packet);
   } else {
@@ -73,6 +31,70 @@ fn send_login_packet(event: &PlayerJoin, game: &mut AirmashGame) {
   }
 }
 
+#[handler(priority = crate::priority::RESTORE)]
+fn restore_player_state(event: &PlayerJoin, game: &mut AirmashGame) {
+  let player = match game.world.remove_one::<RestoredPlayer>(event.player) {
+    Ok(RestoredPlayer(player)) => player,
+    Err(_) => return,
+  };
+
+  debug!("Restoring saved state of player {:?}", event.player);
+
+  let plane = game
+    .resources
+    .read::<Config>()
+    .planes
+    .get(&*player.plane)
+    .copied();
+  if let Some(plane) = plane {
+    let _ = game.world.insert(
+      event.player,
+      (
+        plane,
+        EnergyRegen(plane.energy_regen),
+        HealthRegen(plane.health_regen),
+      ),
+    );
+  }
+
+  let [speed, defense, energy, missile] = player.upgrades;
+  let _ = game.world.insert(
+    event.player,
+    (
+      Session(player.session),
+      player.flag,
+      Team(player.team),
+      Level(player.level),
+      Score(player.score),
+      Earnings(player.earnings),
+      KillCount(player.kills),
+      DeathCount(player.deaths),
+      Captures(player.captures),
+      TotalDamage(player.damage),
+      Upgrades {
+        speed,
+        defense,
+        energy,
+        missile,
+        unused: 0,
+      },
+      Position(player.pos),
+    ),
+  );
+  let _ = game.world.insert(
+    event.player,
+    (
+      Velocity(player.vel),
+      Rotation(player.rot),
+      Energy(player.energy),
+      Health(player.health),
+      IsAlive(player.alive),
+      IsSpectating(player.spectating),
+      IsMuted(player.muted),
+    ),
+  );
+}
+
 #[handler]
 fn send_level_packet(event: &PlayerJoin, game: &mut AirmashGame) {
   use crate::protocol::server::PlayerLevel;
@@ -157,12 +179,9 @@ fn send_score_update(event: &PlayerJoin, game: &mut AirmashGame) {
 
 #[handler]
 fn update_server_stats(_: &PlayerJoin, game: &mut AirmashGame) {
-  use crate::network::NUM_PLAYERS;
-
   let mut stats = game.resources.write::<ServerStats>();
 
   stats.num_players += 1;
-  NUM_PLAYERS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 }
 
 #[handler(priority = crate::priority::CLEANUP)]
@@ -1,8 +1,10 @@
 use std::time::Duration;
 
+use rand::Rng;
+
 use crate::component::*;
-use crate::event::{PlayerKilled, PlayerRespawn};
-use crate::resource::{Config, GameConfig, TaskScheduler, ThisFrame};
+use crate::event::PlayerKilled;
+use crate::resource::{Config, GameConfig, GameRng, Metrics, TaskScheduler, ThisFrame};
 use crate::util::NalgebraExt;
 use crate::{consts, AirmashGame, Vector2};
 
@@ -42,10 +44,7 @@ fn launch_respawn_task(event: &PlayerKilled, game: &mut AirmashGame) {
       can_respawn.0 = true;
 
       if !spectating.0 {
-        game.dispatch(PlayerRespawn {
-          player: event.player,
-          alive: false,
-        });
+        game.respawn_player(event.player);
       }
     },
   );
@@ -158,7 +157,7 @@ fn update_upgrades(event: &PlayerKilled, game: &mut AirmashGame) {
     Err(_) => return,
   };
 
-  let offsets = rand::random::<u8>() & 0xF;
+  let offsets = game.resources.write::<GameRng>().gen::<u8>() & 0xF;
 
   upgrades.speed += offsets & 1;
   upgrades.defense += (offsets >> 1) & 1;
@@ -201,7 +200,7 @@ fn drop_upgrade(event: &PlayerKilled, game: &mut AirmashGame) {
     // If there is no upgrade prototype then we don't drop upgrades
     None => return,
   };
-  let prob = rand::random::<f32>();
+  let prob = game.resources.write::<GameRng>().gen::<f32>();
 
   drop(config);
   drop(game_config);
@@ -210,3 +209,8 @@ fn drop_upgrade(event: &PlayerKilled, game: &mut AirmashGame) {
     game.spawn_mob(mob.server_type, pos.0, mob.lifetime);
   }
 }
+
+#[handler]
+fn count_kill(_: &PlayerKilled, game: &mut AirmashGame) {
+  game.resources.write::<Metrics>().kills += 1;
+}
@@ -58,10 +58,7 @@ fn retarget_spectators(event: &PlayerLeave, game: &mut AirmashGame) {
 
 #[handler]
 fn update_server_stats(_: &PlayerLeave, game: &mut AirmashGame) {
-  use crate::network::NUM_PLAYERS;
-
   let mut stats = game.resources.write::<ServerStats>();
 
   stats.num_players -= 1;
-  NUM_PLAYERS.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
 }
@@ -4,7 +4,7 @@ use smallvec::SmallVec;
 
 use crate::component::*;
 use crate::config::{MissilePrototypeRef, PlanePrototypeRef};
-use crate::event::{PlayerHit, PlayerKilled, PlayerMissileCollision};
+use crate::event::{BeforePlayerHit, PlayerHit, PlayerKilled, PlayerMissileCollision};
 use crate::resource::GameConfig;
 use crate::AirmashGame;
 
@@ -18,67 +18,79 @@ fn damage_player(event: &PlayerMissileCollision, game: &mut AirmashGame) {
     Err(_) => return,
   };
 
-  let game_config = game.resources.read::<GameConfig>();
+  let allow_damage = game.resources.read::<GameConfig>().allow_damage;
   let attacker = game.world.get::<IsPlayer>(owner.0).ok().map(|_| owner.0);
 
   let mut events = SmallVec::<[_; 16]>::new();
   let mut hits = SmallVec::<[_; 16]>::new();
   let mut killed = HashSet::new();
   for player in event.players.iter().copied() {
-    let query = game.world.query_one::<(
-      &mut Health,
-      &PlanePrototypeRef,
-      &Effects,
-      &Upgrades,
-      &mut IsAlive,
-    )>(player);
-    let mut query = match query {
-      Ok(query) => query.with::<IsPlayer>(),
+    let query = game
+      .world
+      .query_one_mut::<(&PlanePrototypeRef, &Effects, &Upgrades, &IsAlive, &IsPlayer)>(player);
+    let (&plane, effects, upgrades, alive, _) = match query {
+      Ok(query) => query,
       Err(_) => continue,
     };
 
-    if let Some((health, &plane, effects, upgrades, alive)) = query.get() {
-      // No damage can be done if the player is dead
-      if !alive.0 {
-        continue;
+    // No damage can be done if the player is dead
+    if !alive.0 {
+      continue;
+    }
+
+    let damage = match allow_damage {
+      true => {
+        mob.damage * plane.damage_factor
+          / crate::consts::UPGRADE_MULTIPLIERS[upgrades.defense as usize]
+          * effects.damage_mult()
       }
+      false => 0.0,
+    };
 
-      let damage = match game_config.allow_damage {
-        true => {
-          mob.damage * plane.damage_factor
-            / crate::consts::UPGRADE_MULTIPLIERS[upgrades.defense as usize]
-            * effects.damage_mult()
-        }
-        false => 0.0,
-      };
-      health.0 -= damage;
+    let before = game.dispatch_pre(BeforePlayerHit {
+      player,
+      missile: event.missile,
+      attacker,
+      damage,
+      cancelled: false,
+    });
+    if before.cancelled {
+      continue;
+    }
 
-      hits.push(PlayerHit {
-        player,
-        missile: event.missile,
-        damage,
-        attacker,
-      });
+    let (health, alive) = match game.world.query_one_mut::<(&mut Health, &IsAlive)>(player) {
+      Ok(query) => query,
+      Err(_) => continue,
+    };
+
+    // A pre-event handler may have killed the player.
+    if !alive.0 {
+      continue;
+    }
 
-      if health.0 <= 0.0 {
-        // Avoid double-kills if multiple missiles hit the player in the same frame.
-        if !killed.insert(player) {
-          continue;
-        }
+    health.0 -= before.damage;
 
-        let owner = game.world.get::<IsPlayer>(owner.0).ok().map(|_| owner.0);
+    hits.push(PlayerHit {
+      player,
+      missile: event.missile,
+      damage: before.damage,
+      attacker,
+    });
 
-        events.push(PlayerKilled {
-          missile: event.missile,
-          player,
-          killer: owner,
-        });
+    if health.0 <= 0.0 {
+      // Avoid double-kills if multiple missiles hit the player in the same frame.
+      if !killed.insert(player) {
+        continue;
       }
+
+      events.push(PlayerKilled {
+        missile: event.missile,
+        player,
+        killer: attacker,
+      });
     }
   }
 
-  drop(game_config);
-
   game.dispatch_many(hits);
   game.dispatch_many(events);
 }
@@ -1,22 +1,12 @@
 use crate::component::*;
 use crate::config::MobPrototypeRef;
-use crate::event::{MobDespawn, MobDespawnType, PlayerMobCollision, PlayerPowerup, PowerupExpire};
+use crate::event::{
+  BeforePowerup, MobDespawn, MobDespawnType, PlayerMobCollision, PlayerPowerup, PowerupExpire,
+};
 use crate::AirmashGame;
 
-#[handler]
-fn dispatch_despawn_event(event: &PlayerMobCollision, game: &mut AirmashGame) {
-  if !game.world.contains(event.mob) {
-    return;
-  }
-
-  game.dispatch(MobDespawn {
-    ty: MobDespawnType::PickUp,
-    mob: event.mob,
-  });
-}
-
 #[handler(priority = crate::priority::HIGH)]
-fn update_player_powerup(event: &PlayerMobCollision, game: &mut AirmashGame) {
+fn pick_up_powerup(event: &PlayerMobCollision, game: &mut AirmashGame) {
   let (&mob, _) = match game
     .world
     .query_one_mut::<(&MobPrototypeRef, &IsMob)>(event.mob)
@@ -25,15 +15,27 @@ fn update_player_powerup(event: &PlayerMobCollision, game: &mut AirmashGame) {
     Err(_) => return,
   };
 
-  let (effects, _) = match game
-    .world
-    .query_one_mut::<(&Effects, &IsPlayer)>(event.player)
-  {
-    Ok(query) => query,
+  if game.world.get::<IsPlayer>(event.player).is_err() {
+    return;
+  }
+
+  // If the pickup is cancelled then the mob is left where it is so that it can
+  // be picked up later.
+  let before = game.dispatch_pre(BeforePowerup {
+    player: event.player,
+    powerup: mob.powerup,
+    cancelled: false,
+  });
+  if before.cancelled {
+    return;
+  }
+
+  let has_powerup = match game.world.get::<Effects>(event.player) {
+    Ok(effects) => effects.powerup().is_some(),
     Err(_) => return,
   };
 
-  if effects.powerup().is_some() {
+  if has_powerup {
     game.dispatch(PowerupExpire {
       player: event.player,
     });
@@ -41,6 +43,12 @@ fn update_player_powerup(event: &PlayerMobCollision, game: &mut AirmashGame) {
 
   game.dispatch(PlayerPowerup {
     player: event.player,
-    powerup: mob.powerup,
+    powerup: before.powerup,
+  });
+
+  game.dispatch(MobDespawn {
+    ty: MobDespawnType::PickUp,
+    mob: event.mob,
   });
+  game.despawn(event.mob);
 }
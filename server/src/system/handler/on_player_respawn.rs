@@ -87,6 +87,20 @@ fn reset_player(event: &PlayerRespawn, game: &mut AirmashGame) {
   spectgt.0 = None;
 }
 
+// Run after the game mode has picked a spawn position but before the respawn
+// packet is sent.
+#[handler(priority = crate::priority::DEFAULT + 1)]
+fn apply_requested_position(event: &PlayerRespawn, game: &mut AirmashGame) {
+  let pos = match event.pos {
+    Some(pos) => pos,
+    None => return,
+  };
+
+  if let Ok(mut position) = game.world.get_mut::<Position>(event.player) {
+    position.0 = pos;
+  }
+}
+
 #[handler]
 fn dispatch_player_spawn(event: &PlayerRespawn, game: &mut AirmashGame) {
   game.dispatch(PlayerSpawn {
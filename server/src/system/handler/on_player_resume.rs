@@ -0,0 +1,50 @@
+use bstr::BString;
+
+use crate::component::*;
+use crate::event::PlayerResume;
+use crate::world::AirmashGame;
+
+#[handler(priority = crate::priority::LOGIN)]
+fn resend_login_packet(event: &PlayerResume, game: &mut AirmashGame) {
+  let mut query = match game.world.query_one::<(&Team, &Session)>(event.player) {
+    Ok(query) => query.with::<IsPlayer>(),
+    Err(_) => return,
+  };
+
+  debug!("Resending login packet to player id {:?}", event.player);
+
+  if let Some((team, session)) = query.get() {
+    let packet = crate::util::get_login_packet(
+      game,
+      event.player.id() as u16,
+      team.0,
+      BString::from(format!("{}", session.0)),
+    );
+
+    game.send_to(event.player, packet);
+  }
+}
+
+#[handler]
+fn resend_score_update(event: &PlayerResume, game: &mut AirmashGame) {
+  use crate::protocol::server::ScoreUpdate;
+
+  let (score, earnings, deaths, kills, upgrades) =
+    match game
+      .world
+      .query_one_mut::<(&Score, &Earnings, &DeathCount, &KillCount, &Upgrades)>(event.player)
+    {
+      Ok(query) => query,
+      Err(_) => return,
+    };
+
+  let packet = ScoreUpdate {
+    id: event.player.id() as _,
+    score: score.0,
+    earnings: earnings.0,
+    total_deaths: deaths.0,
+    total_kills: kills.0,
+    upgrades: upgrades.unused,
+  };
+  game.send_to(event.player, packet);
+}
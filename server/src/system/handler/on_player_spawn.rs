@@ -1,5 +1,5 @@
 use crate::component::*;
-use crate::event::{PlayerPowerup, PlayerSpawn};
+use crate::event::{BeforePowerup, PlayerPowerup, PlayerSpawn};
 use crate::resource::{Config, GameConfig};
 use crate::AirmashGame;
 
@@ -31,10 +31,20 @@ fn give_spawn_shield(event: &PlayerSpawn, game: &mut AirmashGame) {
     .get("spawn-shield")
     .copied();
 
-  if let Some(proto) = proto {
+  let proto = match proto {
+    Some(proto) => proto,
+    None => return,
+  };
+
+  let before = game.dispatch_pre(BeforePowerup {
+    player: event.player,
+    powerup: proto,
+    cancelled: false,
+  });
+  if !before.cancelled {
     game.dispatch(PlayerPowerup {
       player: event.player,
-      powerup: proto,
+      powerup: before.powerup,
     });
   }
 }
@@ -0,0 +1,76 @@
+use crate::component::*;
+use crate::event::{PacketEvent, PlayerLeave};
+use crate::protocol::client::VoteMute;
+use crate::protocol::server as s;
+use crate::resource::{MuteVotes, ServerStats};
+use crate::AirmashGame;
+
+/// The number of votes required to mute a player given the number of players
+/// currently in the game.
+///
+/// See `notes/votemutes.md` for where this formula comes from.
+fn required_votes(player_count: u32) -> usize {
+  (player_count as f64).sqrt().floor() as usize + 1
+}
+
+#[handler]
+fn on_vote_mute(event: &PacketEvent<VoteMute>, game: &mut AirmashGame) {
+  if game.world.get::<IsPlayer>(event.entity).is_err() {
+    return;
+  }
+
+  let target = match game.find_entity_by_id(event.packet.id) {
+    Some(target) => target,
+    None => return,
+  };
+
+  if target == event.entity {
+    return;
+  }
+
+  let muted = match game
+    .world
+    .query_one_mut::<(&mut IsMuted, &IsPlayer)>(target)
+  {
+    Ok((muted, _)) => muted,
+    Err(_) => return,
+  };
+
+  if muted.0 {
+    return;
+  }
+
+  let num_players = game.resources.read::<ServerStats>().num_players;
+  let mut votes = game.resources.write::<MuteVotes>();
+  let voters = votes.entry(target).or_default();
+  voters.insert(event.entity);
+
+  if voters.len() < required_votes(num_players) {
+    return;
+  }
+
+  muted.0 = true;
+  let voters = votes.remove(&target).unwrap_or_default();
+  drop(votes);
+
+  debug!("Player {:?} has been vote-muted", target);
+
+  game.send_to_entities(
+    voters,
+    s::ChatVoteMutePassed {
+      id: target.id() as _,
+    },
+  );
+  game.send_to(target, s::ChatVoteMuted);
+}
+
+#[handler]
+fn remove_votes_on_leave(event: &PlayerLeave, game: &mut AirmashGame) {
+  let mut votes = game.resources.write::<MuteVotes>();
+
+  votes.remove(&event.player);
+  votes.retain(|_, voters| {
+    voters.remove(&event.player);
+    !voters.is_empty()
+  });
+}
@@ -41,13 +41,20 @@ fn fire_missiles(game: &mut AirmashGame) {
       count = count * 2 + 1;
     }
 
-    events.push((ent, count, plane.missile));
+    events.push((ent, count, *plane));
   }
 
   drop(query);
 
-  for (ent, missiles, ty) in events {
-    let _ = game.fire_missiles_count(ent, missiles, ty);
+  for (ent, missiles, plane) in events {
+    let fired = game.fire_missiles_count(ent, missiles, plane.missile);
+
+    // Refund the energy if the shot was cancelled.
+    if matches!(fired, Ok(fired) if fired.is_empty()) {
+      if let Ok(mut energy) = game.world.get_mut::<Energy>(ent) {
+        energy.0 += plane.fire_energy;
+      }
+    }
   }
 }
 
@@ -4,6 +4,9 @@
 //! it also exposes some optional systems that are not registered by default but
 //! may be useful for certain game modes.
 
+use std::time::Instant;
+
+use crate::resource::{Metrics, Profiler};
 use crate::AirmashGame;
 
 pub mod ctf;
@@ -11,6 +14,7 @@ pub mod ffa;
 
 mod admin;
 mod collision;
+mod commands;
 mod despawn;
 mod handler;
 mod keys;
@@ -24,6 +28,12 @@ mod specials;
 mod upgrades;
 mod visibility;
 
+/// Register all of the builtin commands.
+pub(crate) fn register_commands(registry: &mut crate::resource::CommandRegistry) {
+  self::admin::register_commands(registry);
+  self::commands::register_commands(registry);
+}
+
 /// Main airmash update loop.
 ///
 /// This is the main method that contains all the work done within a single
@@ -36,32 +46,63 @@ mod visibility;
 pub fn update(game: &mut AirmashGame) {
   use crate::event::{Frame, FrameEnd, FrameStart};
 
+  let start = Instant::now();
+
   game.dispatch(FrameStart);
 
-  self::physics::update(game);
-  self::regen::update(game);
-  self::specials::update(game);
+  stage(game, "physics", self::physics::update);
+  stage(game, "regen", self::regen::update);
+  stage(game, "specials", self::specials::update);
 
-  self::collision::generate_collision_lookups(game);
-  self::visibility::generate_horizon_events(game);
-  self::collision::check_collisions(game);
+  stage(game, "collision", |game| {
+    self::collision::generate_collision_lookups(game);
+    self::visibility::generate_horizon_events(game);
+    self::collision::check_collisions(game);
+  });
 
   // Note: most events will happen here
-  self::network::process_packets(game);
+  stage(game, "network", |game| {
+    self::network::process_packets(game);
+    self::network::expire_disconnected(game);
+    self::network::update_send_queues(game);
+    self::network::publish_status(game);
+  });
+
+  stage(game, "keys", self::keys::update);
+  stage(game, "despawn", self::despawn::update);
+  stage(game, "powerups", self::powerups::update);
+  stage(game, "scoreboard", self::scoreboard::update);
+  stage(game, "ping", self::ping::update);
+  stage(game, "upgrades", self::upgrades::update);
+
+  stage(game, "frame", |game| game.dispatch(Frame));
+
+  stage(game, "tasks", update_tasks);
+  cull_zombies(game);
 
-  self::keys::update(game);
-  self::despawn::update(game);
-  self::powerups::update(game);
-  self::scoreboard::update(game);
-  self::ping::update(game);
-  self::upgrades::update(game);
+  game.dispatch(FrameEnd);
 
-  game.dispatch(Frame);
+  let elapsed = start.elapsed();
+  if let Some(mut metrics) = game.resources.get_mut::<Metrics>() {
+    metrics.record_frame(elapsed);
+  }
+  if let Some(mut profiler) = game.resources.get_mut::<Profiler>() {
+    profiler.record_frame(elapsed);
+  }
+}
 
-  update_tasks(game);
-  cull_zombies(game);
+/// Run one stage of the update loop and record how long it took.
+fn stage(game: &mut AirmashGame, name: &'static str, func: impl FnOnce(&mut AirmashGame)) {
+  let start = Instant::now();
+  func(game);
 
-  game.dispatch(FrameEnd);
+  let elapsed = start.elapsed();
+  if let Some(mut metrics) = game.resources.get_mut::<Metrics>() {
+    metrics.record_stage(name, elapsed);
+  }
+  if let Some(mut profiler) = game.resources.get_mut::<Profiler>() {
+    profiler.record_stage(name, elapsed);
+  }
 }
 
 /// Reusing an id soon after it was created causes problems with the airmash web
@@ -1,17 +1,30 @@
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 
 use bstr::BString;
+use hecs::Entity;
+use rand::Rng;
+use smallvec::SmallVec;
+use uuid::Uuid;
 
-use crate::component::IsPlayer;
+use crate::component::{DisconnectDeadline, IsPlayer};
 use crate::event::*;
 use crate::network::*;
 use crate::protocol::client::{self as c, Login};
 use crate::protocol::v5::deserialize;
 use crate::protocol::ClientPacket;
-use crate::resource::{Config, TakenNames};
+use crate::resource::{
+  client_packet_name, BanList, BanTarget, Config, ConnectionLimits, GameConfig, GameRng, Metrics,
+  ServerStats, TakenNames,
+};
 use crate::AirmashGame;
 
 pub fn process_packets(game: &mut AirmashGame) {
+  let this_frame = game.this_frame();
+  game
+    .resources
+    .write::<ConnectionMgr>()
+    .begin_frame(this_frame);
+
   loop {
     let mut conn_mgr = game.resources.write::<ConnectionMgr>();
     let (conn, evt) = match conn_mgr.next_packet() {
@@ -54,13 +67,52 @@ pub fn process_packets(game: &mut AirmashGame) {
           continue;
         }
 
-        game.dispatch(PlayerLeave { player: entity });
-        game.despawn(entity);
+        let mut conn_mgr = game.resources.write::<ConnectionMgr>();
+        let evicted = conn_mgr.take_evicted(entity);
+        let backup = conn_mgr.backup(entity);
+        drop(conn_mgr);
+
+        let window = game.resources.read::<GameConfig>().resume_window;
+        if evicted || window.is_zero() {
+          remove_player(game, entity);
+          continue;
+        }
+
+        // If the client already has a backup connection open then it can take
+        // over right away.
+        match backup {
+          Some(backup) => resume_player(game, entity, backup),
+          None => disconnect_player(game, entity, this_frame + window),
+        }
 
         continue;
       }
     };
 
+    // Rate limits are checked before deserializing so that a flood of packets
+    // doesn't cost us anything beyond looking at the first byte.
+    let check =
+      game
+        .resources
+        .write::<ConnectionMgr>()
+        .check_rate(conn, packet_category(&data), time);
+    match check {
+      RateCheck::Allowed => (),
+      RateCheck::Dropped => continue,
+      RateCheck::Flooding => {
+        warn!("Disconnecting connection {} for packet flooding", conn);
+
+        game.send_to_conn(
+          conn,
+          crate::protocol::server::Error {
+            error: crate::protocol::ErrorType::PacketFloodingDisconnect,
+          },
+        );
+        game.resources.write::<ConnectionMgr>().close(conn);
+        continue;
+      }
+    }
+
     let packet = match deserialize::<ClientPacket>(&data) {
       Ok(packet) => packet,
       Err(_) => {
@@ -69,6 +121,11 @@ pub fn process_packets(game: &mut AirmashGame) {
       }
     };
 
+    game
+      .resources
+      .write::<Metrics>()
+      .record_packet_in(client_packet_name(&packet));
+
     // Other packets are only valid once the connection has been initiated.
     if assoc.is_none() && !matches!(packet, ClientPacket::Login(_) | ClientPacket::Backup(_)) {
       continue;
@@ -76,7 +133,11 @@ pub fn process_packets(game: &mut AirmashGame) {
 
     match packet {
       ClientPacket::Login(login) => handle_login(game, login, conn),
-      ClientPacket::Backup(_) => continue,
+      ClientPacket::Backup(backup) => {
+        if assoc.is_none() {
+          handle_backup(game, backup, conn);
+        }
+      }
       ClientPacket::Horizon(packet) => game.dispatch(PacketEvent {
         entity: assoc.unwrap(),
         conn,
@@ -154,11 +215,266 @@ pub fn process_packets(game: &mut AirmashGame) {
   }
 }
 
-fn make_unique_name(names: &mut TakenNames, name: &mut BString) {
+/// Determine the category of a raw packet from its opcode.
+fn packet_category(data: &[u8]) -> PacketCategory {
+  match data.first() {
+    // Login, Backup
+    Some(0 | 1) => PacketCategory::Login,
+    // Key
+    Some(10) => PacketCategory::Key,
+    // Command, ScoreDetailed
+    Some(11 | 12) => PacketCategory::Command,
+    // Chat, Whisper, Say, TeamChat, VoteMute
+    Some(20 | 21 | 22 | 23 | 25) => PacketCategory::Chat,
+    _ => PacketCategory::Other,
+  }
+}
+
+/// Remove players that have been disconnected for longer than the resume
+/// window without reconnecting.
+pub fn expire_disconnected(game: &mut AirmashGame) {
+  let this_frame = game.this_frame();
+  let expired: SmallVec<[Entity; 8]> = game
+    .world
+    .query_mut::<&DisconnectDeadline>()
+    .with::<IsPlayer>()
+    .into_iter()
+    .filter(|(_, deadline)| deadline.0 <= this_frame)
+    .map(|(player, _)| player)
+    .collect();
+
+  for player in expired {
+    debug!("Player {:?} did not reconnect in time", player);
+    remove_player(game, player);
+  }
+}
+
+fn remove_player(game: &mut AirmashGame, player: Entity) {
+  game.dispatch(PlayerLeave { player });
+  game.despawn(player);
+}
+
+/// Keep a player whose connection dropped in the game until `deadline` so that
+/// they have a chance to resume their session from a new connection.
+fn disconnect_player(game: &mut AirmashGame, player: Entity, deadline: std::time::Instant) {
+  use crate::component::{IsAlive, KeyState};
+  use crate::protocol::KeyCode;
+
+  debug!(
+    "Player {:?} disconnected, waiting for them to resume",
+    player
+  );
+
+  let _ = game.world.insert_one(player, DisconnectDeadline(deadline));
+
+  // Release any keys that were held so that the plane doesn't keep flying (or
+  // firing) on its own while nobody is controlling it.
+  let pressed: SmallVec<[KeyCode; 6]> =
+    match game.world.query_one_mut::<(&KeyState, &IsAlive)>(player) {
+      Ok((keystate, alive)) if alive.0 => [
+        (KeyCode::Up, keystate.up),
+        (KeyCode::Down, keystate.down),
+        (KeyCode::Left, keystate.left),
+        (KeyCode::Right, keystate.right),
+        (KeyCode::Fire, keystate.fire),
+        (KeyCode::Special, keystate.special),
+      ]
+      .iter()
+      .filter(|(_, pressed)| *pressed)
+      .map(|&(key, _)| key)
+      .collect(),
+      _ => SmallVec::new(),
+    };
+
+  game.dispatch_many(pressed.into_iter().map(|key| KeyEvent {
+    player,
+    key,
+    state: false,
+  }));
+}
+
+/// Make `conn` the primary connection of a disconnected player.
+fn resume_player(game: &mut AirmashGame, player: Entity, conn: ConnectionId) {
+  info!("Player {:?} resumed their session on {}", player, conn);
+
+  let _ = game.world.remove_one::<DisconnectDeadline>(player);
+  game
+    .resources
+    .write::<ConnectionMgr>()
+    .mark_primary(player, conn);
+
+  game.dispatch(PlayerResume { player });
+}
+
+/// A `Backup` packet attaches a new connection to an existing player using the
+/// session token that was sent to them in their login packet. If the player is
+/// currently disconnected then the new connection takes over as their primary
+/// connection.
+fn handle_backup(game: &mut AirmashGame, backup: c::Backup, conn: ConnectionId) {
+  use crate::component::{LoginSession, Session};
+  use crate::protocol::server as s;
+  use crate::protocol::ErrorType;
+
+  let addr = match game.resources.read::<ConnectionMgr>().socket_addr(conn) {
+    Some(addr) => addr,
+    None => return,
+  };
+
+  let token = backup.token.to_string();
+  let player = Uuid::parse_str(&token).ok().and_then(|token| {
+    game
+      .world
+      .query::<&Session>()
+      .with::<IsPlayer>()
+      .iter()
+      .find(|(_, session)| session.0 == token)
+      .map(|(player, _)| player)
+  });
+
+  let banned = {
+    let bans = game.resources.read::<BanList>();
+    let login_session = player.and_then(|player| game.world.get::<LoginSession>(player).ok());
+
+    bans.is_banned(&BanTarget::Ip(addr.ip()))
+      || login_session
+        .is_some_and(|session| bans.is_banned(&BanTarget::Session(session.0.to_string())))
+  };
+  if banned {
+    info!("Rejecting backup from banned connection {}", conn);
+
+    game.send_to_conn(
+      conn,
+      s::Error {
+        error: ErrorType::Banned,
+      },
+    );
+    game.resources.write::<ConnectionMgr>().close(conn);
+    return;
+  }
+
+  let player = match player {
+    Some(player) => player,
+    None => {
+      debug!("Connection {} sent a backup for an unknown session", conn);
+
+      game.send_to_conn(
+        conn,
+        s::Error {
+          error: ErrorType::InvalidLogin,
+        },
+      );
+      game.resources.write::<ConnectionMgr>().close(conn);
+      return;
+    }
+  };
+
+  game
+    .resources
+    .write::<ConnectionMgr>()
+    .associate(player, conn);
+
+  if game.world.get::<DisconnectDeadline>(player).is_ok() {
+    resume_player(game, player, conn);
+  } else {
+    game.send_to_conn(conn, s::Backup);
+  }
+}
+
+/// Disconnect clients that have fallen too far behind and record the state of
+/// the send queues in [`ServerStats`].
+pub fn update_send_queues(game: &mut AirmashGame) {
+  let this_frame = game.this_frame();
+  let queues = game
+    .resources
+    .write::<ConnectionMgr>()
+    .evict_backlogged(this_frame);
+
+  let mut stats = game.resources.write::<ServerStats>();
+  stats.queued_messages = queues.queued;
+  stats.max_queue_depth = queues.max_depth;
+  stats.dropped_messages = queues.dropped;
+}
+
+/// Publish the current server status and metrics for the HTTP status
+/// endpoints. This is done at most once a second.
+pub fn publish_status(game: &mut AirmashGame) {
+  use std::collections::BTreeMap;
+  use std::time::Duration;
+
+  use crate::component::{Name, PlayerPing, Score, Team};
+  use crate::protocol::GameType;
+  use crate::resource::{RegionName, TeamScores};
+
+  let shared = game.resources.read::<SharedStatus>().clone();
+  if let Some(published) = shared.last_published() {
+    if published.elapsed() < Duration::from_secs(1) {
+      return;
+    }
+  }
+
+  let mut teams = BTreeMap::new();
+  let mut players = Vec::new();
+  let query = game
+    .world
+    .query_mut::<(&Name, &Team, &Score, &PlayerPing)>()
+    .with::<IsPlayer>();
+  for (ent, (name, team, score, ping)) in query {
+    *teams.entry(team.0).or_insert(0) += 1;
+    players.push(PlayerStatus {
+      id: ent.id() as _,
+      name: name.0.to_string(),
+      team: team.0,
+      score: score.0,
+      ping: ping.0.as_millis() as _,
+    });
+  }
+
+  let game_type = match *game.resources.read::<GameType>() {
+    GameType::FFA => "ffa".to_owned(),
+    GameType::CTF => "ctf".to_owned(),
+    GameType::BTR => "btr".to_owned(),
+    other => u8::from(other).to_string(),
+  };
+
+  let status = ServerStatus {
+    region: game.resources.read::<RegionName>().0.clone(),
+    game_type,
+    uptime: game
+      .this_frame()
+      .saturating_duration_since(game.start_time())
+      .as_secs(),
+    players: game.resources.read::<ServerStats>().num_players,
+    teams,
+    scores: game
+      .resources
+      .get::<TeamScores>()
+      .map(|scores| scores.0.clone())
+      .unwrap_or_default(),
+  };
+
+  let metrics = {
+    use crate::component::IsMissile;
+
+    let mut metrics = game.resources.write::<Metrics>();
+    metrics.players = status.players as u64;
+    metrics.missiles = game.world.query::<&IsMissile>().iter().count() as u64;
+    metrics.bytes_sent = game
+      .resources
+      .read::<ConnectionMgr>()
+      .bytes_sent()
+      .map(|(conn, bytes)| (conn.id(), bytes))
+      .collect();
+    metrics.render()
+  };
+
+  shared.publish(status, players, metrics);
+}
+
+fn make_unique_name(names: &mut TakenNames, rng: &mut GameRng, name: &mut BString) {
   'outer: while names.contains(name) {
     let mut ext = 0;
     for _ in 0..100 {
-      ext = rand::random::<u32>() % 1000;
+      ext = rng.gen::<u32>() % 1000;
 
       name.append(&mut format!("#{:03}", ext).into_bytes());
       if !names.contains(name) {
@@ -175,47 +491,163 @@ fn make_unique_name(names: &mut TakenNames, name: &mut BString) {
   names.insert(name.clone());
 }
 
+fn is_banned(game: &AirmashGame, login: &Login, addr: SocketAddr) -> bool {
+  let bans = game.resources.read::<BanList>();
+
+  bans.is_banned(&BanTarget::Ip(addr.ip()))
+    || bans.is_banned(&BanTarget::Session(login.session.to_string()))
+}
+
+/// Check the login against the configured [`Limits`], returning the reason it
+/// was rejected if it exceeds them.
+///
+/// [`Limits`]: crate::resource::Limits
+fn is_full(game: &AirmashGame) -> bool {
+  match game
+    .resources
+    .read::<ConnectionLimits>()
+    .limits()
+    .max_players
+  {
+    Some(max) => {
+      let players = game.world.query::<()>().with::<IsPlayer>().iter().count();
+      players >= max as usize
+    }
+    None => false,
+  }
+}
+
 fn handle_login(game: &mut AirmashGame, mut login: Login, conn: ConnectionId) {
   use crate::component::*;
   use crate::protocol::server as s;
-  use crate::resource::{EntityMapping, StartTime, ThisFrame};
+  use crate::resource::{EntityMapping, StartTime, ThisFrame, WordFilter};
+  use crate::snapshot::RestoredPlayer;
 
   debug!("Handling login on {}", conn);
 
-  let entity = {
-    let mut conn_mgr = game.resources.write::<ConnectionMgr>();
-    let mut names = game.resources.write::<TakenNames>();
-    let mut mapping = game.resources.write::<EntityMapping>();
-    let start_time = game.resources.read::<StartTime>().0;
-    let this_frame = game.resources.read::<ThisFrame>().0;
+  let addr = match game.resources.read::<ConnectionMgr>().socket_addr(conn) {
+    Some(addr) => addr,
+    // The connection was closed by the server before the login was processed.
+    None => return,
+  };
 
-    if login.protocol != 5 {
-      game.send_to_conn(
-        conn,
-        s::Error {
-          error: airmash_protocol::ErrorType::IncorrectProtocol,
-        },
-      );
-      return;
-    }
+  if is_banned(game, &login, addr) {
+    info!("Rejecting login from banned connection {}", conn);
+
+    game.send_to_conn(
+      conn,
+      s::Error {
+        error: airmash_protocol::ErrorType::Banned,
+      },
+    );
+    game.resources.write::<ConnectionMgr>().close(conn);
+    return;
+  }
+
+  if is_full(game) {
+    info!("Rejecting login on connection {}: the server is full", conn);
+
+    game.send_to_conn(
+      conn,
+      s::Error {
+        error: airmash_protocol::ErrorType::InvalidLogin,
+      },
+    );
+    game.resources.write::<ConnectionMgr>().close(conn);
+    return;
+  }
+
+  login.name = match game.resources.read::<WordFilter>().filter(&login.name) {
+    Some(name) => name.into_owned().into(),
+    None => {
+      info!("Rejecting login on connection {}: name was filtered", conn);
 
-    if login.name.len() > 40 {
       game.send_to_conn(
         conn,
         s::Error {
           error: airmash_protocol::ErrorType::InvalidLogin,
         },
       );
+      game.resources.write::<ConnectionMgr>().close(conn);
       return;
     }
+  };
+
+  if login.protocol != 5 {
+    game.send_to_conn(
+      conn,
+      s::Error {
+        error: airmash_protocol::ErrorType::IncorrectProtocol,
+      },
+    );
+    return;
+  }
+
+  if login.name.len() > 40 {
+    game.send_to_conn(
+      conn,
+      s::Error {
+        error: airmash_protocol::ErrorType::InvalidLogin,
+      },
+    );
+    return;
+  }
+
+  // Only logins that are otherwise valid count towards the per-address limit.
+  let this_frame = game.this_frame();
+  if !game
+    .resources
+    .read::<ConnectionLimits>()
+    .login(addr.ip(), this_frame)
+  {
+    info!(
+      "Rejecting login on connection {}: too many logins from this address",
+      conn
+    );
+
+    game.send_to_conn(
+      conn,
+      s::Error {
+        error: airmash_protocol::ErrorType::InvalidLogin,
+      },
+    );
+    game.resources.write::<ConnectionMgr>().close(conn);
+    return;
+  }
 
-    make_unique_name(&mut names, &mut login.name);
+  // Players from a restored snapshot get back their original name and ID. To
+  // be recognized the client has to send the token from the server's `Login`
+  // packet (i.e. its `Session`) back as the session of its new login.
+  let restored = game.take_restored_player(&login.session.to_string());
+  if let Some((_, player)) = &restored {
+    login.name = player.name.as_str().into();
+  }
+
+  let entity = {
+    let mut conn_mgr = game.resources.write::<ConnectionMgr>();
+    let mut names = game.resources.write::<TakenNames>();
+    let mut mapping = game.resources.write::<EntityMapping>();
+    let start_time = game.resources.read::<StartTime>().0;
+    let this_frame = game.resources.read::<ThisFrame>().0;
+
+    if restored.is_none() {
+      make_unique_name(&mut names, &mut game.resources.write(), &mut login.name);
+    }
 
     let config = game.resources.read::<Config>();
     let mut builder =
       crate::defaults::build_default_player(&login, config.default_plane, start_time, this_frame);
 
-    let entity = game.world.spawn(builder.build());
+    let entity = match restored {
+      Some((entity, player)) => {
+        let _ = game.world.despawn(entity);
+
+        builder.add(RestoredPlayer(Box::new(player)));
+        game.world.spawn_at(entity, builder.build());
+        entity
+      }
+      None => game.world.spawn(builder.build()),
+    };
 
     if entity.id() > u16::MAX as _ {
       game.send_to_conn(
@@ -3,10 +3,11 @@ use std::convert::TryInto;
 use std::time::{Duration, Instant};
 
 use airmash_protocol::client::Pong;
+use rand::Rng;
 
 use crate::component::*;
 use crate::event::PacketEvent;
-use crate::resource::ServerStats;
+use crate::resource::{GameRng, ServerStats};
 use crate::AirmashGame;
 
 struct PingData {
@@ -50,21 +51,21 @@ fn send_ping_packets(game: &mut AirmashGame) {
 
   let this_frame = game.this_frame();
   let clock = crate::util::get_time_clock(game, Instant::now());
-  let data = game
+  let last_ping = game
     .resources
     .entry::<PingData>()
-    .or_insert_with(PingData::new);
+    .or_insert_with(PingData::new)
+    .last_ping();
 
-  if data
-    .last_ping()
+  if last_ping
     .map(|p| this_frame.saturating_duration_since(p) < Duration::from_secs(5))
     .unwrap_or(false)
   {
     return;
   }
 
-  let seq = rand::random();
-  data.push_seq(seq, this_frame);
+  let seq = game.resources.write::<GameRng>().gen();
+  game.resources.write::<PingData>().push_seq(seq, this_frame);
 
   game.send_to_all(Ping { clock, num: seq });
 }
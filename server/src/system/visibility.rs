@@ -3,7 +3,7 @@ use std::collections::HashSet;
 use hecs::Entity;
 
 use crate::component::*;
-use crate::event::{EntitySpawn, EventHorizon, MobSpawn, PlayerFire};
+use crate::event::{EntitySpawn, EventHorizon, MobSpawn, PlayerFire, PlayerResume};
 use crate::resource::collision::LayerSpec;
 use crate::resource::{collision as c, Config};
 use crate::util::NalgebraExt;
@@ -68,6 +68,9 @@ pub fn generate_horizon_events(game: &mut AirmashGame) {
     std::mem::swap(&mut visible.0, &mut new_vis);
     let old_vis = &new_vis;
 
+    // Iteration order of a HashSet varies between runs so the events are
+    // sorted to keep replays deterministic.
+    let start = actions.len();
     for lost in old_vis.difference(&visible.0).copied() {
       actions.push(EventHorizon {
         player: ent,
@@ -75,7 +78,9 @@ pub fn generate_horizon_events(game: &mut AirmashGame) {
         in_horizon: false,
       });
     }
+    actions[start..].sort_unstable_by_key(|action| action.entity);
 
+    let start = actions.len();
     for found in visible.difference(old_vis).copied() {
       actions.push(EventHorizon {
         player: ent,
@@ -83,6 +88,7 @@ pub fn generate_horizon_events(game: &mut AirmashGame) {
         in_horizon: true,
       });
     }
+    actions[start..].sort_unstable_by_key(|action| action.entity);
   }
 
   drop(config);
@@ -104,6 +110,15 @@ fn record_entity_spawn_frame(event: &EntitySpawn, game: &mut AirmashGame) {
   );
 }
 
+/// A client that resumes its session on a new connection doesn't know about
+/// any of the entities around it so they all need to be sent again.
+#[handler]
+fn reset_visible_on_resume(event: &PlayerResume, game: &mut AirmashGame) {
+  if let Ok(mut visible) = game.world.get_mut::<VisibleEntities>(event.player) {
+    visible.clear();
+  }
+}
+
 /// New missiles need to be added to the respective visible entity set of
 /// players within range.
 ///
@@ -1,18 +1,19 @@
+use std::borrow::Cow;
 use std::cell::{Cell, RefCell, UnsafeCell};
 use std::collections::BinaryHeap;
 use std::future::Future;
 use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut};
 use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::{Receiver, Sender};
-use futures_task::{ArcWake, Context, Poll};
+use futures_task::{ArcWake, Context, Poll, Waker};
+use hecs::Entity;
 use slab::Slab;
 
-use crate::AirmashGame;
+use crate::{AirmashGame, Event};
 
 #[derive(Clone, Copy)]
 struct TaskContext {
@@ -24,6 +25,12 @@ thread_local! {
   static TASK_CONTEXT: Cell<Option<TaskContext>> = Cell::new(None);
 }
 
+/// Access to the game from within an async task.
+///
+/// The game can only be borrowed within the closure passed to [`with`] which
+/// makes it impossible to hold a reference to it across an await.
+///
+/// [`with`]: GameRef::with
 pub struct GameRef(PhantomData<*mut ()>);
 
 impl GameRef {
@@ -31,29 +38,252 @@ impl GameRef {
     Self(PhantomData)
   }
 
+  /// Run a function with mutable access to the game.
+  ///
+  /// # Panics
+  /// Panics if called outside of a task that is being run by the
+  /// [`TaskScheduler`] or from within another call to `with`.
+  pub fn with<F, R>(&mut self, func: F) -> R
+  where
+    F: FnOnce(&mut AirmashGame) -> R,
+  {
+    // The context is removed while the closure runs so that no other GameRef
+    // can get at the game until we're done with it.
+    let ctx = TASK_CONTEXT.with(|ctx| ctx.take()).expect(
+      "Attempted to access the game outside of a task or within another call to GameRef::with",
+    );
+    let _guard = DropGuard::new(move || TASK_CONTEXT.with(|c| c.set(Some(ctx))));
+
+    // SAFETY: The pointer is valid for as long as the scheduler is polling the
+    //         task and the borrow cannot escape the closure.
+    func(unsafe { &mut *ctx.game })
+  }
+
   pub async fn sleep_until(&mut self, until: Instant) {
-    TimeoutFuture::new(until, self).await
+    TimeoutFuture::new(until).await
   }
 
   pub async fn sleep_for(&mut self, time: Duration) {
-    let this_frame = self.this_frame();
+    let this_frame = self.with(|game| game.this_frame());
     self.sleep_until(this_frame + time).await
   }
+
+  /// Wait until an event `E` for which `filter` returns true is dispatched and
+  /// return a copy of it.
+  ///
+  /// The future resolves after all the handlers for the event have run. Only
+  /// events dispatched after the future is first polled are considered.
+  ///
+  /// # Example
+  /// ```
+  /// # use airmash::GameRef;
+  /// # use airmash::event::PlayerKilled;
+  /// # async fn example(game: GameRef, player: airmash::Entity) {
+  /// let event = game.wait_for(move |e: &PlayerKilled| e.player == player).await;
+  /// # }
+  /// ```
+  pub fn wait_for<E, F>(&self, filter: F) -> WaitFor<E>
+  where
+    E: Event + Clone,
+    F: FnMut(&E) -> bool + 'static,
+  {
+    WaitFor {
+      state: Rc::new(RefCell::new(WaitState {
+        filter: Some(Box::new(filter)),
+        event: None,
+        waker: None,
+      })),
+      registered: false,
+    }
+  }
+
+  /// Run `future` until it completes or `time` has passed, whichever happens
+  /// first. Returns `None` if the future timed out.
+  pub async fn timeout<Fut: Future>(&mut self, time: Duration, future: Fut) -> Option<Fut::Output> {
+    let this_frame = self.with(|game| game.this_frame());
+    self.timeout_at(this_frame + time, future).await
+  }
+
+  /// Run `future` until it completes or until the first frame after `until`,
+  /// whichever happens first. Returns `None` if the future timed out.
+  pub async fn timeout_at<Fut: Future>(
+    &mut self,
+    until: Instant,
+    future: Fut,
+  ) -> Option<Fut::Output> {
+    let mut future = Box::pin(future);
+    let mut timeout = TimeoutFuture::new(until);
+
+    std::future::poll_fn(move |cx| {
+      if let Poll::Ready(output) = future.as_mut().poll(cx) {
+        return Poll::Ready(Some(output));
+      }
+
+      Pin::new(&mut timeout).poll(cx).map(|()| None)
+    })
+    .await
+  }
+}
+
+type EventFilter<E> = Box<dyn FnMut(&E) -> bool>;
+
+struct WaitState<E> {
+  filter: Option<EventFilter<E>>,
+  event: Option<E>,
+  waker: Option<Waker>,
+}
+
+/// Future returned by [`GameRef::wait_for`].
+pub struct WaitFor<E> {
+  state: Rc<RefCell<WaitState<E>>>,
+  registered: bool,
+}
+
+impl<E: Event + Clone> Future for WaitFor<E> {
+  type Output = E;
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let mut state = self.state.borrow_mut();
+    if let Some(event) = state.event.take() {
+      return Poll::Ready(event);
+    }
+
+    state.waker = Some(cx.waker().clone());
+    drop(state);
+
+    if !self.registered {
+      self.registered = true;
+
+      // The dispatcher only keeps a weak reference so that the waiter gets
+      // cleaned up if this future is dropped before the event arrives.
+      let weak = Rc::downgrade(&self.state);
+      let dispatcher = GameRef::new().with(|game| game.dispatcher());
+      dispatcher.add_waiter(move |event: &E| {
+        let state = match weak.upgrade() {
+          Some(state) => state,
+          None => return true,
+        };
+        let mut state = state.borrow_mut();
+
+        let matched = match state.filter.as_mut() {
+          Some(filter) => filter(event),
+          None => return true,
+        };
+        if !matched {
+          return false;
+        }
+
+        state.filter = None;
+        state.event = Some(event.clone());
+        if let Some(waker) = state.waker.take() {
+          waker.wake();
+        }
+
+        true
+      });
+    }
+
+    Poll::Pending
+  }
+}
+
+struct TaskState {
+  name: Option<Cow<'static, str>>,
+  entity: Option<Entity>,
+  cancelled: Cell<bool>,
+  finished: Cell<bool>,
+}
+
+impl TaskState {
+  fn should_stop(&self, game: &AirmashGame) -> bool {
+    if self.cancelled.get() {
+      return true;
+    }
+
+    match self.entity {
+      Some(entity) => !game.world.contains(entity),
+      None => false,
+    }
+  }
+}
+
+/// Handle to a task spawned on the [`TaskScheduler`].
+///
+/// Dropping the handle does not stop the task.
+#[derive(Clone)]
+pub struct TaskHandle {
+  state: Rc<TaskState>,
 }
 
-impl Deref for GameRef {
-  type Target = AirmashGame;
+impl TaskHandle {
+  /// The name that the task was spawned with, if any.
+  pub fn name(&self) -> Option<&str> {
+    self.state.name.as_deref()
+  }
+
+  /// The entity that the task is tied to, if any.
+  pub fn entity(&self) -> Option<Entity> {
+    self.state.entity
+  }
+
+  /// Stop the task. It will not be polled again and will be dropped at the
+  /// start of the next frame.
+  pub fn cancel(&self) {
+    self.state.cancelled.set(true);
+  }
 
-  fn deref(&self) -> &Self::Target {
-    let game = TASK_CONTEXT.with(|ctx| ctx.get().map(|c| c.game));
-    unsafe { &*game.expect("Attempted to dereference a GameRef outside of a game context") }
+  /// Whether the task has completed or been stopped.
+  pub fn is_finished(&self) -> bool {
+    self.state.finished.get()
   }
 }
 
-impl DerefMut for GameRef {
-  fn deref_mut(&mut self) -> &mut Self::Target {
-    let game = TASK_CONTEXT.with(|ctx| ctx.get().map(|c| c.game));
-    unsafe { &mut *game.expect("Attempted to dereference a GameRef outside of a game context") }
+/// Builder for a task with extra options. Created by [`TaskScheduler::task`].
+pub struct TaskBuilder<'s> {
+  scheduler: &'s TaskScheduler,
+  name: Option<Cow<'static, str>>,
+  entity: Option<Entity>,
+}
+
+impl<'s> TaskBuilder<'s> {
+  /// Give the task a name so that it can be found later with
+  /// [`TaskScheduler::find`].
+  pub fn name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+    self.name = Some(name.into());
+    self
+  }
+
+  /// Tie the task to an entity. The task will be stopped once the entity is
+  /// despawned.
+  pub fn entity(mut self, entity: Entity) -> Self {
+    self.entity = Some(entity);
+    self
+  }
+
+  /// Spawn the task.
+  pub fn spawn<Fut, Fn>(self, func: Fn) -> TaskHandle
+  where
+    Fn: FnOnce(GameRef) -> Fut + 'static,
+    Fut: Future<Output = ()> + 'static,
+  {
+    let handle = TaskHandle {
+      state: Rc::new(TaskState {
+        name: self.name,
+        entity: self.entity,
+        cancelled: Cell::new(false),
+        finished: Cell::new(false),
+      }),
+    };
+
+    self.scheduler.live.borrow_mut().push(handle.clone());
+    let _ = self.scheduler.spawn.send(NewTask {
+      state: Rc::clone(&handle.state),
+      task: Box::new(async {
+        func(GameRef::new()).await;
+      }),
+    });
+
+    handle
   }
 }
 
@@ -65,7 +295,9 @@ impl DerefMut for GameRef {
 #[derive(Clone)]
 pub struct TaskScheduler {
   inner: Rc<RefCell<TaskSchedulerImpl>>,
-  spawn: Sender<Box<dyn Future<Output = ()>>>,
+  spawn: Sender<NewTask>,
+  /// Handles for all tasks which have not finished yet.
+  live: Rc<RefCell<Vec<TaskHandle>>>,
 }
 
 impl TaskScheduler {
@@ -75,46 +307,71 @@ impl TaskScheduler {
     Self {
       inner: Rc::new(RefCell::new(TaskSchedulerImpl::new(rx))),
       spawn: tx,
+      live: Rc::default(),
     }
   }
 
   /// Schedule an async function. By using the async methods on [`GameRef`] you
   /// can perform multiple waits across frames easily.
   ///
-  /// # Safety
-  /// By using this function you are guaranteeing that you will never keep a
-  /// mutable reference to [`AirmashGame`] (gotten by dereferencing [`GameRef`])
-  /// across an await.
+  /// Use [`task`] instead to spawn a task that is named or tied to an entity.
   ///
-  /// If you are only using the async methods on [`GameRef`] to suspend then
-  /// this is impossible, however external futures don't have that limitation.
-  pub unsafe fn spawn<Fut, Fn>(&self, func: Fn)
+  /// [`task`]: TaskScheduler::task
+  pub fn spawn<Fut, Fn>(&self, func: Fn) -> TaskHandle
   where
     Fn: FnOnce(GameRef) -> Fut + 'static,
     Fut: Future<Output = ()> + 'static,
   {
-    let _ = self.spawn.send(Box::new(async {
-      func(GameRef::new()).await;
-    }));
+    self.task().spawn(func)
+  }
+
+  /// Create a builder for a task with extra options.
+  pub fn task(&self) -> TaskBuilder<'_> {
+    TaskBuilder {
+      scheduler: self,
+      name: None,
+      entity: None,
+    }
   }
 
   /// Schedule a function to run the first frame after `time`.
-  pub fn schedule<T>(&self, time: Instant, task: T)
+  pub fn schedule<T>(&self, time: Instant, task: T) -> TaskHandle
   where
     T: FnOnce(&mut AirmashGame) + 'static,
   {
-    unsafe {
-      self.spawn(move |mut game: GameRef| async move {
-        game.sleep_until(time).await;
-        task(&mut *game)
-      });
-    }
+    self.spawn(move |mut game: GameRef| async move {
+      game.sleep_until(time).await;
+      game.with(task)
+    })
+  }
+
+  /// Find a running task with the provided name.
+  pub fn find(&self, name: &str) -> Option<TaskHandle> {
+    self
+      .live
+      .borrow()
+      .iter()
+      .find(|task| !task.is_finished() && task.name() == Some(name))
+      .cloned()
+  }
+
+  /// Handles to all the tasks that have not finished yet.
+  pub fn tasks(&self) -> Vec<TaskHandle> {
+    self
+      .live
+      .borrow()
+      .iter()
+      .filter(|task| !task.is_finished())
+      .cloned()
+      .collect()
   }
 
   pub(crate) fn update(&self, game: &mut AirmashGame) {
     let inner = Rc::clone(&self.inner);
     let mut inner = inner.borrow_mut();
     inner.turn(game);
+
+    self.live.borrow_mut().retain(|task| !task.is_finished());
   }
 }
 
@@ -124,9 +381,15 @@ impl Default for TaskScheduler {
   }
 }
 
+struct NewTask {
+  state: Rc<TaskState>,
+  task: Box<dyn Future<Output = ()>>,
+}
+
 struct TaskItem {
   task: Pin<Box<dyn Future<Output = ()>>>,
   waker: Arc<TaskWaker>,
+  state: Rc<TaskState>,
   last: u64,
 }
 
@@ -135,13 +398,16 @@ struct TaskSchedulerImpl {
   queue: BinaryHeap<TimeoutDesc>,
   external: Receiver<usize>,
   sender: Sender<usize>,
-  incoming: Receiver<Box<dyn Future<Output = ()>>>,
+  incoming: Receiver<NewTask>,
+  /// Tasks that were woken after they had already been polled this turn. They
+  /// will be polled again next turn.
+  deferred: Vec<usize>,
 
   turn: u64,
 }
 
 impl TaskSchedulerImpl {
-  fn new(incoming: Receiver<Box<dyn Future<Output = ()>>>) -> Self {
+  fn new(incoming: Receiver<NewTask>) -> Self {
     let (tx, rx) = crossbeam_channel::unbounded();
 
     Self {
@@ -150,6 +416,7 @@ impl TaskSchedulerImpl {
       external: rx,
       sender: tx,
       incoming,
+      deferred: Vec::new(),
       turn: 0,
     }
   }
@@ -160,6 +427,17 @@ impl TaskSchedulerImpl {
 
     let this_frame = game.this_frame();
 
+    // Drop tasks that have been cancelled or whose entity has been despawned.
+    // Stale entries for them in the timeout queue are skipped when polling.
+    self.tasks.retain(|_, item| {
+      if item.state.should_stop(game) {
+        item.state.finished.set(true);
+        return false;
+      }
+
+      true
+    });
+
     TASK_CONTEXT.with(|ctx| {
       ctx.set(Some(TaskContext {
         game,
@@ -170,17 +448,17 @@ impl TaskSchedulerImpl {
       TASK_CONTEXT.with(|ctx| ctx.set(None));
     });
 
-    while let Ok(task) = self.incoming.try_recv() {
+    while let Ok(NewTask { state, task }) = self.incoming.try_recv() {
       let entry = self.tasks.vacant_entry();
       let id = entry.key();
 
       entry.insert(TaskItem {
-        // SAFETY: This is just Box::into_pin which is still unstable.
-        task: unsafe { Pin::new_unchecked(task) },
+        task: Box::into_pin(task),
         waker: Arc::new(TaskWaker {
           id,
           channel: self.sender.clone(),
         }),
+        state,
         last: 0,
       });
 
@@ -197,24 +475,52 @@ impl TaskSchedulerImpl {
     }
 
     while let Ok(taskid) = self.external.try_recv() {
+      // A task that has already been polled this turn might have been woken by
+      // something that happened afterwards (e.g. an event dispatched by another
+      // task) so it needs to be polled again next turn.
+      let polled = self.tasks.get(taskid).is_some_and(|t| t.last == self.turn);
+      if polled {
+        if !self.deferred.contains(&taskid) {
+          self.deferred.push(taskid);
+        }
+        continue;
+      }
+
       self.poll_task(taskid, &timeout);
     }
+
+    for taskid in self.deferred.drain(..) {
+      let _ = self.sender.send(taskid);
+    }
   }
 
   fn poll_task(&mut self, taskid: usize, timeout: &UnsafeCell<Option<Instant>>) {
-    let mut task = &mut self.tasks[taskid];
+    let task = match self.tasks.get_mut(taskid) {
+      Some(task) => task,
+      None => return,
+    };
 
     if task.last == self.turn {
       return;
     }
 
+    // The task may have been cancelled, or had its entity despawned, by another
+    // task earlier this frame.
+    let ctx = TASK_CONTEXT.with(|ctx| ctx.get());
+    // SAFETY: No task is being polled so nothing else is borrowing the game.
+    if ctx.is_some_and(|ctx| task.state.should_stop(unsafe { &*ctx.game })) {
+      task.state.finished.set(true);
+      self.tasks.remove(taskid);
+      return;
+    }
+
     task.last = self.turn;
     let waker = futures_task::waker_ref(&task.waker);
     let mut context = Context::from_waker(&waker);
 
     match Future::poll(task.task.as_mut(), &mut context) {
       Poll::Ready(()) => {
-        self.tasks.remove(taskid);
+        self.tasks.remove(taskid).state.finished.set(true);
         // SAFETY: Timeout is only accessed otherwise from within Future::poll
         //         and references do not outlive it.
         unsafe { *timeout.get() = None };
@@ -285,22 +591,21 @@ impl Ord for TimeoutDesc {
 
 impl Eq for TimeoutDesc {}
 
-struct TimeoutFuture<'g> {
-  game: &'g mut GameRef,
+struct TimeoutFuture {
   timeout: Instant,
 }
 
-impl<'g> TimeoutFuture<'g> {
-  fn new(timeout: Instant, game: &'g mut GameRef) -> Self {
-    Self { timeout, game }
+impl TimeoutFuture {
+  fn new(timeout: Instant) -> Self {
+    Self { timeout }
   }
 }
 
-impl<'g> Future for TimeoutFuture<'g> {
+impl Future for TimeoutFuture {
   type Output = ();
 
   fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
-    let this_frame = self.game.this_frame();
+    let this_frame = GameRef::new().with(|game| game.this_frame());
 
     if this_frame >= self.timeout {
       Poll::Ready(())
@@ -2,8 +2,12 @@
 
 use std::time::{Duration, Instant};
 
-use crate::component::{Effects, Upgrades};
-use crate::protocol::Time;
+use bstr::BString;
+
+use crate::component::*;
+use crate::config::PlanePrototypeRef;
+use crate::protocol::server::{Login, LoginPlayer};
+use crate::protocol::{FlagCode, GameType, PlayerStatus, Time};
 use crate::resource::*;
 use crate::{AirmashGame, Vector2};
 
@@ -46,6 +50,54 @@ pub fn get_server_upgrades(upgrades: &Upgrades, effects: &Effects) -> crate::pro
   }
 }
 
+/// Build the login packet sent to a player when they join the game. It
+/// contains the current state of every player in the game.
+pub fn get_login_packet(game: &AirmashGame, id: u16, team: u16, token: BString) -> Login {
+  let mut query = game
+    .world
+    .query::<(
+      &IsAlive,
+      &Level,
+      &Name,
+      &PlanePrototypeRef,
+      &Team,
+      &Position,
+      &Rotation,
+      &FlagCode,
+      &Upgrades,
+      &Effects,
+    )>()
+    .with::<IsPlayer>();
+  let players = query
+    .into_iter()
+    .map(
+      |(ent, (alive, level, name, plane, team, pos, rot, flag, upgrades, effects))| LoginPlayer {
+        id: ent.id() as u16,
+        status: PlayerStatus::from(*alive),
+        level: level.0,
+        name: name.0.clone(),
+        ty: plane.server_type,
+        team: team.0,
+        pos: pos.into(),
+        rot: rot.0,
+        flag: *flag,
+        upgrades: get_server_upgrades(upgrades, effects),
+      },
+    )
+    .collect::<Vec<_>>();
+
+  Login {
+    success: true,
+    id,
+    team,
+    clock: get_current_clock(game),
+    token,
+    ty: *game.resources.read::<GameType>(),
+    room: game.resources.read::<RegionName>().0.clone().into(),
+    players,
+  }
+}
+
 pub fn rotate(v: Vector2, angle: f32) -> Vector2 {
   let (sin, cos) = angle.sin_cos();
   Vector2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
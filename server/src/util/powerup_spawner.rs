@@ -1,13 +1,16 @@
 use std::time::{Duration, Instant};
 
+use crate::component::{IsMob, Position};
 use crate::event::Frame;
 use crate::protocol::MobType;
-use crate::{Entity, EventHandler, Vector2};
+use crate::{AirmashGame, Entity, EventHandler, Vector2};
 
 #[derive(Copy, Clone)]
 enum SpawnerState {
   Spawned(Entity),
   Unspawned(Instant),
+  /// The powerup will be spawned on the first frame.
+  Initial,
 }
 
 pub struct PeriodicPowerupSpawner {
@@ -23,7 +26,7 @@ impl PeriodicPowerupSpawner {
       mob,
       pos,
       interval,
-      state: SpawnerState::Unspawned(Instant::now()),
+      state: SpawnerState::Initial,
     }
   }
 
@@ -34,10 +37,22 @@ impl PeriodicPowerupSpawner {
   pub fn shield(pos: Vector2, interval: Duration) -> Self {
     Self::new(MobType::Shield, pos, interval)
   }
+
+  /// A mob of the right type that already exists at the spawn position (e.g.
+  /// because it was restored from a snapshot).
+  fn existing(&self, game: &mut AirmashGame) -> Option<Entity> {
+    game
+      .world
+      .query_mut::<(&MobType, &Position)>()
+      .with::<IsMob>()
+      .into_iter()
+      .find(|(_, (&mob, pos))| mob == self.mob && pos.0 == self.pos)
+      .map(|(ent, _)| ent)
+  }
 }
 
 impl EventHandler<Frame> for PeriodicPowerupSpawner {
-  fn on_event(&mut self, _: &Frame, game: &mut crate::AirmashGame) {
+  fn on_event(&mut self, _: &Frame, game: &mut AirmashGame) {
     let frame = game.this_frame();
 
     match self.state {
@@ -46,11 +61,15 @@ impl EventHandler<Frame> for PeriodicPowerupSpawner {
           self.state = SpawnerState::Unspawned(frame + self.interval);
         }
       }
-      SpawnerState::Unspawned(next) => {
-        if frame > next {
-          let entity = game.spawn_mob(self.mob, self.pos, Duration::from_secs(60));
-          self.state = SpawnerState::Spawned(entity);
-        }
+      SpawnerState::Unspawned(next) if frame <= next => (),
+      SpawnerState::Unspawned(_) | SpawnerState::Initial => {
+        let existing = match self.state {
+          SpawnerState::Initial => self.existing(game),
+          _ => None,
+        };
+        let entity =
+          existing.unwrap_or_else(|| game.spawn_mob(self.mob, self.pos, Duration::from_secs(60)));
+        self.state = SpawnerState::Spawned(entity);
       }
     }
   }
@@ -9,8 +9,11 @@ use hecs::Entity;
 
 use crate::dispatch::EventDispatcher;
 use crate::event::ServerStartup;
-use crate::network::ConnectionMgr;
-use crate::{Event, EventHandler};
+use crate::network::{
+  AllowedOrigins, ConnectionMgr, NetworkOptions, ProxyConfig, SharedStatus, TlsConfig,
+};
+use crate::resource::{BanList, ConnectionLimits, Profiler};
+use crate::{Event, EventHandler, HandlerInfo, PreEventHandler};
 
 /// Main airmash game, containing all game data and resources.
 pub struct AirmashGame {
@@ -48,12 +51,15 @@ impl AirmashGame {
     let timestep = Duration::from_secs_f32(1.0 / 60.0);
     let mut current = Instant::now();
 
-    while !self.shutdown.load(Ordering::Relaxed) {
+    while !self.shutdown.load(Ordering::Relaxed) && !SHUTDOWN_SIGNALLED.load(Ordering::Relaxed) {
       current += timestep;
       let now = Instant::now();
 
       // If we're falling behind then skip a frame
       if current < now {
+        if let Some(mut profiler) = self.resources.get_mut::<Profiler>() {
+          profiler.record_skipped_frame();
+        }
         continue;
       }
 
@@ -66,6 +72,15 @@ impl AirmashGame {
   }
 }
 
+/// Set when a shutdown signal is received. See
+/// [`AirmashGame::shutdown_on_signal`].
+static SHUTDOWN_SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle_shutdown_signal(_: libc::c_int) {
+  SHUTDOWN_SIGNALLED.store(true, Ordering::Relaxed);
+}
+
 impl AirmashGame {
   /// Create a new game with no default resources or event handlers.
   ///
@@ -86,9 +101,33 @@ impl AirmashGame {
 
   /// An airmash server with the full networking backend enabled.
   pub fn with_network(addr: SocketAddr) -> Self {
+    Self::with_network_options(addr, None)
+  }
+
+  /// An airmash server with the full networking backend enabled that serves
+  /// websocket connections over TLS.
+  pub fn with_tls_network(addr: SocketAddr, tls: TlsConfig) -> Self {
+    Self::with_network_options(addr, Some(tls))
+  }
+
+  fn with_network_options(addr: SocketAddr, tls: Option<TlsConfig>) -> Self {
     let mut me = Self::with_test_defaults();
-    me.resources
-      .insert(ConnectionMgr::with_server(addr, me.shutdown.clone()));
+    let options = NetworkOptions {
+      bans: me.resources.read::<BanList>().clone(),
+      limits: me.resources.read::<ConnectionLimits>().clone(),
+      tls: tls.clone(),
+      proxy: me.resources.read::<ProxyConfig>().clone(),
+      origins: me.resources.read::<AllowedOrigins>().clone(),
+      status: me.resources.read::<SharedStatus>().clone(),
+    };
+    me.resources.insert(ConnectionMgr::with_server(
+      addr,
+      me.shutdown.clone(),
+      options,
+    ));
+    if let Some(tls) = tls {
+      me.resources.insert(tls);
+    }
 
     me
   }
@@ -105,10 +144,27 @@ impl AirmashGame {
     self.shutdown.store(true, Ordering::Relaxed);
   }
 
+  /// Shut down the server when the process receives `SIGINT` or `SIGTERM`
+  /// instead of exiting immediately. This gives the caller of
+  /// [`run_until_shutdown`] a chance to save state before exiting.
+  ///
+  /// This does nothing on platforms other than unix.
+  ///
+  /// [`run_until_shutdown`]: crate::AirmashGame::run_until_shutdown
+  pub fn shutdown_on_signal(&self) {
+    #[cfg(unix)]
+    unsafe {
+      let handler = handle_shutdown_signal as extern "C" fn(libc::c_int);
+      libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+      libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+    }
+  }
+
   /// Register an event handler with the default priority.
   ///
   /// See the [`event`](crate::event) module docs for a description of how event
   /// handling works.
+  #[track_caller]
   pub fn register<E, H>(&mut self, handler: H)
   where
     E: Event,
@@ -121,6 +177,7 @@ impl AirmashGame {
   ///
   /// See the [`event`](crate::event) module docs for a description of how event
   /// handling works.
+  #[track_caller]
   pub fn register_with_priority<E, H>(&mut self, priority: i32, handler: H)
   where
     E: Event,
@@ -129,6 +186,119 @@ impl AirmashGame {
     self.dispatcher().register_with_priority(priority, handler);
   }
 
+  /// Register a pre-event handler with the default priority.
+  ///
+  /// See the [`event`](crate::event) module docs for a description of how
+  /// pre-events work.
+  #[track_caller]
+  pub fn register_pre<E, H>(&mut self, handler: H)
+  where
+    E: Event,
+    H: PreEventHandler<E>,
+  {
+    self.register_pre_with_priority(crate::priority::DEFAULT, handler);
+  }
+
+  /// Register a pre-event handler with a custom priority.
+  ///
+  /// See the [`event`](crate::event) module docs for a description of how
+  /// pre-events work.
+  #[track_caller]
+  pub fn register_pre_with_priority<E, H>(&mut self, priority: i32, handler: H)
+  where
+    E: Event,
+    H: PreEventHandler<E>,
+  {
+    self
+      .dispatcher()
+      .register_pre_with_priority(priority, handler);
+  }
+
+  /// List all registered event handlers along with their names and priorities.
+  ///
+  /// Handlers registered via `#[handler]` are named after the full path of the
+  /// function. These names can be passed to [`disable_handler`] and
+  /// [`replace_handler`] to change the default behaviour of the server.
+  ///
+  /// [`disable_handler`]: crate::AirmashGame::disable_handler
+  /// [`replace_handler`]: crate::AirmashGame::replace_handler
+  pub fn handlers(&self) -> Vec<HandlerInfo> {
+    self.dispatcher().handlers()
+  }
+
+  /// List the handlers registered for `E`, along with their priorities and
+  /// where they were registered, in the order that they will be executed.
+  pub fn handlers_for<E: Event>(&self) -> Vec<HandlerInfo> {
+    self.dispatcher().handlers_for::<E>()
+  }
+
+  /// Log every dispatched event along with the time taken by each of its
+  /// handlers. See [`EventDispatcher::set_tracing`] for details.
+  pub fn set_event_tracing(&self, enabled: bool) {
+    self.dispatcher().set_tracing(enabled);
+  }
+
+  /// Remove all event handlers with the provided name so that they no longer
+  /// run. Returns whether there were any such handlers.
+  ///
+  /// This should be done before calling [`run_until_shutdown`].
+  ///
+  /// # Panics
+  /// Panics if called from within an event handler.
+  ///
+  /// [`run_until_shutdown`]: crate::AirmashGame::run_until_shutdown
+  pub fn disable_handler(&mut self, name: &str) -> bool {
+    self.dispatcher().disable(name)
+  }
+
+  /// Replace the handler for `E` with the provided name. The new handler runs
+  /// at the same priority as the one it replaces. Returns whether there was a
+  /// handler with that name for `E`.
+  ///
+  /// This should be done before calling [`run_until_shutdown`].
+  ///
+  /// # Panics
+  /// Panics if called from within an event handler.
+  ///
+  /// [`run_until_shutdown`]: crate::AirmashGame::run_until_shutdown
+  pub fn replace_handler<E, H>(&mut self, name: &str, handler: H) -> bool
+  where
+    E: Event,
+    H: EventHandler<E>,
+  {
+    self.dispatcher().replace(name, handler)
+  }
+
+  /// Replace the pre-event handler for `E` with the provided name. This works
+  /// the same as [`replace_handler`] but for pre-events.
+  ///
+  /// [`replace_handler`]: crate::AirmashGame::replace_handler
+  pub fn replace_pre_handler<E, H>(&mut self, name: &str, handler: H) -> bool
+  where
+    E: Event,
+    H: PreEventHandler<E>,
+  {
+    self.dispatcher().replace_pre(name, handler)
+  }
+
+  /// Dispatch a pre-event and return it as modified by its handlers.
+  ///
+  /// The handlers are executed immediately, even if this is called while
+  /// another event is being dispatched, so that the caller can act on the
+  /// result.
+  ///
+  /// See the [`event`](crate::event) module docs for a description of how
+  /// pre-events work.
+  pub fn dispatch_pre<E>(&mut self, event: E) -> E
+  where
+    E: Event,
+  {
+    self.record_event::<E>();
+
+    let dispatcher = self.dispatcher();
+    dispatcher.dispatch_pre(event, self)
+  }
+
   /// Dispatch an event and execute all the corresponding event handlers.
   ///
   /// If there is no event currently executing then the event will be dispatched
@@ -142,6 +312,8 @@ impl AirmashGame {
   where
     E: Event,
   {
+    self.record_event::<E>();
+
     let dispatcher = self.dispatcher();
     dispatcher.dispatch(event, self)
   }
@@ -157,6 +329,7 @@ impl AirmashGame {
   {
     let dispatcher = self.dispatcher();
     for event in events {
+      self.record_event::<E>();
       dispatcher.dispatch(event, self);
     }
   }
@@ -167,6 +340,14 @@ impl AirmashGame {
     self.resources.read::<EventDispatcher>().clone()
   }
 
+  fn record_event<E: 'static>(&self) {
+    use crate::resource::Metrics;
+
+    if let Some(mut metrics) = self.resources.get_mut::<Metrics>() {
+      metrics.record_event(std::any::type_name::<E>());
+    }
+  }
+
   fn init_defaults(&mut self) {
     use crate::resource::collision::*;
     use crate::resource::*;
@@ -184,6 +365,26 @@ impl AirmashGame {
     self.resources.insert(TaskScheduler::new());
     self.resources.insert(GameConfig::default());
     self.resources.insert(ServerStats::default());
+    self.resources.insert(Metrics::default());
+    self.resources.insert(Profiler::default());
+    self.resources.insert(OutputDirs::default());
+    self.resources.insert(GameRng::default());
+    self.resources.insert(AdminTokens::default());
+    self.resources.insert(BanList::default());
+    self.resources.insert(ConnectionLimits::default());
+    self.resources.insert(ChatLimits::default());
+    self.resources.insert(WordFilter::default());
+    self.resources.insert(SharedStatus::default());
+    self.resources.insert(ProxyConfig::default());
+    self.resources.insert(AllowedOrigins::default());
+    self.resources.insert(SnapshotRegistry::default());
+    self.resources.insert(RetiredConfigs::default());
+    self.resources.insert({
+      let mut registry = CommandRegistry::new();
+      crate::command::register_builtin_commands(&mut registry);
+      crate::system::register_commands(&mut registry);
+      registry
+    });
 
     self.resources.insert(RegionName("default".to_owned()));
     self.resources.insert(GameType::FFA);
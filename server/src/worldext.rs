@@ -7,8 +7,12 @@ use smallvec::SmallVec;
 
 use crate::component::*;
 use crate::config::{MissilePrototypeRef, PlanePrototypeRef};
-use crate::event::{EntitySpawn, MobSpawn, PlayerFire};
-use crate::network::{ConnectionId, ConnectionMgr};
+use crate::event::{
+  BeforePlayerFire, BeforeRespawn, EntitySpawn, MobSpawn, PlayerFire, PlayerRespawn,
+};
+use crate::network::{
+  is_spectator_visible, without_token, ConnectionId, ConnectionMgr, DemoTarget,
+};
 use crate::protocol::{v5, MobType, ServerPacket};
 use crate::resource::collision::LayerSpec;
 use crate::resource::{Config, LastFrame, ThisFrame};
@@ -73,15 +77,59 @@ impl AirmashGame {
     None
   }
 
+  /// Respawn a player.
+  ///
+  /// This dispatches [`BeforeRespawn`] and then, unless one of its handlers
+  /// cancelled the respawn, [`PlayerRespawn`]. Returns whether the player was
+  /// respawned.
+  pub fn respawn_player(&mut self, player: Entity) -> bool {
+    let alive = match self.world.query_one_mut::<(&IsAlive, &IsPlayer)>(player) {
+      Ok((alive, _)) => alive.0,
+      Err(_) => return false,
+    };
+
+    let before = self.dispatch_pre(BeforeRespawn {
+      player,
+      alive,
+      pos: None,
+      cancelled: false,
+    });
+    if before.cancelled {
+      return false;
+    }
+
+    self.dispatch(PlayerRespawn {
+      player,
+      alive,
+      pos: before.pos,
+    });
+    true
+  }
+
   /// Fire a number of missiles from a plane.
   ///
   /// This will create the entities for the missiles and also dispatch the
-  /// required events.
+  /// required events. A [`BeforePlayerFire`] handler may change the missiles
+  /// that are fired or cancel the shot, in which case no missiles are fired.
   pub fn fire_missiles(
     &mut self,
     player: Entity,
     missiles: &[FireMissileInfo],
   ) -> Result<SmallVec<[Entity; 3]>, hecs::NoSuchEntity> {
+    if !self.world.contains(player) {
+      return Err(NoSuchEntity);
+    }
+
+    let before = self.dispatch_pre(BeforePlayerFire {
+      player,
+      missiles: missiles.iter().copied().collect(),
+      cancelled: false,
+    });
+    if before.cancelled {
+      return Ok(SmallVec::new());
+    }
+    let missiles = &before.missiles[..];
+
     let mut entities = SmallVec::new();
     let mut builders = SmallVec::<[EntityBuilder; 5]>::new();
 
@@ -328,31 +376,70 @@ impl AirmashGame {
       //       There is a test at the end of this file that verifies that this works
       //       as expected.
       let entity = Entity::from_bits(entity.to_bits().get() + (1 << 32)).unwrap_or(entity);
+      let this_frame = game.this_frame();
 
       // The airmash client doesn't like it if you reuse ids soon after they get
       // destroyed. By reserving them for a minute we should prevent having dead
       // missiles just lying around.
       game.world.spawn_at(
         entity,
-        (Expiry(Instant::now() + Duration::from_secs(10)), IsZombie),
+        (Expiry(this_frame + Duration::from_secs(10)), IsZombie),
       );
     });
   }
 }
 
+/// Whether a packet can be dropped when a connection's send queue is backed
+/// up. These are packets whose contents will be superseded by a later packet
+/// anyway.
+fn is_droppable(packet: &ServerPacket) -> bool {
+  matches!(packet, ServerPacket::PlayerUpdate(_))
+}
+
 impl AirmashGame {
+  fn record_packets_out(&self, packet: &ServerPacket, count: u64) {
+    use crate::resource::{server_packet_name, Metrics};
+
+    if let Some(mut metrics) = self.resources.get_mut::<Metrics>() {
+      metrics.record_packets_out(server_packet_name(packet), count);
+    }
+  }
+
+  fn record_demo_packet(&self, connmgr: &mut ConnectionMgr, packet: &ServerPacket, data: &[u8]) {
+    match without_token(packet) {
+      Some(packet) => {
+        if let Ok(data) = v5::serialize(&packet) {
+          connmgr.record_demo_packet(self.this_frame(), &data);
+        }
+      }
+      None => connmgr.record_demo_packet(self.this_frame(), data),
+    }
+  }
+
   /// Send a packet directly to a connection.
   ///
   /// This method is rather low-level. Generally you should be using one of the
   /// other methods for sending packets as they are more convenient.
   pub fn send_to_conn(&self, conn: ConnectionId, packet: impl Into<ServerPacket>) {
     let mut connmgr = self.resources.write::<ConnectionMgr>();
-    let data = match v5::serialize(&packet.into()) {
-      Ok(data) => data,
+    let packet = packet.into();
+    let data = match v5::serialize(&packet) {
+      Ok(data) => Arc::new(data),
       Err(_) => return,
     };
 
-    connmgr.send_to_conn(conn, Arc::new(data));
+    self.record_packets_out(&packet, 1);
+    if let Some(DemoTarget::Player(player)) = connmgr.demo_target() {
+      if connmgr.primary(player) == Some(conn) {
+        self.record_demo_packet(&mut connmgr, &packet, &data);
+      }
+    }
+
+    if is_droppable(&packet) {
+      connmgr.send_droppable_to_conn(conn, data);
+    } else {
+      connmgr.send_to_conn(conn, data);
+    }
   }
 
   /// Given an iterator of entities send the provided packet to all of them. If
@@ -378,9 +465,28 @@ impl AirmashGame {
       Err(_) => return,
     };
 
+    let droppable = is_droppable(packet);
+    let target = connmgr.demo_target();
+    let mut count = 0;
+    let mut recorded = false;
     for entity in entities {
-      connmgr.send_to(entity, Arc::clone(&data));
+      if !recorded && target == Some(DemoTarget::Player(entity)) {
+        self.record_demo_packet(&mut connmgr, packet, &data);
+        recorded = true;
+      }
+
+      if droppable {
+        connmgr.send_droppable_to(entity, Arc::clone(&data));
+      } else {
+        connmgr.send_to(entity, Arc::clone(&data));
+      }
+      count += 1;
+    }
+
+    if target == Some(DemoTarget::Spectator) && count > 0 && is_spectator_visible(packet) {
+      self.record_demo_packet(&mut connmgr, packet, &data);
     }
+    self.record_packets_out(packet, count);
   }
 
   /// Send a packet to the connection corresponding to the player.
@@ -390,11 +496,20 @@ impl AirmashGame {
   fn _send_to(&self, player: Entity, packet: &ServerPacket) {
     let mut connmgr = self.resources.write::<ConnectionMgr>();
     let data = match v5::serialize(packet) {
-      Ok(data) => data,
+      Ok(data) => Arc::new(data),
       Err(_) => return,
     };
 
-    connmgr.send_to(player, Arc::new(data));
+    self.record_packets_out(packet, 1);
+    if connmgr.demo_target() == Some(DemoTarget::Player(player)) {
+      self.record_demo_packet(&mut connmgr, packet, &data);
+    }
+
+    if is_droppable(packet) {
+      connmgr.send_droppable_to(player, data);
+    } else {
+      connmgr.send_to(player, data);
+    }
   }
 
   /// Send a packet to all players that are within the visible range of the
@@ -1,6 +1,6 @@
-use airmash::component::Position;
+use airmash::component::{AdminRole, Position};
 use airmash::protocol::client as c;
-use airmash::resource::GameConfig;
+use airmash::resource::AdminTokens;
 
 #[test]
 fn admin_teleport() {
@@ -14,8 +14,12 @@ fn admin_teleport() {
   let id = crate::utils::get_login_id(&mut client);
   let ent = game.find_entity_by_id(id).unwrap();
 
-  game.resources.write::<GameConfig>().admin_enabled = true;
+  game
+    .resources
+    .write::<AdminTokens>()
+    .insert("secret", AdminRole::Admin);
 
+  client.send_command("auth", "secret");
   client.send(c::Command {
     com: "teleport".into(),
     data: "0 -700 2200".into(),
@@ -28,3 +32,68 @@ fn admin_teleport() {
   assert_abs_diff_eq!(pos.x, -700.0, epsilon = 0.1);
   assert_abs_diff_eq!(pos.y, 2200.0, epsilon = 0.1);
 }
+
+#[test]
+fn teleport_requires_authentication() {
+  let (mut game, mut mock) = crate::utils::create_mock_server();
+
+  let mut client = mock.open();
+  let ent = client.login("test", &mut game);
+
+  game
+    .resources
+    .write::<AdminTokens>()
+    .insert("secret", AdminRole::Admin);
+
+  client.send_command("auth", "not-the-secret");
+  client.send_command("teleport", "0 -700 2200");
+  game.run_once();
+
+  assert!(game.world.get::<AdminRole>(ent).is_err());
+
+  let pos = game.world.get::<Position>(ent).unwrap();
+  assert_abs_diff_ne!(pos.x, -700.0, epsilon = 0.1);
+}
+
+#[test]
+fn moderator_cannot_teleport() {
+  let (mut game, mut mock) = crate::utils::create_mock_server();
+
+  let mut client = mock.open();
+  let ent = client.login("test", &mut game);
+
+  game
+    .resources
+    .write::<AdminTokens>()
+    .insert("modtoken", AdminRole::Moderator);
+
+  client.send_command("auth", "modtoken");
+  client.send_command("teleport", "0 -700 2200");
+  game.run_once();
+
+  assert_eq!(
+    *game.world.get::<AdminRole>(ent).unwrap(),
+    AdminRole::Moderator
+  );
+
+  let pos = game.world.get::<Position>(ent).unwrap();
+  assert_abs_diff_ne!(pos.x, -700.0, epsilon = 0.1);
+}
+
+#[test]
+fn admin_tokens_parse() {
+  let tokens = AdminTokens::parse(
+    "# comment\n\
+     moderator modtoken\n\
+     \n\
+     owner  ownertoken\n",
+  )
+  .unwrap();
+
+  assert_eq!(tokens.authenticate(b"modtoken"), Some(AdminRole::Moderator));
+  assert_eq!(tokens.authenticate(b"ownertoken"), Some(AdminRole::Owner));
+  assert_eq!(tokens.authenticate(b"other"), None);
+
+  assert!(AdminTokens::parse("superuser token").is_err());
+  assert!(AdminTokens::parse("admin").is_err());
+}
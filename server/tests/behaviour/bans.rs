@@ -0,0 +1,187 @@
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use airmash::component::AdminRole;
+use airmash::protocol::{client as c, ErrorType, ServerPacket};
+use airmash::resource::{BanList, BanTarget};
+use airmash::test::TestGame;
+
+fn addr(s: &str) -> SocketAddr {
+  s.parse().unwrap()
+}
+
+#[test]
+fn moderator_can_kick_player() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut admin = mock.open();
+  let mut target = mock.open();
+  let admin_ent = admin.login("admin", &mut game);
+  let target_ent = target.login("target", &mut game);
+
+  game
+    .world
+    .insert_one(admin_ent, AdminRole::Moderator)
+    .unwrap();
+
+  admin.send_command("kick", &target_ent.id().to_string());
+  game.run_count(2);
+
+  assert!(target.packets().any(|p| matches!(
+    p,
+    ServerPacket::Error(e) if e.error == ErrorType::Kicked
+  )));
+  assert!(game.find_entity_by_id(target_ent.id() as _).is_none());
+}
+
+#[test]
+fn banned_ip_cannot_login() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut admin = mock.open_with_addr(addr("10.0.0.1:1000"));
+  let mut target = mock.open_with_addr(addr("10.0.0.2:1000"));
+  let admin_ent = admin.login("admin", &mut game);
+  let target_ent = target.login("target", &mut game);
+
+  game.world.insert_one(admin_ent, AdminRole::Admin).unwrap();
+
+  admin.send_command("ban-ip", &format!("{} 1h", target_ent.id()));
+  game.run_count(2);
+
+  assert!(target.packets().any(|p| matches!(
+    p,
+    ServerPacket::Error(e) if e.error == ErrorType::Banned
+  )));
+  assert!(game
+    .resources
+    .read::<BanList>()
+    .is_banned(&BanTarget::Ip("10.0.0.2".parse().unwrap())));
+
+  let mut rejoin = mock.open_with_addr(addr("10.0.0.2:2000"));
+  rejoin.send_login("target");
+  game.run_once();
+
+  assert!(matches!(
+    rejoin.next_packet(),
+    Some(ServerPacket::Error(e)) if e.error == ErrorType::Banned
+  ));
+}
+
+#[test]
+fn moderator_cannot_ban() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut admin = mock.open();
+  let mut target = mock.open();
+  let admin_ent = admin.login("admin", &mut game);
+  let target_ent = target.login("target", &mut game);
+
+  game
+    .world
+    .insert_one(admin_ent, AdminRole::Moderator)
+    .unwrap();
+
+  admin.send_command("ban-session", &target_ent.id().to_string());
+  game.run_count(2);
+
+  assert!(game.find_entity_by_id(target_ent.id() as _).is_some());
+}
+
+#[test]
+fn banned_session_cannot_login() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut admin = mock.open_with_addr(addr("10.0.0.1:1000"));
+  let mut target = mock.open_with_addr(addr("10.0.0.2:1000"));
+  let admin_ent = admin.login("admin", &mut game);
+  target.send(c::Login {
+    session: "target-session".into(),
+    ..crate::utils::create_login_packet("target")
+  });
+  game.run_once();
+  let target_ent = target.wait_for_login(&mut game);
+
+  game.world.insert_one(admin_ent, AdminRole::Admin).unwrap();
+
+  admin.send_command("ban-session", &target_ent.id().to_string());
+  game.run_count(2);
+
+  assert!(game.find_entity_by_id(target_ent.id() as _).is_none());
+
+  // Same session from a different address.
+  let mut rejoin = mock.open_with_addr(addr("10.0.0.3:1000"));
+  rejoin.send(c::Login {
+    session: "target-session".into(),
+    ..crate::utils::create_login_packet("target")
+  });
+  game.run_once();
+
+  assert!(matches!(
+    rejoin.next_packet(),
+    Some(ServerPacket::Error(e)) if e.error == ErrorType::Banned
+  ));
+}
+
+#[test]
+fn cannot_kick_or_ban_equal_or_higher_role() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut admin = mock.open();
+  let mut peer = mock.open();
+  let mut owner = mock.open();
+  let admin_ent = admin.login("admin", &mut game);
+  let peer_ent = peer.login("peer", &mut game);
+  let owner_ent = owner.login("owner", &mut game);
+
+  game.world.insert_one(admin_ent, AdminRole::Admin).unwrap();
+  game.world.insert_one(peer_ent, AdminRole::Admin).unwrap();
+  game.world.insert_one(owner_ent, AdminRole::Owner).unwrap();
+
+  admin.send_command("kick", &peer_ent.id().to_string());
+  admin.send_command("ban-ip", &owner_ent.id().to_string());
+  game.run_count(2);
+
+  assert!(game.find_entity_by_id(peer_ent.id() as _).is_some());
+  assert!(game.find_entity_by_id(owner_ent.id() as _).is_some());
+  assert!(!peer
+    .packets()
+    .any(|p| matches!(p, ServerPacket::Error(e) if e.error == ErrorType::Kicked)));
+
+  owner.send_command("kick", &admin_ent.id().to_string());
+  game.run_count(2);
+
+  assert!(game.find_entity_by_id(admin_ent.id() as _).is_none());
+}
+
+#[test]
+fn ban_list_persists_to_file() {
+  let path = std::env::temp_dir().join(format!("airmash-bans-{}.txt", std::process::id()));
+  let _ = std::fs::remove_file(&path);
+
+  let ip = BanTarget::Ip("192.168.1.1".parse().unwrap());
+  let session = BanTarget::Session("token".to_owned());
+  let expired = BanTarget::Session("expired".to_owned());
+
+  let bans = BanList::default();
+  bans.load(&path).unwrap();
+  bans.ban(ip.clone(), None);
+  bans.ban(
+    session.clone(),
+    Some(SystemTime::now() + Duration::from_secs(3600)),
+  );
+  bans.ban(
+    expired.clone(),
+    Some(SystemTime::now() - Duration::from_secs(1)),
+  );
+
+  let loaded = BanList::default();
+  loaded.load(&path).unwrap();
+  let _ = std::fs::remove_file(&path);
+
+  assert!(loaded.is_banned(&ip));
+  assert!(loaded.is_banned(&session));
+  assert!(!loaded.is_banned(&expired));
+
+  assert!(loaded.unban(&ip));
+  assert!(!loaded.is_banned(&ip));
+}
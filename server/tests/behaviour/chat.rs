@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use airmash::protocol::{client as c, ErrorType, ServerPacket};
+use airmash::resource::ChatLimits;
+use airmash::test::{MockConnection, TestGame};
+
+fn drain(client: &mut MockConnection) -> Vec<ServerPacket> {
+  client.packets().collect()
+}
+
+fn count_chats(packets: &[ServerPacket]) -> usize {
+  packets
+    .iter()
+    .filter(|p| matches!(p, ServerPacket::ChatPublic(_)))
+    .count()
+}
+
+fn was_throttled(packets: &[ServerPacket]) -> bool {
+  packets.iter().any(|p| {
+    matches!(
+      p,
+      ServerPacket::Error(e) if e.error == ErrorType::ChatThrottled
+    )
+  })
+}
+
+fn chat(client: &mut MockConnection, text: &str) {
+  client.send(c::Chat { text: text.into() });
+}
+
+#[test]
+fn messages_over_burst_are_throttled() {
+  let (mut game, mut mock) = TestGame::new();
+  game.resources.insert(ChatLimits {
+    burst: 3,
+    rate: 1.0,
+    ..Default::default()
+  });
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  let _ = client.packets().count();
+
+  for i in 0..4 {
+    chat(&mut client, &format!("message {}", i));
+  }
+  game.run_once();
+
+  assert!(was_throttled(&drain(&mut client)));
+
+  // After waiting the bucket should have refilled enough for another message.
+  game.run_for(Duration::from_secs(2));
+  let _ = client.packets().count();
+
+  chat(&mut client, "another message");
+  game.run_once();
+  assert_eq!(count_chats(&drain(&mut client)), 1);
+}
+
+#[test]
+fn repeated_messages_are_throttled() {
+  let (mut game, mut mock) = TestGame::new();
+  game.resources.insert(ChatLimits {
+    max_repeats: 2,
+    ..Default::default()
+  });
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  let _ = client.packets().count();
+
+  for _ in 0..3 {
+    chat(&mut client, "spam");
+  }
+  game.run_once();
+
+  assert_eq!(count_chats(&drain(&mut client)), 2);
+}
+
+#[test]
+fn repeat_offenders_are_muted_for_longer() {
+  let (mut game, mut mock) = TestGame::new();
+  game.resources.insert(ChatLimits {
+    burst: 1,
+    rate: 1.0,
+    violations_before_mute: 1,
+    mute_durations: vec![Duration::from_secs(5), Duration::from_secs(20)],
+    ..Default::default()
+  });
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+
+  // First offense: muted for 5 seconds.
+  chat(&mut client, "a");
+  chat(&mut client, "b");
+  game.run_once();
+
+  game.run_for(Duration::from_secs(6));
+  let _ = client.packets().count();
+  chat(&mut client, "c");
+  game.run_once();
+  assert_eq!(count_chats(&drain(&mut client)), 1);
+
+  // Second offense: muted for 20 seconds.
+  chat(&mut client, "d");
+  game.run_once();
+
+  game.run_for(Duration::from_secs(6));
+  let _ = client.packets().count();
+  chat(&mut client, "e");
+  game.run_once();
+  let packets = drain(&mut client);
+  assert_eq!(count_chats(&packets), 0);
+  assert!(was_throttled(&packets));
+
+  game.run_for(Duration::from_secs(15));
+  let _ = client.packets().count();
+  chat(&mut client, "f");
+  game.run_once();
+  assert_eq!(count_chats(&drain(&mut client)), 1);
+}
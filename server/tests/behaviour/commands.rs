@@ -0,0 +1,88 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use airmash::command::{ArgType, CommandSpec};
+use airmash::component::AdminRole;
+use airmash::protocol::ServerPacket;
+use airmash::test::TestGame;
+
+fn command_replies(packets: impl Iterator<Item = ServerPacket>) -> Vec<String> {
+  packets
+    .filter_map(|p| match p {
+      ServerPacket::CommandReply(reply) => Some(reply.text.to_string()),
+      _ => None,
+    })
+    .collect()
+}
+
+#[test]
+fn registered_command_receives_parsed_args() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let total = Rc::new(Cell::new(0));
+  let total2 = Rc::clone(&total);
+  game.register_command(
+    CommandSpec::new("add", move |ctx, _| {
+      total2.set(ctx.args.integer("a").unwrap() + ctx.args.integer("b").unwrap());
+      Ok(())
+    })
+    .arg("a", ArgType::Integer)
+    .arg("b", ArgType::Integer),
+  );
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+
+  client.send_command("add", "2 5");
+  game.run_once();
+
+  assert_eq!(total.get(), 7);
+}
+
+#[test]
+fn invalid_args_reply_with_usage() {
+  let (mut game, mut mock) = TestGame::new();
+
+  game.register_command(
+    CommandSpec::new("add", |_, _| Ok(()))
+      .arg("a", ArgType::Integer)
+      .arg("b", ArgType::Integer),
+  );
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  let _ = client.packets().count();
+
+  client.send_command("add", "2 five");
+  game.run_once();
+
+  let replies = command_replies(client.packets());
+  assert_eq!(replies.len(), 1);
+  assert!(replies[0].contains("Usage: /add <a> <b>"), "{}", replies[0]);
+}
+
+#[test]
+fn help_only_lists_permitted_commands() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  let ent = client.login("test", &mut game);
+  let _ = client.packets().count();
+
+  client.send_command("help", "");
+  game.run_once();
+
+  let replies = command_replies(client.packets());
+  assert_eq!(replies.len(), 1);
+  assert!(replies[0].contains("/respawn"));
+  assert!(!replies[0].contains("/teleport"));
+
+  game.world.insert_one(ent, AdminRole::Admin).unwrap();
+
+  client.send_command("help", "");
+  game.run_once();
+
+  let replies = command_replies(client.packets());
+  assert_eq!(replies.len(), 1);
+  assert!(replies[0].contains("/teleport"));
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use airmash::config::{GamePrototype, PlanePrototypeRef};
+use airmash::protocol::MobType;
+use airmash::resource::{Config, RetiredConfigs};
+use airmash::test::TestGame;
+use airmash::Vector2;
+
+use crate::utils::temp_path;
+
+#[test]
+fn reload_migrates_players_to_new_prototypes() {
+  let path = temp_path("migrate.lua");
+  std::fs::write(&path, "").unwrap();
+
+  let (mut game, mut mock) = TestGame::new();
+  game.load_config(&path).unwrap();
+
+  let mut client = mock.open();
+  let player = client.login("test", &mut game);
+  game.run_once();
+
+  std::fs::write(
+    &path,
+    "for idx, plane in pairs(data.planes) do plane.max_speed = 30.0 end",
+  )
+  .unwrap();
+  assert_eq!(game.reload_config(), Ok(true));
+  std::fs::remove_file(&path).unwrap();
+
+  let plane = *game.world.get::<PlanePrototypeRef>(player).unwrap();
+  let config = game.resources.read::<Config>();
+  assert_eq!(plane.max_speed, 30.0);
+  assert!(std::ptr::eq(plane, config.planes[&*plane.name]));
+}
+
+#[test]
+fn replaced_config_is_freed_once_unused() {
+  let (mut game, mut mock) = TestGame::new();
+  let mut client = mock.open();
+  client.login("test", &mut game);
+
+  let old = game.resources.read::<Config>().planes["predator"];
+  let mut proto = GamePrototype::default();
+  proto.planes[0].max_speed = 30.0;
+  game.set_config(Config::new(proto).unwrap()).unwrap();
+  assert_eq!(game.resources.read::<RetiredConfigs>().len(), 1);
+
+  // An entity that still refers to the old config keeps it alive.
+  let holder = game.world.spawn((old,));
+  game.run_once();
+  assert_eq!(game.resources.read::<RetiredConfigs>().len(), 1);
+
+  game.world.despawn(holder).unwrap();
+  game.run_once();
+  assert!(game.resources.read::<RetiredConfigs>().is_empty());
+}
+
+#[test]
+fn invalid_script_keeps_current_config() {
+  let path = temp_path("invalid.lua");
+  std::fs::write(&path, "").unwrap();
+
+  let (mut game, _mock) = TestGame::new();
+  game.load_config(&path).unwrap();
+  let predator = game.resources.read::<Config>().planes["predator"];
+
+  std::fs::write(&path, "this is not lua").unwrap();
+  assert!(game.reload_config().is_err());
+  std::fs::remove_file(&path).unwrap();
+
+  let config = game.resources.read::<Config>();
+  assert!(std::ptr::eq(predator, config.planes["predator"]));
+}
+
+#[test]
+fn removing_a_prototype_in_use_is_rejected() {
+  let (mut game, _mock) = TestGame::new();
+  game.run_once();
+
+  let mob = game.spawn_mob(
+    MobType::Shield,
+    Vector2::new(100.0, 100.0),
+    Duration::from_secs(60),
+  );
+  let shield = game.resources.read::<Config>().mobs["shield"];
+
+  let mut proto = GamePrototype::default();
+  proto.mobs.retain(|mob| mob.name != "shield");
+  let config = Config::new(proto).unwrap();
+
+  assert!(game.set_config(config).is_err());
+  assert!(std::ptr::eq(
+    shield,
+    game.resources.read::<Config>().mobs["shield"]
+  ));
+  assert!(game.world.contains(mob));
+}
+
+#[test]
+fn config_is_not_reloaded_without_a_file() {
+  let (mut game, _mock) = TestGame::new();
+
+  assert_eq!(game.reload_config(), Ok(false));
+}
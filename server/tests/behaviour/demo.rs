@@ -0,0 +1,176 @@
+use std::time::Duration;
+
+use airmash::component::Session;
+use airmash::network::{Demo, DemoTarget, SPECTATOR_ID};
+use airmash::protocol::{client as c, v5, KeyCode, ServerPacket};
+use airmash::test::TestGame;
+
+use crate::utils::temp_path;
+
+fn decode(demo: &Demo) -> Vec<ServerPacket> {
+  demo
+    .packets()
+    .iter()
+    .map(|packet| v5::deserialize(&packet.data).unwrap())
+    .collect()
+}
+
+#[test]
+fn player_demo_matches_sent_packets() {
+  let path = temp_path("player.demo");
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut alice = mock.open();
+  let ent = alice.login("alice", &mut game);
+  game.run_count(5);
+  while alice.next_raw().is_some() {}
+
+  game.record_demo(&path, DemoTarget::Player(ent)).unwrap();
+
+  let mut bob = mock.open();
+  bob.login("bob", &mut game);
+  alice.send_key(KeyCode::Up, true);
+  bob.send_key(KeyCode::Fire, true);
+  game.run_for(Duration::from_secs(2));
+  game.stop_demo();
+  game.run_for(Duration::from_millis(500));
+
+  let demo = Demo::load(&path).unwrap();
+  let _ = std::fs::remove_file(&path);
+
+  let packets = decode(&demo);
+  match &packets[0] {
+    ServerPacket::Login(login) => {
+      assert_eq!(login.id, ent.id() as u16);
+      assert_eq!(login.players.len(), 1);
+    }
+    packet => panic!("Expected a login packet, got {:?}", packet),
+  }
+
+  // Everything else in the demo is exactly what was sent to the player while
+  // recording. The rest of what the player received was sent after recording
+  // stopped.
+  let received: Vec<_> = std::iter::from_fn(|| alice.next_raw()).collect();
+  let recorded: Vec<_> = demo.packets()[1..].iter().map(|p| p.data.clone()).collect();
+  assert!(recorded.len() > 1);
+  assert!(received.len() > recorded.len());
+  assert_eq!(received[..recorded.len()], recorded[..]);
+
+  let duration = demo.duration();
+  assert!(duration >= Duration::from_millis(1900), "{:?}", duration);
+  assert!(duration <= Duration::from_secs(2), "{:?}", duration);
+}
+
+#[test]
+fn player_demo_does_not_contain_session() {
+  let path = temp_path("session.demo");
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut alice = mock.open();
+  let ent = alice.login("alice", &mut game);
+  let session = game.world.get::<Session>(ent).unwrap().0.to_string();
+
+  game.record_demo(&path, DemoTarget::Player(ent)).unwrap();
+
+  // Resuming the session sends the player another login packet.
+  alice.close();
+  game.run_once();
+  let mut resumed = mock.open();
+  resumed.send(c::Backup {
+    token: session.clone().into(),
+  });
+  game.run_once();
+  assert_eq!(resumed.wait_for_login(&mut game), ent);
+  game.stop_demo();
+
+  let demo = Demo::load(&path).unwrap();
+  let data = std::fs::read(&path).unwrap();
+  let _ = std::fs::remove_file(&path);
+
+  let logins = decode(&demo)
+    .into_iter()
+    .filter(|p| matches!(p, ServerPacket::Login(_)))
+    .count();
+  assert_eq!(logins, 2);
+  assert!(!data
+    .windows(session.len())
+    .any(|window| window == session.as_bytes()));
+}
+
+#[test]
+fn spectator_demo_records_broadcasts() {
+  let path = temp_path("spectator.demo");
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut alice = mock.open();
+  let ent = alice.login("alice", &mut game);
+  game.run_count(5);
+
+  game.record_demo(&path, DemoTarget::Spectator).unwrap();
+
+  let mut bob = mock.open();
+  let bob = bob.login("bob", &mut game);
+  alice.send_command("respawn", "2");
+  game.run_for(Duration::from_secs(1));
+  game.stop_demo();
+
+  let demo = Demo::load(&path).unwrap();
+  let _ = std::fs::remove_file(&path);
+  let packets = decode(&demo);
+
+  match &packets[0] {
+    ServerPacket::Login(login) => {
+      assert_eq!(login.id, SPECTATOR_ID);
+      let mut ids: Vec<_> = login.players.iter().map(|p| p.id).collect();
+      ids.sort();
+      assert_eq!(ids, [ent.id() as u16, SPECTATOR_ID]);
+    }
+    packet => panic!("Expected a login packet, got {:?}", packet),
+  }
+  match &packets[1] {
+    ServerPacket::GameSpectate(spectate) => assert_eq!(spectate.id, ent.id() as u16),
+    packet => panic!("Expected a spectate packet, got {:?}", packet),
+  }
+
+  // Packets sent to everyone are recorded but packets that are only meant for
+  // a single client are not.
+  assert!(packets.iter().any(|p| matches!(
+    p,
+    ServerPacket::PlayerNew(new) if new.id == bob.id() as u16
+  )));
+  assert!(packets.iter().any(|p| matches!(
+    p,
+    ServerPacket::PlayerType(ty) if ty.id == ent.id() as u16
+  )));
+  assert!(!packets[1..].iter().any(|p| matches!(
+    p,
+    ServerPacket::Login(_) | ServerPacket::Ping(_) | ServerPacket::CommandReply(_)
+  )));
+}
+
+#[test]
+fn truncated_demo_can_be_loaded() {
+  let path = temp_path("truncated.demo");
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut alice = mock.open();
+  alice.login("alice", &mut game);
+  game.record_demo(&path, DemoTarget::Spectator).unwrap();
+  game.run_for(Duration::from_secs(1));
+  game.stop_demo();
+
+  let full = Demo::load(&path).unwrap();
+  let data = std::fs::read(&path).unwrap();
+  std::fs::write(&path, &data[..data.len() - 1]).unwrap();
+  let truncated = Demo::load(&path).unwrap();
+
+  std::fs::write(&path, b"not a demo").unwrap();
+  assert!(Demo::load(&path).is_err());
+  let _ = std::fs::remove_file(&path);
+
+  assert_eq!(truncated.packets().len(), full.packets().len() - 1);
+  assert_eq!(
+    truncated.packets(),
+    &full.packets()[..truncated.packets().len()]
+  );
+}
@@ -0,0 +1,168 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use airmash::component::AdminRole;
+use airmash::event::PlayerRespawn;
+use airmash::protocol::ServerPacket;
+use airmash::resource::AdminTokens;
+use airmash::test::TestGame;
+use airmash::{AirmashGame, EventDispatcher};
+
+const SEND_RESPAWN: &str = "airmash::system::handler::on_player_respawn::send_packet";
+
+#[test]
+fn builtin_handlers_are_listed_by_path() {
+  let (game, _) = TestGame::new();
+
+  let handlers = game.handlers();
+  let handler = handlers
+    .iter()
+    .find(|handler| handler.name == SEND_RESPAWN)
+    .expect("send_packet handler was not registered");
+
+  assert_eq!(handler.event, std::any::type_name::<PlayerRespawn>());
+  assert_eq!(handler.priority, airmash::priority::DEFAULT);
+  assert!(!handler.pre);
+}
+
+#[test]
+fn disabled_handler_does_not_run() {
+  let (mut game, mut mock) = TestGame::new();
+
+  assert!(game.disable_handler(SEND_RESPAWN));
+  assert!(!game.disable_handler(SEND_RESPAWN));
+  assert!(!game.handlers().iter().any(|h| h.name == SEND_RESPAWN));
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+
+  game.run_for(Duration::from_secs(2));
+  let _ = client.packets().count();
+  client.send_command("respawn", "3");
+  game.run_once();
+
+  assert!(!client
+    .packets()
+    .any(|p| matches!(p, ServerPacket::PlayerRespawn(_))));
+}
+
+#[test]
+fn replaced_handler_runs_instead() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let called = Rc::new(Cell::new(false));
+  let flag = called.clone();
+  assert!(game.replace_handler(
+    SEND_RESPAWN,
+    move |_: &PlayerRespawn, _: &mut AirmashGame| {
+      flag.set(true);
+    }
+  ));
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+
+  game.run_for(Duration::from_secs(2));
+  let _ = client.packets().count();
+  client.send_command("respawn", "3");
+  game.run_once();
+
+  assert!(called.get());
+  assert!(!client
+    .packets()
+    .any(|p| matches!(p, ServerPacket::PlayerRespawn(_))));
+}
+
+#[test]
+fn replacing_requires_matching_event() {
+  let (mut game, _) = TestGame::new();
+
+  assert!(!game.replace_handler(SEND_RESPAWN, |_: &u32, _: &mut AirmashGame| ()));
+  assert!(!game.replace_handler(
+    "no::such::handler",
+    |_: &PlayerRespawn, _: &mut AirmashGame| ()
+  ));
+}
+
+#[test]
+fn handlers_for_event_are_in_execution_order() {
+  let (game, _) = TestGame::new();
+
+  let handlers = game.handlers_for::<PlayerRespawn>();
+  assert!(handlers.len() > 1);
+  assert!(handlers
+    .iter()
+    .all(|h| h.event == std::any::type_name::<PlayerRespawn>()));
+  assert!(handlers.windows(2).all(|w| w[0].priority >= w[1].priority));
+
+  let reset = handlers
+    .iter()
+    .find(|h| h.name.ends_with("on_player_respawn::reset_player"))
+    .unwrap();
+  let send = handlers.iter().find(|h| h.name == SEND_RESPAWN).unwrap();
+
+  assert_eq!(reset.priority, airmash::priority::PRE_LOGIN);
+  assert!(send.location.file.ends_with("on_player_respawn.rs"));
+  assert!(reset.location.file.ends_with("on_player_respawn.rs"));
+  assert!(send.location.line > 0);
+  assert!(reset.location.line > send.location.line);
+}
+
+#[test]
+fn manually_registered_handlers_record_their_caller() {
+  struct CustomEvent;
+
+  let (mut game, _) = TestGame::new();
+  game.register(|_: &CustomEvent, _: &mut AirmashGame| ());
+
+  let handlers = game.handlers_for::<CustomEvent>();
+  assert_eq!(handlers.len(), 1);
+  assert!(handlers[0].location.file.ends_with("handlers.rs"));
+}
+
+#[test]
+fn closures_get_unique_names_in_registration_order() {
+  struct CustomEvent;
+
+  let (mut game, _) = TestGame::new();
+  game
+    .register(|_: &CustomEvent, game: &mut AirmashGame| game.resources.write::<Vec<u32>>().push(1));
+  game
+    .register(|_: &CustomEvent, game: &mut AirmashGame| game.resources.write::<Vec<u32>>().push(2));
+  game.resources.insert(Vec::<u32>::new());
+
+  let handlers = game.handlers_for::<CustomEvent>();
+  assert_eq!(handlers.len(), 2);
+  assert_eq!(handlers[1].name, format!("{}#2", handlers[0].name));
+  assert!(handlers[0].location.line < handlers[1].location.line);
+
+  assert!(game.disable_handler(handlers[0].name));
+  game.dispatch(CustomEvent);
+  assert_eq!(*game.resources.read::<Vec<u32>>(), [2]);
+}
+
+#[test]
+fn trace_command_toggles_tracing() {
+  let (mut game, mut mock) = TestGame::new();
+  game
+    .resources
+    .write::<AdminTokens>()
+    .insert("secret", AdminRole::Admin);
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  client.send_command("auth", "secret");
+  client.send_command("trace", "on");
+  game.run_once();
+
+  assert!(game.resources.read::<EventDispatcher>().tracing());
+
+  // Make sure that nothing breaks while tracing events.
+  client.send_command("respawn", "2");
+  game.run_count(3);
+
+  client.send_command("trace", "off");
+  game.run_once();
+  assert!(!game.resources.read::<EventDispatcher>().tracing());
+}
@@ -0,0 +1,131 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use airmash::component::AdminRole;
+use airmash::protocol::{client as c, ErrorType, ServerPacket};
+use airmash::resource::{ConnectionLimits, Limits};
+use airmash::test::TestGame;
+
+fn addr(s: &str) -> SocketAddr {
+  s.parse().unwrap()
+}
+
+fn set_limits(game: &TestGame, limits: Limits) {
+  game.resources.read::<ConnectionLimits>().set(limits);
+}
+
+fn rejected(mut packets: impl Iterator<Item = ServerPacket>) -> bool {
+  packets.any(|p| matches!(p, ServerPacket::Error(e) if e.error == ErrorType::InvalidLogin))
+}
+
+#[test]
+fn logins_per_ip_are_capped() {
+  let (mut game, mut mock) = TestGame::new();
+  set_limits(
+    &game,
+    Limits {
+      logins_per_ip: Some(2),
+      ..Default::default()
+    },
+  );
+
+  mock
+    .open_with_addr(addr("10.0.0.1:1000"))
+    .login("first", &mut game);
+  mock
+    .open_with_addr(addr("10.0.0.1:1001"))
+    .login("second", &mut game);
+
+  let mut third = mock.open_with_addr(addr("10.0.0.1:1002"));
+  third.send_login("third");
+  game.run_once();
+  assert!(rejected(third.packets()));
+
+  // Other addresses are not affected.
+  mock
+    .open_with_addr(addr("10.0.0.2:1000"))
+    .login("other", &mut game);
+
+  // The cap is per minute.
+  game.run_for(Duration::from_secs(61));
+  mock
+    .open_with_addr(addr("10.0.0.1:1003"))
+    .login("fourth", &mut game);
+}
+
+#[test]
+fn invalid_logins_are_not_counted() {
+  let (mut game, mut mock) = TestGame::new();
+  set_limits(
+    &game,
+    Limits {
+      logins_per_ip: Some(1),
+      ..Default::default()
+    },
+  );
+
+  let mut client = mock.open_with_addr(addr("10.0.0.1:1000"));
+  client.send(c::Login {
+    protocol: 4,
+    ..crate::utils::create_login_packet("old")
+  });
+  client.send(crate::utils::create_login_packet(&"x".repeat(41)));
+  game.run_once();
+  assert!(client.packets().any(|p| matches!(
+    p,
+    ServerPacket::Error(e) if e.error == ErrorType::IncorrectProtocol
+  )));
+
+  mock
+    .open_with_addr(addr("10.0.0.1:1001"))
+    .login("valid", &mut game);
+}
+
+#[test]
+fn total_players_are_capped() {
+  let (mut game, mut mock) = TestGame::new();
+  set_limits(
+    &game,
+    Limits {
+      max_players: Some(1),
+      ..Default::default()
+    },
+  );
+
+  let mut first = mock.open();
+  first.login("first", &mut game);
+
+  let mut second = mock.open();
+  second.send_login("second");
+  game.run_once();
+  assert!(rejected(second.packets()));
+
+  first.close();
+  game.run_for(Duration::from_secs(30));
+
+  mock.open().login("third", &mut game);
+}
+
+#[test]
+fn admin_can_change_limits() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut admin = mock.open();
+  let admin_ent = admin.login("admin", &mut game);
+  game.world.insert_one(admin_ent, AdminRole::Admin).unwrap();
+
+  admin.send_command("limit", "players 10");
+  admin.send_command("limit", "connections 3");
+  game.run_once();
+
+  let limits = game.resources.read::<ConnectionLimits>().limits();
+  assert_eq!(limits.max_players, Some(10));
+  assert_eq!(limits.connections_per_ip, Some(3));
+  assert_eq!(limits.logins_per_ip, None);
+
+  admin.send_command("limit", "players none");
+  game.run_once();
+
+  let limits = game.resources.read::<ConnectionLimits>().limits();
+  assert_eq!(limits.max_players, None);
+}
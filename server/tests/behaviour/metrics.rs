@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use airmash::event::PlayerJoin;
+use airmash::resource::Metrics;
+use airmash::test::TestGame;
+
+#[test]
+fn packets_and_events_are_counted() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  game.run_once();
+
+  let metrics = game.resources.read::<Metrics>();
+  assert_eq!(metrics.packets_in.get("Login"), Some(&1));
+  assert_eq!(metrics.packets_out.get("Login"), Some(&1));
+  assert_eq!(
+    metrics.events.get(std::any::type_name::<PlayerJoin>()),
+    Some(&1)
+  );
+}
+
+#[test]
+fn frame_stages_are_timed() {
+  let (mut game, _mock) = TestGame::new();
+  game.run_count(3);
+
+  let metrics = game.resources.read::<Metrics>();
+  assert_eq!(metrics.frame_duration.count(), 3);
+  for stage in ["physics", "collision", "network", "tasks"] {
+    assert_eq!(metrics.stage_duration[stage].count(), 3, "{}", stage);
+  }
+}
+
+#[test]
+fn slow_frames_are_counted_as_overruns() {
+  let (mut game, _mock) = TestGame::new();
+  game.resources.write::<Metrics>().frame_budget = Duration::ZERO;
+  game.run_count(2);
+
+  assert_eq!(game.resources.read::<Metrics>().frame_overruns, 2);
+}
+
+#[test]
+fn render_includes_all_metrics() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  game.run_once();
+
+  let text = game.resources.read::<Metrics>().render();
+  for name in [
+    "airmash_frame_duration_seconds_bucket{le=\"0.016\"}",
+    "airmash_stage_duration_seconds_count{stage=\"physics\"}",
+    "airmash_frame_overruns_total",
+    "airmash_packets_received_total{type=\"Login\"} 1",
+    "airmash_packets_sent_total{type=\"Login\"} 1",
+    "airmash_events_dispatched_total",
+    "airmash_kills_total 0",
+    "airmash_missiles",
+    "airmash_players",
+  ] {
+    assert!(text.contains(name), "missing {} in\n{}", name, text);
+  }
+}
@@ -1,8 +1,25 @@
 mod admin;
+mod bans;
+mod chat;
+mod commands;
+mod config_reload;
+mod demo;
 mod despawn;
+mod handlers;
+mod limits;
+mod metrics;
+mod packet_limits;
 mod powerups;
+mod pre_events;
+mod profiler;
 mod prowler;
+mod replay;
 mod respawn;
+mod resume;
+mod send_queue;
 mod shoot;
+mod snapshot;
 mod upgrades;
 mod visibility;
+mod votemute;
+mod word_filter;
@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use airmash::component::KeyState;
+use airmash::network::{ConnectionMgr, PacketLimits, RateLimit};
+use airmash::protocol::{ErrorType, KeyCode, ServerPacket};
+use airmash::test::TestGame;
+
+fn set_limits(game: &mut TestGame, limits: PacketLimits) {
+  game
+    .resources
+    .write::<ConnectionMgr>()
+    .set_packet_limits(limits);
+}
+
+#[test]
+fn packets_over_limit_are_dropped() {
+  let (mut game, mut mock) = TestGame::new();
+  set_limits(
+    &mut game,
+    PacketLimits {
+      key: Some(RateLimit::new(1, 0.001)),
+      ..PacketLimits::unlimited()
+    },
+  );
+
+  let mut client = mock.open();
+  let ent = client.login("test", &mut game);
+
+  client.send_key(KeyCode::Up, true);
+  client.send_key(KeyCode::Up, false);
+  game.run_once();
+
+  // The second key packet should have been dropped.
+  assert!(game.world.get::<KeyState>(ent).unwrap().up);
+}
+
+#[test]
+fn flooding_connection_is_disconnected() {
+  let (mut game, mut mock) = TestGame::new();
+  set_limits(
+    &mut game,
+    PacketLimits {
+      command: Some(RateLimit::new(1, 0.001)),
+      flood_threshold: 10,
+      flood_window: Duration::from_secs(60),
+      ..PacketLimits::unlimited()
+    },
+  );
+
+  let mut client = mock.open();
+  let ent = client.login("test", &mut game);
+  let _ = client.packets().count();
+
+  for _ in 0..20 {
+    client.send_command("flag", "CA");
+  }
+  game.run_once();
+
+  assert!(!game.world.contains(ent));
+  assert!(client.packets().any(|p| matches!(
+    p,
+    ServerPacket::Error(e) if e.error == ErrorType::PacketFloodingDisconnect
+  )));
+}
+
+#[test]
+fn categories_are_limited_separately() {
+  let (mut game, mut mock) = TestGame::new();
+  set_limits(
+    &mut game,
+    PacketLimits {
+      command: Some(RateLimit::new(1, 0.001)),
+      ..PacketLimits::unlimited()
+    },
+  );
+
+  let mut client = mock.open();
+  let ent = client.login("test", &mut game);
+
+  client.send_command("flag", "CA");
+  client.send_command("flag", "US");
+  client.send_key(KeyCode::Up, true);
+  game.run_once();
+
+  assert!(game.world.get::<KeyState>(ent).unwrap().up);
+}
+
+#[test]
+fn malformed_packets_count_against_their_opcode() {
+  let (mut game, mut mock) = TestGame::new();
+  set_limits(
+    &mut game,
+    PacketLimits {
+      chat: Some(RateLimit::new(1, 0.001)),
+      flood_threshold: 10,
+      flood_window: Duration::from_secs(60),
+      ..PacketLimits::unlimited()
+    },
+  );
+
+  let mut client = mock.open();
+  let ent = client.login("test", &mut game);
+
+  // A truncated chat packet.
+  for _ in 0..20 {
+    client.send_raw(vec![20]);
+  }
+  game.run_once();
+
+  assert!(!game.world.contains(ent));
+}
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use airmash::component::*;
+use airmash::config::PlanePrototypeRef;
+use airmash::event::{
+  BeforePlayerFire, BeforePlayerHit, BeforePowerup, BeforeRespawn, PlayerMissileCollision,
+};
+use airmash::protocol::{KeyCode, ServerPacket};
+use airmash::resource::Config;
+use airmash::test::TestGame;
+use airmash::util::NalgebraExt;
+use airmash::{AirmashGame, Entity, FireMissileInfo, Vector2};
+use smallvec::smallvec;
+
+/// Have `attacker` fire a missile and hit `target` with it.
+fn hit(game: &mut TestGame, attacker: Entity, target: Entity) {
+  let proto = game.resources.read::<Config>().missiles["predator"];
+  let missiles = game
+    .fire_missiles(
+      attacker,
+      &[FireMissileInfo {
+        pos_offset: Vector2::zeros(),
+        rot_offset: 0.0,
+        proto,
+      }],
+    )
+    .unwrap();
+
+  game.dispatch(PlayerMissileCollision {
+    missile: missiles[0],
+    players: smallvec![target],
+  });
+}
+
+#[test]
+fn hit_damage_can_be_changed() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let attacker = mock.open().login("attacker", &mut game);
+  let target = mock.open().login("target", &mut game);
+
+  game.register_pre(|event: &mut BeforePlayerHit, _: &mut AirmashGame| {
+    event.damage = 0.25;
+  });
+  hit(&mut game, attacker, target);
+
+  assert_eq!(game.world.get::<Health>(target).unwrap().0, 0.75);
+}
+
+#[test]
+fn cancelled_hit_does_no_damage() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let attacker = mock.open().login("attacker", &mut game);
+  let target = mock.open().login("target", &mut game);
+
+  game.register_pre(|event: &mut BeforePlayerHit, _: &mut AirmashGame| {
+    event.cancelled = true;
+  });
+  hit(&mut game, attacker, target);
+
+  assert_eq!(game.world.get::<Health>(target).unwrap().0, 1.0);
+}
+
+#[test]
+fn cancelled_shot_fires_nothing_and_costs_no_energy() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  let player = client.login("test", &mut game);
+  game.register_pre(|event: &mut BeforePlayerFire, _: &mut AirmashGame| {
+    event.cancelled = true;
+  });
+
+  game.run_for(Duration::from_secs(1));
+  let _ = client.packets().count();
+
+  client.send_key(KeyCode::Fire, true);
+  game.run_once();
+
+  assert!(!client
+    .packets()
+    .any(|p| matches!(p, ServerPacket::PlayerFire(_))));
+  assert_eq!(game.world.get::<Energy>(player).unwrap().0, 1.0);
+}
+
+#[test]
+fn respawn_can_be_redirected() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  let player = client.login("test", &mut game);
+  game.register_pre(|event: &mut BeforeRespawn, _: &mut AirmashGame| {
+    event.pos = Some(Vector2::new(1000.0, -500.0));
+  });
+
+  game.run_for(Duration::from_secs(2));
+  client.send_command("respawn", "1");
+  game.run_once();
+
+  assert_eq!(
+    game.world.get::<Position>(player).unwrap().0,
+    Vector2::new(1000.0, -500.0)
+  );
+}
+
+#[test]
+fn cancelled_respawn_keeps_plane() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  let player = client.login("test", &mut game);
+  game.register_pre(|event: &mut BeforeRespawn, _: &mut AirmashGame| {
+    event.cancelled = true;
+  });
+
+  game.run_for(Duration::from_secs(2));
+  let _ = client.packets().count();
+
+  client.send_command("respawn", "3");
+  game.run_once();
+
+  assert!(!client
+    .packets()
+    .any(|p| matches!(p, ServerPacket::PlayerRespawn(_))));
+  let plane = *game.world.get::<PlanePrototypeRef>(player).unwrap();
+  assert_eq!(plane.name, "predator");
+}
+
+#[test]
+fn cancelled_powerup_stays_on_the_map() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let player = mock.open().login("test", &mut game);
+  game.register_pre(|event: &mut BeforePowerup, _: &mut AirmashGame| {
+    event.cancelled = true;
+  });
+
+  // Let the spawn shield expire.
+  game.run_for(Duration::from_secs(3));
+
+  let powerup = game.spawn_mob(MobType::Inferno, Vector2::zeros(), Duration::from_secs(60));
+  game.world.get_mut::<Position>(player).unwrap().0 = Vector2::zeros();
+  game.run_once();
+
+  assert!(game.world.contains(powerup));
+  assert!(game
+    .world
+    .get::<Effects>(player)
+    .unwrap()
+    .powerup()
+    .is_none());
+}
@@ -0,0 +1,160 @@
+use airmash::component::AdminRole;
+use airmash::protocol::ServerPacket;
+use airmash::resource::{AdminTokens, OutputDirs, Profiler};
+use airmash::test::TestGame;
+
+fn command_replies(client: &mut airmash::test::MockConnection) -> Vec<String> {
+  client
+    .packets()
+    .filter_map(|packet| match packet {
+      ServerPacket::CommandReply(reply) => Some(reply.text.to_string()),
+      _ => None,
+    })
+    .collect()
+}
+
+#[test]
+fn profiler_is_disabled_by_default() {
+  let (mut game, _mock) = TestGame::new();
+  game.run_count(3);
+
+  let profiler = game.resources.read::<Profiler>();
+  assert!(!profiler.enabled());
+  assert_eq!(profiler.frames(), 0);
+  assert_eq!(profiler.stages().count(), 0);
+}
+
+#[test]
+fn profiler_records_stages_and_handlers() {
+  let (mut game, mut mock) = TestGame::new();
+  game.resources.write::<Profiler>().start();
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  game.run_count(2);
+
+  let profiler = game.resources.read::<Profiler>();
+  assert_eq!(profiler.frames(), 3);
+  assert!(profiler
+    .handler("airmash::system::handler::on_player_join::send_login_packet")
+    .is_some());
+
+  let physics = profiler
+    .stages()
+    .find(|(name, _)| *name == "physics")
+    .map(|(_, stats)| stats.calls);
+  assert_eq!(physics, Some(3));
+
+  // Handlers are identified by their path.
+  let handlers = profiler.slowest_handlers(usize::MAX);
+  assert!(!handlers.is_empty());
+  assert!(handlers.iter().all(|(name, _)| name.starts_with("airmash")));
+}
+
+#[test]
+fn profile_command_reports_results() {
+  let (mut game, mut mock) = TestGame::new();
+  game
+    .resources
+    .write::<AdminTokens>()
+    .insert("secret", AdminRole::Admin);
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  client.send_command("auth", "secret");
+  client.send_command("profile", "start");
+  game.run_once();
+
+  assert!(game.resources.read::<Profiler>().enabled());
+
+  game.run_count(5);
+  let _ = client.packets().count();
+
+  client.send_command("profile", "report 3");
+  game.run_once();
+
+  let replies = command_replies(&mut client);
+  assert_eq!(replies.len(), 1, "{:?}", replies);
+  assert!(replies[0].contains("Stages:"), "{}", replies[0]);
+  assert!(replies[0].contains("physics"), "{}", replies[0]);
+  assert!(replies[0].contains("Slowest handlers:"), "{}", replies[0]);
+
+  client.send_command("profile", "stop");
+  game.run_once();
+  assert!(!game.resources.read::<Profiler>().enabled());
+}
+
+#[test]
+fn profile_command_dumps_to_file() {
+  let (mut game, mut mock) = TestGame::new();
+  game
+    .resources
+    .write::<AdminTokens>()
+    .insert("secret", AdminRole::Admin);
+
+  game.resources.write::<OutputDirs>().profiles = Some(std::env::temp_dir());
+  let name = format!("airmash-profile-{}.txt", std::process::id());
+  let path = std::env::temp_dir().join(&name);
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  client.send_command("auth", "secret");
+  client.send_command("profile", "start");
+  game.run_count(3);
+
+  client.send_command("profile", &format!("dump {}", name));
+  game.run_once();
+
+  let report = std::fs::read_to_string(&path).unwrap();
+  let _ = std::fs::remove_file(&path);
+  assert!(report.contains("Slowest handlers:"));
+}
+
+#[test]
+fn profile_dump_only_accepts_file_names() {
+  let (mut game, mut mock) = TestGame::new();
+  game
+    .resources
+    .write::<AdminTokens>()
+    .insert("secret", AdminRole::Admin);
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  client.send_command("auth", "secret");
+  game.run_once();
+
+  // Disabled until a directory has been configured.
+  client.send_command("profile", "dump report.txt");
+  game.run_once();
+  assert!(!command_replies(&mut client)
+    .iter()
+    .any(|reply| reply.starts_with("Wrote")));
+
+  let dir = std::env::temp_dir().join(format!("airmash-profiles-{}", std::process::id()));
+  std::fs::create_dir_all(dir.join("inner")).unwrap();
+  game.resources.write::<OutputDirs>().profiles = Some(dir.join("inner"));
+
+  for name in ["../escaped.txt", "..", "sub/report.txt", "sub\\report.txt"] {
+    client.send_command("profile", &format!("dump {}", name));
+  }
+  game.run_once();
+
+  let escaped = dir.join("escaped.txt").exists();
+  let _ = std::fs::remove_dir_all(&dir);
+  assert!(!escaped);
+  assert!(!command_replies(&mut client)
+    .iter()
+    .any(|reply| reply.starts_with("Wrote")));
+}
+
+#[test]
+fn profile_command_requires_admin() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  client.send_command("profile", "start");
+  game.run_once();
+
+  assert!(!game.resources.read::<Profiler>().enabled());
+}
@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use airmash::component::{Health, IsPlayer, Name, Position, Score};
+use airmash::network::{ConnectionMgr, QueueLimits, Replay};
+use airmash::protocol::KeyCode;
+use airmash::resource::{Config, GameConfig, GameRng};
+use airmash::test::TestGame;
+use airmash::AirmashGame;
+
+use crate::utils::temp_path;
+
+/// A snapshot of the state of every player in the game.
+fn players(game: &mut AirmashGame) -> Vec<(u32, String, [u32; 2], u32, u32)> {
+  let mut players: Vec<_> = game
+    .world
+    .query_mut::<(&Name, &Position, &Score, &Health)>()
+    .with::<IsPlayer>()
+    .into_iter()
+    .map(|(ent, (name, pos, score, health))| {
+      (
+        ent.id(),
+        name.0.to_string(),
+        [pos.x.to_bits(), pos.y.to_bits()],
+        score.0,
+        health.0.to_bits(),
+      )
+    })
+    .collect();
+  players.sort();
+  players
+}
+
+#[test]
+fn replay_reproduces_session() {
+  let path = temp_path("replay.rec");
+  let (mut game, mut mock) = TestGame::new();
+  game.record_session(&path).unwrap();
+
+  let mut alice = mock.open();
+  let mut bob = mock.open();
+  alice.login("test", &mut game);
+  // Duplicate names are disambiguated with a random suffix.
+  bob.login("test", &mut game);
+
+  alice.send_key(KeyCode::Up, true);
+  bob.send_key(KeyCode::Right, true);
+  game.run_for(Duration::from_millis(500));
+
+  bob.send_key(KeyCode::Fire, true);
+  alice.send_command("respawn", "2");
+  game.run_for(Duration::from_secs(2));
+
+  alice.close();
+  game.run_for(Duration::from_millis(100));
+
+  let expected = players(&mut game);
+  drop(game);
+
+  let replay = Replay::load(&path).unwrap();
+  let _ = std::fs::remove_file(&path);
+
+  let mut replayed = AirmashGame::with_test_defaults();
+  replayed
+    .resources
+    .insert(Config::new(Default::default()).unwrap());
+  replay.run(&mut replayed);
+
+  assert_eq!(replayed.resources.read::<GameRng>().seed(), replay.seed());
+  assert_eq!(players(&mut replayed), expected);
+}
+
+#[test]
+fn replay_reproduces_backlogged_client() {
+  let path = temp_path("backlogged.rec");
+  let (mut game, mut mock) = TestGame::new();
+  game.resources.write::<GameConfig>().resume_window = Duration::from_secs(60);
+  game
+    .resources
+    .write::<ConnectionMgr>()
+    .set_queue_limits(QueueLimits {
+      high_water_mark: 1,
+      max_queued: 10_000,
+      backlog_timeout: Duration::from_secs(1),
+    });
+  game.record_session(&path).unwrap();
+
+  let mut stalled = mock.open();
+  let mut active = mock.open();
+  let stalled_ent = stalled.login("stalled", &mut game);
+  active.login("active", &mut game);
+
+  for _ in 0..120 {
+    let _ = active.packets().count();
+    game.run_once();
+  }
+
+  // The server closed the connection itself so the player doesn't get to
+  // resume their session.
+  assert!(!game.world.contains(stalled_ent));
+  let expected = players(&mut game);
+  drop(game);
+
+  let replay = Replay::load(&path).unwrap();
+  let _ = std::fs::remove_file(&path);
+
+  let mut replayed = AirmashGame::with_test_defaults();
+  replayed
+    .resources
+    .insert(Config::new(Default::default()).unwrap());
+  replayed.resources.write::<GameConfig>().resume_window = Duration::from_secs(60);
+  replay.run(&mut replayed);
+
+  assert_eq!(players(&mut replayed), expected);
+}
+
+#[test]
+fn truncated_recording_can_be_loaded() {
+  let path = temp_path("truncated.rec");
+  let (mut game, mut mock) = TestGame::new();
+  game.record_session(&path).unwrap();
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  game.run_count(10);
+  drop(game);
+
+  let full = Replay::load(&path).unwrap();
+  let data = std::fs::read(&path).unwrap();
+  std::fs::write(&path, &data[..data.len() - 3]).unwrap();
+  let truncated = Replay::load(&path).unwrap();
+  let _ = std::fs::remove_file(&path);
+
+  // One frame for the login and then the ten that were run afterwards.
+  assert_eq!(full.frames().len(), 11);
+  assert_eq!(truncated.frames().len(), 10);
+}
+
+#[test]
+fn invalid_recording_is_rejected() {
+  let path = temp_path("invalid.rec");
+  std::fs::write(&path, b"not a recording").unwrap();
+
+  assert!(Replay::load(&path).is_err());
+  let _ = std::fs::remove_file(&path);
+}
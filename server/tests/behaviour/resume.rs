@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use airmash::component::*;
+use airmash::protocol::{client as c, ErrorType, ServerPacket};
+use airmash::resource::GameConfig;
+use airmash::test::TestGame;
+
+#[test]
+fn dropped_player_leaves_after_resume_window() {
+  let (mut game, mut mock) = TestGame::new();
+  game.resources.write::<GameConfig>().resume_window = Duration::from_secs(1);
+
+  let mut client = mock.open();
+  let player = client.login("test", &mut game);
+
+  client.close();
+  game.run_once();
+  assert!(game.world.get::<IsPlayer>(player).is_ok());
+
+  game.run_for(Duration::from_secs(2));
+  assert!(game.world.get::<IsPlayer>(player).is_err());
+}
+
+#[test]
+fn backup_resumes_dropped_session() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  let player = client.login("test", &mut game);
+  let session = game.world.get::<Session>(player).unwrap().0;
+  game.world.get_mut::<Score>(player).unwrap().0 = 500;
+
+  client.close();
+  game.run_once();
+
+  let mut resumed = mock.open();
+  resumed.send(c::Backup {
+    token: session.to_string().into(),
+  });
+  game.run_once();
+
+  assert_eq!(resumed.wait_for_login(&mut game), player);
+  assert!(resumed.packets().any(|p| matches!(
+    p,
+    ServerPacket::ScoreUpdate(s) if s.score == 500
+  )));
+  assert!(game.world.get::<DisconnectDeadline>(player).is_err());
+
+  game.run_for(Duration::from_secs(20));
+  assert!(game.world.get::<IsPlayer>(player).is_ok());
+}
+
+#[test]
+fn dropped_player_releases_keys() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  let player = client.login("test", &mut game);
+  client.send_key(airmash::protocol::KeyCode::Up, true);
+  game.run_once();
+  assert!(game.world.get::<KeyState>(player).unwrap().up);
+
+  client.close();
+  game.run_once();
+  assert!(!game.world.get::<KeyState>(player).unwrap().up);
+}
+
+#[test]
+fn backup_with_unknown_session_is_rejected() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  client.send(c::Backup {
+    token: "00000000-0000-0000-0000-000000000000".into(),
+  });
+  game.run_once();
+
+  assert!(client.packets().any(|p| matches!(
+    p,
+    ServerPacket::Error(e) if e.error == ErrorType::InvalidLogin
+  )));
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use airmash::network::{ConnectionMgr, QueueLimits};
+use airmash::protocol::KeyCode;
+use airmash::resource::ServerStats;
+use airmash::test::TestGame;
+
+fn set_limits(game: &mut TestGame, limits: QueueLimits) {
+  game
+    .resources
+    .write::<ConnectionMgr>()
+    .set_queue_limits(limits);
+}
+
+#[test]
+fn queue_depth_is_reported_in_stats() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  game.run_once();
+
+  let depth = game.resources.read::<ServerStats>().max_queue_depth;
+  assert!(depth > 0);
+
+  let _ = client.packets().count();
+  game.run_once();
+
+  let stats = game.resources.read::<ServerStats>();
+  assert!(stats.max_queue_depth < depth);
+}
+
+#[test]
+fn backlogged_client_is_disconnected() {
+  let (mut game, mut mock) = TestGame::new();
+  set_limits(
+    &mut game,
+    QueueLimits {
+      high_water_mark: 1,
+      max_queued: 10_000,
+      backlog_timeout: Duration::from_secs(1),
+    },
+  );
+
+  let mut stalled = mock.open();
+  let mut active = mock.open();
+  let stalled_ent = stalled.login("stalled", &mut game);
+  let active_ent = active.login("active", &mut game);
+
+  for _ in 0..120 {
+    let _ = active.packets().count();
+    game.run_once();
+  }
+
+  assert!(!game.world.contains(stalled_ent));
+  assert!(game.world.contains(active_ent));
+}
+
+#[test]
+fn client_over_max_queued_is_disconnected() {
+  let (mut game, mut mock) = TestGame::new();
+  set_limits(
+    &mut game,
+    QueueLimits {
+      high_water_mark: 1,
+      max_queued: 4,
+      backlog_timeout: Duration::MAX,
+    },
+  );
+
+  let mut client = mock.open();
+  client.send_login("test");
+  game.run_count(2);
+
+  assert_eq!(game.resources.read::<ServerStats>().num_players, 0);
+}
+
+#[test]
+fn player_updates_are_dropped_under_pressure() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut stalled = mock.open();
+  let mut active = mock.open();
+  stalled.login("stalled", &mut game);
+  active.login("active", &mut game);
+
+  set_limits(
+    &mut game,
+    QueueLimits {
+      high_water_mark: 1,
+      max_queued: 10_000,
+      backlog_timeout: Duration::MAX,
+    },
+  );
+
+  for i in 0..10 {
+    active.send_key(KeyCode::Up, i % 2 == 0);
+    game.run_once();
+  }
+
+  assert!(game.resources.read::<ServerStats>().dropped_messages > 0);
+}
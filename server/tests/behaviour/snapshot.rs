@@ -0,0 +1,213 @@
+use std::time::Duration;
+
+use airmash::component::*;
+use airmash::protocol::{client as c, MobType};
+use airmash::resource::{AdminTokens, Config, GameConfig, OutputDirs, TakenNames};
+use airmash::snapshot::Snapshot;
+use airmash::test::{MockConnection, TestGame};
+use airmash::Vector2;
+
+use crate::utils::temp_path;
+
+fn login_with_session(conn: &mut MockConnection, name: &str, session: &str, game: &mut TestGame) {
+  conn.send(c::Login {
+    protocol: 5,
+    session: session.into(),
+    name: name.into(),
+    horizon_x: 4000,
+    horizon_y: 4000,
+    flag: "UN".into(),
+  });
+  game.run_once();
+}
+
+#[test]
+fn players_are_reattached_by_session() {
+  let path = temp_path("players.json");
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  let alice = client.login("alice", &mut game);
+  game.update_score(alice, 250).unwrap();
+  game.world.get_mut::<Team>(alice).unwrap().0 = 7;
+  game.world.get_mut::<Position>(alice).unwrap().0 = Vector2::new(100.0, -200.0);
+  let session = game.world.get::<Session>(alice).unwrap().0;
+  game.run_once();
+
+  game.save_snapshot(&path).unwrap();
+  drop(client);
+  drop(game);
+
+  let snapshot = Snapshot::load(&path).unwrap();
+  let _ = std::fs::remove_file(&path);
+  assert_eq!(snapshot.players.len(), 1);
+
+  let (mut game, mut mock) = TestGame::new();
+  game.run_once();
+  game.restore_snapshot(&snapshot).unwrap();
+  game.run_once();
+
+  // Other players can't take the name of a player who hasn't reconnected yet.
+  let mut other = mock.open();
+  let bob = other.login("alice", &mut game);
+  assert_ne!(&game.world.get::<Name>(bob).unwrap().0[..], b"alice");
+
+  let mut client = mock.open();
+  login_with_session(&mut client, "someone-else", &session.to_string(), &mut game);
+  let restored = client.wait_for_login(&mut game);
+
+  assert_eq!(restored, alice);
+  assert_eq!(&game.world.get::<Name>(alice).unwrap().0[..], b"alice");
+  assert_eq!(game.world.get::<Score>(alice).unwrap().0, 250);
+  assert_eq!(game.world.get::<Team>(alice).unwrap().0, 7);
+  assert_eq!(game.world.get::<Session>(alice).unwrap().0, session);
+  assert_eq!(
+    game.world.get::<Position>(alice).unwrap().0,
+    Vector2::new(100.0, -200.0)
+  );
+}
+
+#[test]
+fn invalid_login_does_not_claim_restored_player() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  let alice = client.login("alice", &mut game);
+  let session = game.world.get::<Session>(alice).unwrap().0;
+  let snapshot = game.snapshot();
+  drop(client);
+  drop(game);
+
+  let (mut game, mut mock) = TestGame::new();
+  game.run_once();
+  game.restore_snapshot(&snapshot).unwrap();
+  game.run_once();
+
+  let mut client = mock.open();
+  client.send(c::Login {
+    protocol: 4,
+    session: session.to_string().into(),
+    name: "alice".into(),
+    horizon_x: 4000,
+    horizon_y: 4000,
+    flag: "UN".into(),
+  });
+  game.run_once();
+
+  let mut client = mock.open();
+  login_with_session(&mut client, "alice", &session.to_string(), &mut game);
+  assert_eq!(client.wait_for_login(&mut game), alice);
+}
+
+#[test]
+fn unclaimed_players_are_released() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  let alice = client.login("alice", &mut game);
+  let session = game.world.get::<Session>(alice).unwrap().0;
+  let snapshot = game.snapshot();
+  drop(client);
+  drop(game);
+
+  let (mut game, mut mock) = TestGame::new();
+  game.resources.write::<GameConfig>().reconnect_window = Duration::from_secs(1);
+  game.run_once();
+  game.restore_snapshot(&snapshot).unwrap();
+  game.run_for(Duration::from_secs(2));
+
+  let mut client = mock.open();
+  login_with_session(&mut client, "alice", &session.to_string(), &mut game);
+  let player = client.wait_for_login(&mut game);
+
+  // The player gets a new session and the name is free to be used again.
+  assert_ne!(game.world.get::<Session>(player).unwrap().0, session);
+  assert_eq!(&game.world.get::<Name>(player).unwrap().0[..], b"alice");
+  assert_eq!(game.resources.read::<TakenNames>().len(), 1);
+}
+
+#[test]
+fn mobs_and_missiles_are_restored() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  let player = client.login("test", &mut game);
+  game.run_once();
+
+  let mob = game.spawn_mob(
+    MobType::Shield,
+    Vector2::new(500.0, 500.0),
+    Duration::from_secs(60),
+  );
+  let proto = game.resources.read::<Config>().missiles["predator"];
+  let missile = game.fire_missiles_count(player, 1, proto).unwrap()[0];
+  game.run_once();
+
+  let snapshot = game.snapshot();
+  assert_eq!(snapshot.mobs.len(), 1);
+  assert_eq!(snapshot.missiles.len(), 1);
+  drop(client);
+  drop(game);
+
+  let (mut game, _mock) = TestGame::new();
+  game.run_once();
+  game.restore_snapshot(&snapshot).unwrap();
+
+  assert_eq!(*game.world.get::<MobType>(mob).unwrap(), MobType::Shield);
+  assert_eq!(
+    game.world.get::<Position>(mob).unwrap().0,
+    Vector2::new(500.0, 500.0)
+  );
+  assert!(game.world.get::<IsMissile>(missile).is_ok());
+  assert_eq!(game.world.get::<Owner>(missile).unwrap().0, player);
+
+  game.run_once();
+  assert!(game.world.contains(mob));
+}
+
+#[test]
+fn snapshot_command_only_writes_snapshots_to_its_directory() {
+  let (mut game, mut mock) = TestGame::new();
+  {
+    let mut tokens = game.resources.write::<AdminTokens>();
+    tokens.insert("admin", AdminRole::Admin);
+    tokens.insert("owner", AdminRole::Owner);
+  }
+
+  let dir = temp_path("snapshots");
+  std::fs::create_dir_all(dir.join("inner")).unwrap();
+  std::fs::write(dir.join("inner/notes.txt"), "not a snapshot").unwrap();
+  game.resources.write::<OutputDirs>().snapshots = Some(dir.join("inner"));
+
+  let mut admin = mock.open();
+  admin.login("admin", &mut game);
+  admin.send_command("auth", "admin");
+  admin.send_command("snapshot", "by-admin.json");
+
+  let mut owner = mock.open();
+  owner.login("owner", &mut game);
+  owner.send_command("auth", "owner");
+  for name in [
+    "../escaped.json",
+    "sub/saved.json",
+    "notes.txt",
+    "saved.json",
+  ] {
+    owner.send_command("snapshot", name);
+  }
+  game.run_once();
+  // Older snapshots can be replaced.
+  owner.send_command("snapshot", "saved.json");
+  game.run_once();
+
+  let by_admin = dir.join("inner/by-admin.json").exists();
+  let escaped = dir.join("escaped.json").exists();
+  let notes = std::fs::read_to_string(dir.join("inner/notes.txt")).unwrap();
+  let saved = Snapshot::load(dir.join("inner/saved.json"));
+  let _ = std::fs::remove_dir_all(&dir);
+
+  assert!(!by_admin);
+  assert!(!escaped);
+  assert_eq!(notes, "not a snapshot");
+  assert_eq!(saved.unwrap().players.len(), 2);
+}
@@ -0,0 +1,62 @@
+use airmash::component::IsMuted;
+use airmash::protocol::{client as c, ServerPacket};
+use airmash::test::TestGame;
+
+#[test]
+fn player_is_muted_after_enough_votes() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut target = mock.open();
+  let mut voter1 = mock.open();
+  let mut voter2 = mock.open();
+
+  let target_ent = target.login("target", &mut game);
+  voter1.login("voter1", &mut game);
+  voter2.login("voter2", &mut game);
+
+  // With 3 players in the game 2 votes are required to mute someone.
+  voter1.send(c::VoteMute {
+    id: target_ent.id() as _,
+  });
+  game.run_once();
+  assert!(!game.world.get::<IsMuted>(target_ent).unwrap().0);
+
+  voter2.send(c::VoteMute {
+    id: target_ent.id() as _,
+  });
+  game.run_once();
+  assert!(game.world.get::<IsMuted>(target_ent).unwrap().0);
+
+  assert!(voter2
+    .packets()
+    .any(|p| matches!(p, ServerPacket::ChatVoteMutePassed(_))));
+  assert!(target
+    .packets()
+    .any(|p| matches!(p, ServerPacket::ChatVoteMuted)));
+}
+
+#[test]
+fn muted_player_cannot_chat() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut client = mock.open();
+  let mut other = mock.open();
+  let ent = client.login("test", &mut game);
+  other.login("other", &mut game);
+
+  game.world.get_mut::<IsMuted>(ent).unwrap().0 = true;
+  let _ = other.packets().count();
+  let _ = client.packets().count();
+
+  client.send(c::Chat {
+    text: "spam".into(),
+  });
+  game.run_once();
+
+  assert!(!other
+    .packets()
+    .any(|p| matches!(p, ServerPacket::ChatPublic(_))));
+  assert!(client
+    .packets()
+    .any(|p| matches!(p, ServerPacket::ChatVoteMuted)));
+}
@@ -0,0 +1,129 @@
+use airmash::component::{AdminRole, Name};
+use airmash::network::ConnectionMgr;
+use airmash::protocol::{client as c, ServerPacket};
+use airmash::resource::{FilterAction, WordFilter};
+use airmash::test::TestGame;
+
+fn received_chat(packets: impl Iterator<Item = ServerPacket>) -> Vec<String> {
+  packets
+    .filter_map(|p| match p {
+      ServerPacket::ChatPublic(chat) => Some(chat.text.to_string()),
+      _ => None,
+    })
+    .collect()
+}
+
+#[test]
+fn word_filter_parse() {
+  let filter = WordFilter::parse(
+    "
+    # Comments and empty lines are ignored
+
+    mask darn
+    replace heck gosh
+    drop re:buy\\s+gold
+    ",
+  )
+  .unwrap();
+
+  assert_eq!(
+    filter.filter(b"Darn it, heck!").as_deref(),
+    Some(&b"**** it, gosh!"[..])
+  );
+  assert_eq!(
+    filter.filter(b"darnation").as_deref(),
+    Some(&b"darnation"[..])
+  );
+  assert_eq!(filter.filter(b"buy  gold now"), None);
+  // Regex rules are case-sensitive unless they say otherwise.
+  assert_eq!(
+    filter.filter(b"buy GOLD now").as_deref(),
+    Some(&b"buy GOLD now"[..])
+  );
+
+  assert!(WordFilter::parse("mask").is_err());
+  assert!(WordFilter::parse("replace word").is_err());
+  assert!(WordFilter::parse("censor word").is_err());
+  assert!(WordFilter::parse("drop re:(").is_err());
+}
+
+#[test]
+fn chat_is_filtered() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut filter = WordFilter::new();
+  filter.add_word("darn", FilterAction::Mask);
+  filter.add_word("spam", FilterAction::Drop);
+  game.resources.insert(filter);
+
+  let mut client = mock.open();
+  client.login("test", &mut game);
+  let _ = client.packets().count();
+
+  client.send(c::Chat {
+    text: "darn it".into(),
+  });
+  client.send(c::Chat {
+    text: "spam spam spam".into(),
+  });
+  game.run_once();
+
+  assert_eq!(received_chat(client.packets()), vec!["**** it".to_owned()]);
+}
+
+#[test]
+fn login_name_is_filtered() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let mut filter = WordFilter::new();
+  filter.add_word("darn", FilterAction::Replace("nice".into()));
+  filter.add_word("spam", FilterAction::Drop);
+  game.resources.insert(filter);
+
+  let mut client = mock.open();
+  let ent = client.login("darn player", &mut game);
+  assert_eq!(game.world.get::<Name>(ent).unwrap().0, "nice player");
+
+  let mut client = mock.open();
+  client.send_login("spam");
+  game.run_once();
+
+  assert!(client
+    .packets()
+    .any(|p| matches!(p, ServerPacket::Error(_))));
+  assert!(game
+    .resources
+    .read::<ConnectionMgr>()
+    .socket_addr(client.conn())
+    .is_none());
+}
+
+#[test]
+fn admin_can_reload_filter() {
+  let path = std::env::temp_dir().join(format!("airmash-filter-{}.txt", std::process::id()));
+  std::fs::write(&path, "mask darn\n").unwrap();
+
+  let (mut game, mut mock) = TestGame::new();
+  game.resources.insert(WordFilter::load(&path).unwrap());
+
+  let mut client = mock.open();
+  let ent = client.login("test", &mut game);
+  game.world.insert_one(ent, AdminRole::Admin).unwrap();
+
+  std::fs::write(&path, "mask heck\n").unwrap();
+  client.send_command("reload-filter", "");
+  game.run_once();
+  let _ = client.packets().count();
+
+  client.send(c::Chat {
+    text: "darn heck".into(),
+  });
+  game.run_once();
+
+  std::fs::remove_file(&path).unwrap();
+
+  assert_eq!(
+    received_chat(client.packets()),
+    vec!["darn ****".to_owned()]
+  );
+}
@@ -0,0 +1,72 @@
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use airmash::resource::{ConnectionLimits, Limits};
+
+use crate::utils::{connect, start_server, upgrade};
+
+mod utils;
+
+#[test]
+fn connections_per_ip_are_capped() {
+  let (game, addr) = start_server();
+  game.resources.read::<ConnectionLimits>().set(Limits {
+    connections_per_ip: Some(1),
+    ..Default::default()
+  });
+
+  let mut first = connect(addr);
+  assert!(upgrade(&mut first, "").starts_with("HTTP/1.1 101"));
+
+  let mut second = connect(addr);
+  assert!(upgrade(&mut second, "").starts_with("HTTP/1.0 429"));
+
+  let limits = game.resources.read::<ConnectionLimits>().clone();
+  assert_eq!(limits.connections("127.0.0.1".parse().unwrap()), 1);
+
+  // Closing the first connection frees up its slot.
+  drop(first);
+  let mut attempts = 0;
+  while limits.connections("127.0.0.1".parse().unwrap()) != 0 {
+    assert!(attempts < 50, "connection was never closed");
+    attempts += 1;
+    std::thread::sleep(Duration::from_millis(20));
+  }
+
+  let mut third = connect(addr);
+  assert!(upgrade(&mut third, "").starts_with("HTTP/1.1 101"));
+}
+
+#[test]
+fn unfinished_handshakes_count_towards_the_limit() {
+  let (game, addr) = start_server();
+  game.resources.read::<ConnectionLimits>().set(Limits {
+    connections_per_ip: Some(1),
+    ..Default::default()
+  });
+
+  // Never sends a request.
+  let _idle = connect(addr);
+  let limits = game.resources.read::<ConnectionLimits>().clone();
+  let mut attempts = 0;
+  while limits.connections("127.0.0.1".parse().unwrap()) != 1 {
+    assert!(attempts < 50, "connection was never counted");
+    attempts += 1;
+    std::thread::sleep(Duration::from_millis(20));
+  }
+
+  let mut second = connect(addr);
+  assert!(upgrade(&mut second, "").starts_with("HTTP/1.0 429"));
+}
+
+#[test]
+fn oversized_requests_are_rejected() {
+  let (_game, addr) = start_server();
+
+  let mut stream = connect(addr);
+  write!(stream, "GET / HTTP/1.1\r\nX-Padding: {}", "a".repeat(16384)).unwrap();
+
+  let mut response = String::new();
+  stream.read_to_string(&mut response).unwrap();
+  assert!(response.starts_with("HTTP/1.0 431"));
+}
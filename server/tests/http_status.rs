@@ -0,0 +1,78 @@
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use airmash::resource::{RegionName, TeamScores};
+use airmash::AirmashGame;
+
+mod utils;
+
+fn start_server() -> (AirmashGame, SocketAddr) {
+  let (mut game, addr) = utils::start_server();
+  game.resources.insert(RegionName("test-region".to_owned()));
+
+  (game, addr)
+}
+
+fn get(addr: SocketAddr, path: &str) -> String {
+  let mut stream = utils::connect(addr);
+
+  write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+
+  let mut response = String::new();
+  stream.read_to_string(&mut response).unwrap();
+  response
+}
+
+fn body(response: &str) -> serde_json::Value {
+  let (_, body) = response.split_once("\r\n\r\n").unwrap();
+  serde_json::from_str(body).unwrap()
+}
+
+#[test]
+fn status_endpoints() {
+  let (mut game, addr) = start_server();
+
+  // Nothing has been published before the first frame.
+  assert!(get(addr, "/health").starts_with("HTTP/1.0 503"));
+
+  game
+    .resources
+    .write::<TeamScores>()
+    .insert("red".to_owned(), 2);
+  game.run_once(Instant::now());
+
+  let response = get(addr, "/status");
+  assert!(response.starts_with("HTTP/1.0 200 OK"), "{}", response);
+  let status = body(&response);
+  assert_eq!(status["region"], "test-region");
+  assert_eq!(status["game_type"], "ffa");
+  assert_eq!(status["players"], 0);
+  assert_eq!(status["scores"]["red"], 2);
+
+  // The root path serves the same status for compatibility with older tools
+  // that expect a `players` field.
+  assert_eq!(body(&get(addr, "/"))["players"], 0);
+
+  let response = get(addr, "/players");
+  assert!(response.starts_with("HTTP/1.0 200 OK"), "{}", response);
+  assert_eq!(body(&response), serde_json::json!([]));
+
+  assert!(get(addr, "/health").starts_with("HTTP/1.0 200 OK"));
+  assert!(get(addr, "/nonexistent").starts_with("HTTP/1.0 404"));
+}
+
+#[test]
+fn metrics_endpoint() {
+  let (mut game, addr) = start_server();
+  game.run_once(Instant::now());
+
+  let response = get(addr, "/metrics");
+  assert!(response.starts_with("HTTP/1.0 200 OK"), "{}", response);
+  assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+  assert!(response.contains("# TYPE airmash_frame_duration_seconds histogram"));
+  // Metrics are published partway through the frame so only the stages that
+  // ran before then will have been recorded.
+  assert!(response.contains("airmash_stage_duration_seconds_count{stage=\"physics\"} 1"));
+  assert!(response.contains("airmash_players 0"));
+}
@@ -0,0 +1,59 @@
+use std::net::SocketAddr;
+
+use airmash::network::AllowedOrigins;
+
+mod utils;
+
+/// Send a websocket upgrade request on a new connection and return the status
+/// line of the response.
+fn upgrade(addr: SocketAddr, headers: &str) -> String {
+  utils::upgrade(&mut utils::connect(addr), headers)
+}
+
+#[test]
+fn origins_are_checked_against_allow_list() {
+  let (game, addr) = utils::start_server();
+
+  let origins = game.resources.read::<AllowedOrigins>().clone();
+
+  // Everything is allowed until a list is set.
+  let response = upgrade(addr, "Origin: https://evil.example\r\n");
+  assert!(response.starts_with("HTTP/1.1 101"), "{}", response);
+
+  origins.set(["https://airmash.online", "https://*.airmash.online"]);
+
+  let allowed = [
+    "Origin: https://airmash.online\r\n",
+    "Origin: https://Test.AIRMASH.online\r\n",
+    // Non-browser clients don't send an origin.
+    "",
+  ];
+  for headers in allowed {
+    let response = upgrade(addr, headers);
+    assert!(
+      response.starts_with("HTTP/1.1 101"),
+      "{:?}: {}",
+      headers,
+      response
+    );
+  }
+
+  let rejected = [
+    "Origin: https://evil.example\r\n",
+    "Origin: http://airmash.online\r\n",
+    "Origin: https://airmash.online.evil.example\r\n",
+  ];
+  for headers in rejected {
+    let response = upgrade(addr, headers);
+    assert!(
+      response.starts_with("HTTP/1.0 403"),
+      "{:?}: {}",
+      headers,
+      response
+    );
+  }
+
+  origins.allow_all();
+  let response = upgrade(addr, "Origin: https://evil.example\r\n");
+  assert!(response.starts_with("HTTP/1.1 101"), "{}", response);
+}
@@ -0,0 +1,115 @@
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+
+use airmash::network::{ProxyConfig, ProxySettings};
+use airmash::resource::{BanList, BanTarget};
+use airmash::AirmashGame;
+
+mod utils;
+
+const BANNED: &str = "203.0.113.7";
+
+fn start_server(settings: ProxySettings) -> (AirmashGame, SocketAddr) {
+  let (game, addr) = utils::start_server();
+  game.resources.read::<ProxyConfig>().set(settings);
+  game
+    .resources
+    .read::<BanList>()
+    .ban(BanTarget::Ip(BANNED.parse().unwrap()), None);
+
+  (game, addr)
+}
+
+fn trusting(trusted: &str) -> ProxySettings {
+  ProxySettings {
+    proxy_protocol: false,
+    forwarded_headers: false,
+    trusted: airmash::network::IpCidr::parse_list(trusted).unwrap(),
+  }
+}
+
+/// Send `prefix` followed by a request for the health endpoint and return the
+/// response.
+fn request(addr: SocketAddr, prefix: &[u8], headers: &str) -> String {
+  let mut stream = utils::connect(addr);
+
+  // The server may close the connection before the whole request is written
+  // if it rejects the PROXY protocol header.
+  let request = format!("GET /health HTTP/1.1\r\nHost: localhost\r\n{}\r\n", headers);
+  let _ = stream
+    .write_all(prefix)
+    .and_then(|()| stream.write_all(request.as_bytes()));
+
+  let mut response = String::new();
+  let _ = stream.read_to_string(&mut response);
+  response
+}
+
+fn is_forbidden(response: &str) -> bool {
+  response.starts_with("HTTP/1.0 403")
+}
+
+#[test]
+fn proxy_protocol_v1() {
+  let (_game, addr) = start_server(ProxySettings {
+    proxy_protocol: true,
+    ..trusting("127.0.0.1")
+  });
+
+  let header = format!("PROXY TCP4 {} 127.0.0.1 40000 3501\r\n", BANNED);
+  assert!(is_forbidden(&request(addr, header.as_bytes(), "")));
+
+  let header = "PROXY TCP4 198.51.100.1 127.0.0.1 40000 3501\r\n";
+  assert!(!is_forbidden(&request(addr, header.as_bytes(), "")));
+
+  // The header is required from trusted proxies.
+  assert_eq!(request(addr, b"", ""), "");
+}
+
+#[test]
+fn proxy_protocol_v2() {
+  let (_game, addr) = start_server(ProxySettings {
+    proxy_protocol: true,
+    ..trusting("127.0.0.0/8")
+  });
+
+  let mut header = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+  // Version 2, PROXY command, TCP over IPv4 with 12 bytes of addresses.
+  header.extend_from_slice(&[0x21, 0x11, 0, 12]);
+  header.extend_from_slice(&[203, 0, 113, 7]);
+  header.extend_from_slice(&[127, 0, 0, 1]);
+  header.extend_from_slice(&40000u16.to_be_bytes());
+  header.extend_from_slice(&3501u16.to_be_bytes());
+
+  assert!(is_forbidden(&request(addr, &header, "")));
+}
+
+#[test]
+fn forwarded_headers_from_trusted_proxy() {
+  let (_game, addr) = start_server(ProxySettings {
+    forwarded_headers: true,
+    ..trusting("127.0.0.1,10.0.0.0/8")
+  });
+
+  let forwarded = format!("X-Forwarded-For: {}, 10.1.2.3\r\n", BANNED);
+  assert!(is_forbidden(&request(addr, b"", &forwarded)));
+
+  let real_ip = format!("X-Real-IP: {}\r\n", BANNED);
+  assert!(is_forbidden(&request(addr, b"", &real_ip)));
+
+  // Addresses before the first untrusted one could have been made up by the
+  // client.
+  let spoofed = format!("X-Forwarded-For: {}, 198.51.100.1\r\n", BANNED);
+  assert!(!is_forbidden(&request(addr, b"", &spoofed)));
+}
+
+#[test]
+fn forwarded_headers_from_untrusted_peer() {
+  let (_game, addr) = start_server(ProxySettings {
+    forwarded_headers: true,
+    ..trusting("10.0.0.0/8")
+  });
+
+  let forwarded = format!("X-Forwarded-For: {}\r\n", BANNED);
+  assert!(!is_forbidden(&request(addr, b"", &forwarded)));
+}
@@ -1,3 +1,5 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 use std::time::Duration;
 
 use airmash::protocol::server::ServerMessage;
@@ -12,18 +14,18 @@ fn tasks_obey_test_time() {
   let mut conn = mock.open();
   conn.login("test", &mut game);
 
-  unsafe {
-    let sched = game.resources.read::<TaskScheduler>().clone();
-    sched.spawn(move |mut game| async move {
-      game.sleep_for(Duration::from_secs(5)).await;
+  let sched = game.resources.read::<TaskScheduler>().clone();
+  sched.spawn(move |mut game| async move {
+    game.sleep_for(Duration::from_secs(5)).await;
 
+    game.with(|game| {
       game.send_to_all(ServerMessage {
         ty: airmash_protocol::ServerMessageType::Banner,
         text: "test-message".into(),
         duration: 1000,
-      });
+      })
     });
-  }
+  });
 
   game.run_for(Duration::from_secs(7));
 
@@ -36,3 +38,170 @@ fn tasks_obey_test_time() {
     .is_some();
   assert!(found, "Server message not found");
 }
+
+#[test]
+fn cancelled_tasks_stop_running() {
+  let (mut game, _mock) = TestGame::new();
+
+  let steps = Rc::new(Cell::new(0));
+  let counter = steps.clone();
+
+  let sched = game.resources.read::<TaskScheduler>().clone();
+  let handle = sched.spawn(move |mut game| async move {
+    loop {
+      counter.set(counter.get() + 1);
+      game.sleep_for(Duration::from_secs(1)).await;
+    }
+  });
+
+  game.run_for(Duration::from_millis(2500));
+  assert_eq!(steps.get(), 3);
+  assert!(!handle.is_finished());
+
+  handle.cancel();
+  game.run_for(Duration::from_secs(3));
+  assert_eq!(steps.get(), 3);
+  assert!(handle.is_finished());
+}
+
+#[test]
+fn named_tasks_can_be_found() {
+  let (mut game, _mock) = TestGame::new();
+
+  let sched = game.resources.read::<TaskScheduler>().clone();
+  sched
+    .task()
+    .name("countdown")
+    .spawn(move |mut game| async move {
+      game.sleep_for(Duration::from_secs(2)).await;
+    });
+
+  game.run_once();
+  let handle = sched.find("countdown").expect("task was not found");
+  assert_eq!(handle.name(), Some("countdown"));
+  assert!(sched.find("other").is_none());
+
+  game.run_for(Duration::from_secs(3));
+  assert!(handle.is_finished());
+  assert!(sched.find("countdown").is_none());
+  assert!(sched.tasks().is_empty());
+}
+
+#[test]
+fn entity_tasks_stop_when_the_entity_despawns() {
+  let (mut game, mut mock) = TestGame::new();
+
+  let player = mock.open().login("test", &mut game);
+  let finished = Rc::new(Cell::new(false));
+  let flag = finished.clone();
+
+  let sched = game.resources.read::<TaskScheduler>().clone();
+  let handle = sched
+    .task()
+    .entity(player)
+    .spawn(move |mut game| async move {
+      game.sleep_for(Duration::from_secs(2)).await;
+      flag.set(true);
+    });
+
+  game.run_once();
+  game.despawn(player);
+  game.run_for(Duration::from_secs(3));
+
+  assert!(handle.is_finished());
+  assert!(!finished.get());
+}
+
+#[test]
+#[should_panic(expected = "outside of a task")]
+fn game_cannot_be_accessed_outside_a_task() {
+  let (mut game, _mock) = TestGame::new();
+
+  let escaped = Rc::new(RefCell::new(None));
+  let slot = escaped.clone();
+
+  let sched = game.resources.read::<TaskScheduler>().clone();
+  sched.spawn(move |game| async move {
+    *slot.borrow_mut() = Some(game);
+  });
+  game.run_once();
+
+  let mut game_ref = escaped.borrow_mut().take().unwrap();
+  game_ref.with(|game| game.this_frame());
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Ping(u32);
+
+#[test]
+fn wait_for_resolves_on_matching_event() {
+  let (mut game, _mock) = TestGame::new();
+
+  let received = Rc::new(Cell::new(None));
+  let slot = received.clone();
+
+  let sched = game.resources.read::<TaskScheduler>().clone();
+  sched.spawn(move |game| async move {
+    let ping = game.wait_for(|ping: &Ping| ping.0 == 2).await;
+    slot.set(Some(ping));
+  });
+  game.run_once();
+
+  game.dispatch(Ping(1));
+  game.run_once();
+  assert_eq!(received.get(), None);
+
+  game.dispatch(Ping(2));
+  game.run_once();
+  assert_eq!(received.get(), Some(Ping(2)));
+}
+
+#[test]
+fn wait_for_can_time_out() {
+  let (mut game, _mock) = TestGame::new();
+
+  let results = Rc::new(RefCell::new(Vec::new()));
+  let slot = results.clone();
+
+  let sched = game.resources.read::<TaskScheduler>().clone();
+  sched.spawn(move |mut game| async move {
+    let first = game
+      .timeout(Duration::from_secs(2), game.wait_for(|_: &Ping| true))
+      .await;
+    slot.borrow_mut().push(first);
+
+    let second = game
+      .timeout(Duration::from_secs(2), game.wait_for(|_: &Ping| true))
+      .await;
+    slot.borrow_mut().push(second);
+  });
+
+  game.run_for(Duration::from_secs(3));
+  assert_eq!(*results.borrow(), vec![None]);
+
+  game.dispatch(Ping(5));
+  game.run_once();
+  assert_eq!(*results.borrow(), vec![None, Some(Ping(5))]);
+}
+
+#[test]
+fn events_dispatched_by_tasks_wake_other_tasks() {
+  let (mut game, _mock) = TestGame::new();
+
+  let received = Rc::new(Cell::new(false));
+  let flag = received.clone();
+
+  let sched = game.resources.read::<TaskScheduler>().clone();
+  sched.spawn(move |game| async move {
+    game.wait_for(|_: &Ping| true).await;
+    flag.set(true);
+  });
+  game.run_once();
+
+  sched.spawn(move |mut game| async move {
+    game.with(|game| game.dispatch(Ping(0)));
+  });
+  game.run_count(2);
+
+  assert!(received.get());
+}
@@ -0,0 +1,119 @@
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use airmash::network::TlsConfig;
+use airmash::AirmashGame;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{Certificate, ClientConfig, RootCertStore, ServerName};
+use tokio_rustls::TlsConnector;
+
+use crate::utils::{free_addr, temp_path};
+
+mod utils;
+
+struct SelfSigned {
+  der: Vec<u8>,
+  cert_path: PathBuf,
+  key_path: PathBuf,
+}
+
+fn write_self_signed(name: &str) -> SelfSigned {
+  let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+  let cert_path = temp_path(&format!("{}.crt", name));
+  let key_path = temp_path(&format!("{}.key", name));
+
+  std::fs::write(&cert_path, cert.serialize_pem().unwrap()).unwrap();
+  std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+
+  SelfSigned {
+    der: cert.serialize_der().unwrap(),
+    cert_path,
+    key_path,
+  }
+}
+
+async fn connect(
+  addr: SocketAddr,
+  root: &[u8],
+) -> std::io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+  let mut roots = RootCertStore::empty();
+  roots.add(&Certificate(root.to_vec())).unwrap();
+
+  let config = ClientConfig::builder()
+    .with_safe_defaults()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+  let connector = TlsConnector::from(Arc::new(config));
+
+  let stream = utils::connect(addr);
+  stream.set_nonblocking(true)?;
+  let stream = TcpStream::from_std(stream)?;
+
+  let domain = ServerName::try_from("localhost").unwrap();
+  connector.connect(domain, stream).await
+}
+
+#[tokio::test]
+async fn websocket_over_tls() {
+  let cert = write_self_signed("wss");
+  let addr = free_addr();
+  let tls = TlsConfig::load(&cert.cert_path, &cert.key_path).unwrap();
+  let game = AirmashGame::with_tls_network(addr, tls);
+
+  let stream = connect(addr, &cert.der).await.unwrap();
+  let (_ws, response) = tokio_tungstenite::client_async("wss://localhost/", stream)
+    .await
+    .unwrap();
+  assert_eq!(response.status(), 101);
+
+  drop(game);
+}
+
+#[tokio::test]
+async fn status_over_tls() {
+  let cert = write_self_signed("status");
+  let addr = free_addr();
+  let tls = TlsConfig::load(&cert.cert_path, &cert.key_path).unwrap();
+  let game = AirmashGame::with_tls_network(addr, tls);
+
+  let mut stream = connect(addr, &cert.der).await.unwrap();
+  stream
+    .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+    .await
+    .unwrap();
+
+  let mut response = String::new();
+  stream.read_to_string(&mut response).await.unwrap();
+  assert!(response.starts_with("HTTP/1.0 200 OK"), "{}", response);
+
+  drop(game);
+}
+
+#[tokio::test]
+async fn certificate_can_be_reloaded() {
+  let old = write_self_signed("reload");
+  let addr = free_addr();
+  let tls = TlsConfig::load(&old.cert_path, &old.key_path).unwrap();
+  let game = AirmashGame::with_tls_network(addr, tls.clone());
+
+  connect(addr, &old.der).await.unwrap();
+
+  let new = write_self_signed("reload");
+  tls.reload().unwrap();
+
+  assert!(connect(addr, &old.der).await.is_err());
+  connect(addr, &new.der).await.unwrap();
+
+  drop(game);
+}
+
+#[test]
+fn invalid_files_are_rejected() {
+  let cert = write_self_signed("invalid");
+
+  assert!(TlsConfig::load(&cert.key_path, &cert.key_path).is_err());
+  assert!(TlsConfig::load(&cert.cert_path, &cert.cert_path).is_err());
+}
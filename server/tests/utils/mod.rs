@@ -1,5 +1,17 @@
+//! Helpers shared by the integration tests. Each test binary only uses some
+//! of them.
+#![allow(dead_code)]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use airmash::event::ServerStartup;
 use airmash::protocol::client as c;
+use airmash::resource::Config;
 use airmash::test::{MockConnection, MockConnectionEndpoint, TestGame};
+use airmash::AirmashGame;
 use airmash_protocol::ServerPacket;
 
 pub fn create_login_packet(name: &str) -> c::Login {
@@ -25,3 +37,69 @@ pub fn get_login_id(mock: &mut MockConnection) -> u16 {
 pub fn create_mock_server() -> (TestGame, MockConnectionEndpoint) {
   TestGame::new()
 }
+
+/// A path in the temp directory that is unique to this test process.
+pub fn temp_path(file: &str) -> PathBuf {
+  std::env::temp_dir().join(format!("airmash-{}-{}", std::process::id(), file))
+}
+
+/// An address on localhost that a server can listen on.
+pub fn free_addr() -> SocketAddr {
+  TcpListener::bind("127.0.0.1:0")
+    .unwrap()
+    .local_addr()
+    .unwrap()
+}
+
+/// Connect to a server that was just started.
+pub fn connect(addr: SocketAddr) -> TcpStream {
+  // The server thread may not have bound the port yet.
+  let mut attempts = 0;
+  loop {
+    match TcpStream::connect(addr) {
+      Ok(stream) => return stream,
+      Err(e) if attempts > 50 => panic!("unable to connect to server: {}", e),
+      Err(_) => {
+        attempts += 1;
+        std::thread::sleep(Duration::from_millis(20));
+      }
+    }
+  }
+}
+
+/// Start a server with the default config listening on a free port.
+pub fn start_server() -> (AirmashGame, SocketAddr) {
+  let addr = free_addr();
+  let mut game = AirmashGame::with_network(addr);
+  game
+    .resources
+    .insert(Config::new(Default::default()).unwrap());
+  game.dispatch(ServerStartup);
+
+  (game, addr)
+}
+
+/// Send a websocket upgrade request with the given extra headers and return
+/// the status line of the response.
+pub fn upgrade(stream: &mut TcpStream, headers: &str) -> String {
+  write!(
+    stream,
+    "GET / HTTP/1.1\r\n\
+    Host: localhost\r\n\
+    Connection: Upgrade\r\n\
+    Upgrade: websocket\r\n\
+    Sec-WebSocket-Version: 13\r\n\
+    Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+    {}\r\n",
+    headers
+  )
+  .unwrap();
+
+  let mut response = Vec::new();
+  let mut byte = [0u8; 1];
+  while !response.ends_with(b"\r\n") && stream.read(&mut byte).unwrap() == 1 {
+    response.push(byte[0]);
+  }
+
+  String::from_utf8(response).unwrap()
+}